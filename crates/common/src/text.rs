@@ -1,3 +1,4 @@
 use bevy::prelude::*;
 
 pub const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+pub const LINK_COLOR: Color = Color::rgb(0.45, 0.7, 1.0);
@@ -0,0 +1,652 @@
+use std::{cmp::Ordering, fmt, str::FromStr, sync::Arc};
+
+use crate::{
+    chapters::{
+        chapter::Chapter,
+        chapter_node::{displayed_text, ChapterNode, ChapterNodeChild, SOFT_HYPHEN},
+    },
+    error::EpubError,
+};
+
+/// Index of the `<spine>` element among the children of `<package>`, the first step of every CFI
+const SPINE_STEP: usize = 6;
+
+/// Characters which have to be escaped with `^` inside of the assertions
+const SPECIAL_CHARACTERS: [char; 8] = ['^', '[', ']', '(', ')', ',', ';', '='];
+
+/// Reading location following the EPUB Canonical Fragment Identifier spec,
+/// e.g. `epubcfi(/6/4[chapter_1]!/4[body]/10[para05]/3:10)`.
+/// Offsets count UTF-16 code units of the text as it is in the document, including soft hyphens,
+/// the same as the DOM offsets used by other reading systems.
+/// Ranges, spatial and temporal offsets and text assertions are not supported.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cfi {
+    /// Position of the itemref in the spine, the first one is pointed to with `/6/2`
+    pub spine_position: usize,
+    /// Id asserted on the itemref step, used to find the document when the spine has changed
+    pub spine_item_id: Option<String>,
+    /// Steps from the root element of the content document, `/4` points to the `<body>`
+    pub steps: Vec<CfiStep>,
+    /// Offset in UTF-16 code units inside of the text the last step points to
+    pub offset: Option<usize>,
+}
+
+/// Single step of the path, e.g. `/10[para05]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CfiStep {
+    /// Even indices point to the child elements, `/2` being the first one.
+    /// Odd indices point to the text placed between them, `/1` being the text before the first element.
+    pub index: usize,
+    /// Id asserted on the element, used to find it when the document has changed
+    pub id: Option<String>,
+}
+
+/// Place of the document a CFI points to
+#[derive(Debug, Clone)]
+pub struct CfiTarget {
+    pub node: Arc<ChapterNode>,
+    /// Character offset inside of the `text_content` of the node, `None` when the CFI points to the element itself
+    pub offset: Option<usize>,
+}
+
+impl CfiStep {
+    fn element(position: usize, node: &ChapterNode) -> CfiStep {
+        CfiStep {
+            index: (position + 1) * 2,
+            id: node.id().map(|id| id.to_string()),
+        }
+    }
+
+    fn text(elements_before: usize) -> CfiStep {
+        CfiStep {
+            index: elements_before * 2 + 1,
+            id: None,
+        }
+    }
+}
+
+impl Cfi {
+    /// Location of the element, e.g. of the `<body>` when a document is opened from its beginning
+    pub fn from_node(
+        spine_position: usize,
+        spine_item_id: Option<&str>,
+        node: &Arc<ChapterNode>,
+    ) -> Cfi {
+        Cfi {
+            spine_position,
+            spine_item_id: spine_item_id.map(|id| id.to_string()),
+            steps: steps_to(node),
+            offset: None,
+        }
+    }
+
+    /// Location of the character placed at `offset` of the `text_content` of the node.
+    /// Offsets past the end of the text point to the end of the last text of the node.
+    pub fn from_text_offset(
+        spine_position: usize,
+        spine_item_id: Option<&str>,
+        node: &Arc<ChapterNode>,
+        offset: usize,
+    ) -> Cfi {
+        let mut steps = steps_to(node);
+        let (text_steps, text_offset) = text_steps(node, offset);
+        steps.extend(text_steps);
+
+        Cfi {
+            spine_position,
+            spine_item_id: spine_item_id.map(|id| id.to_string()),
+            steps,
+            offset: Some(text_offset),
+        }
+    }
+
+    /// Finds the place of the chapter the CFI points to. Elements are found by their indices,
+    /// unless they do not have the asserted id and an element with this id exists in the chapter.
+    pub fn resolve(&self, chapter: &Chapter) -> Option<CfiTarget> {
+        let mut node = document_element(chapter)?;
+
+        for (position, step) in self.steps.iter().enumerate() {
+            if step.index % 2 == 1 {
+                // Text is not a container, so the step pointing to it has to be the last one
+                if position != self.steps.len() - 1 {
+                    return None;
+                }
+
+                let offset =
+                    text_content_offset(&node, step.index / 2, self.offset.unwrap_or_default())?;
+                return Some(CfiTarget {
+                    node,
+                    offset: Some(offset),
+                });
+            }
+
+            let child = (step.index / 2)
+                .checked_sub(1)
+                .and_then(|child_position| node.get_children().get(child_position).cloned());
+
+            node = match (child, step.id.as_deref()) {
+                (Some(child), Some(id)) if child.id() == Some(id) => child,
+                (child, Some(id)) => chapter.find_by_id(id).or(child)?,
+                (child, None) => child?,
+            };
+        }
+
+        Some(CfiTarget {
+            node,
+            offset: self.offset,
+        })
+    }
+}
+
+impl fmt::Display for Cfi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let spine_step = CfiStep {
+            index: (self.spine_position + 1) * 2,
+            id: self.spine_item_id.clone(),
+        };
+
+        write!(f, "epubcfi(/{}{}!", SPINE_STEP, spine_step)?;
+        for step in &self.steps {
+            write!(f, "{}", step)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, ":{}", offset)?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for CfiStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}", self.index)?;
+
+        if let Some(id) = &self.id {
+            write!(f, "[{}]", escape(id))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Cfi {
+    type Err = EpubError;
+
+    /// Parses the CFI with or without the `epubcfi(...)` wrapper, e.g. taken from the fragment of a link
+    fn from_str(value: &str) -> Result<Cfi, EpubError> {
+        let invalid = || EpubError::InvalidCfi(value.to_string());
+
+        let trimmed = value.trim().trim_start_matches('#');
+        let path = match trimmed.strip_prefix("epubcfi(") {
+            Some(wrapped) => wrapped.strip_suffix(')').ok_or_else(invalid)?,
+            None => trimmed,
+        };
+
+        let mut parser = CfiParser {
+            characters: path.chars().collect(),
+            position: 0,
+        };
+
+        let package_steps = parser.steps().ok_or_else(invalid)?;
+        if !parser.consume('!') {
+            return Err(invalid());
+        }
+        let steps = parser.steps().ok_or_else(invalid)?;
+        let offset = if parser.consume(':') {
+            let offset = parser.number().ok_or_else(invalid)?;
+            // Text assertions and side bias do not change the location
+            parser.assertion().ok_or_else(invalid)?;
+            Some(offset)
+        } else {
+            None
+        };
+
+        if !parser.is_finished() || steps.iter().any(|step| step.index == 0) {
+            return Err(invalid());
+        }
+
+        let spine_step = match package_steps.as_slice() {
+            [.., spine_step] if package_steps.len() >= 2 && spine_step.index % 2 == 0 => spine_step,
+            _ => return Err(invalid()),
+        };
+
+        Ok(Cfi {
+            spine_position: (spine_step.index / 2).checked_sub(1).ok_or_else(invalid)?,
+            spine_item_id: spine_step.id.clone(),
+            steps,
+            offset,
+        })
+    }
+}
+
+impl PartialOrd for Cfi {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cfi {
+    /// Orders the locations as they appear in the book: an element comes before its content, which comes before
+    /// the following siblings. Assertions are compared only to tell apart the same locations asserting other ids.
+    fn cmp(&self, other: &Self) -> Ordering {
+        let indices = |cfi: &Cfi| {
+            cfi.steps
+                .iter()
+                .map(|step| step.index)
+                .collect::<Vec<usize>>()
+        };
+        let ids = |cfi: &Cfi| {
+            cfi.steps
+                .iter()
+                .map(|step| step.id.clone())
+                .collect::<Vec<Option<String>>>()
+        };
+
+        self.spine_position
+            .cmp(&other.spine_position)
+            .then_with(|| indices(self).cmp(&indices(other)))
+            .then_with(|| self.offset.cmp(&other.offset))
+            .then_with(|| self.spine_item_id.cmp(&other.spine_item_id))
+            .then_with(|| ids(self).cmp(&ids(other)))
+    }
+}
+
+struct CfiParser {
+    characters: Vec<char>,
+    position: usize,
+}
+
+impl CfiParser {
+    fn steps(&mut self) -> Option<Vec<CfiStep>> {
+        let mut steps = vec![];
+
+        while self.consume('/') {
+            let index = self.number()?;
+            let id = self.assertion()?;
+
+            steps.push(CfiStep { index, id });
+        }
+
+        Some(steps)
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+
+        self.characters[start..self.position]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    /// Parses the optional `[...]` assertion and returns its value before the parameters, e.g. `id` of `[id;s=a]`.
+    /// Returns `None` when the assertion is not closed.
+    fn assertion(&mut self) -> Option<Option<String>> {
+        if !self.consume('[') {
+            return Some(None);
+        }
+
+        let mut value = String::new();
+        let mut in_parameters = false;
+
+        loop {
+            match self.next()? {
+                '^' => {
+                    let escaped = self.next()?;
+                    if !in_parameters {
+                        value.push(escaped);
+                    }
+                }
+                ']' => break,
+                ';' | ',' => in_parameters = true,
+                c if !in_parameters => value.push(c),
+                _ => {}
+            }
+        }
+
+        Some(Some(value).filter(|value| !value.is_empty()))
+    }
+
+    fn consume(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+
+        Some(c)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.characters.get(self.position).copied()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.position == self.characters.len()
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| {
+            let escape = SPECIAL_CHARACTERS.contains(&c).then_some('^');
+            escape.into_iter().chain(std::iter::once(c))
+        })
+        .collect()
+}
+
+/// Root element of the document, which is the starting point of the steps, usually `<html>`
+fn document_element(chapter: &Chapter) -> Option<Arc<ChapterNode>> {
+    chapter.recreated_structure.get_children().first().cloned()
+}
+
+/// Steps leading from the root element of the document to the node
+fn steps_to(node: &Arc<ChapterNode>) -> Vec<CfiStep> {
+    let mut steps = vec![];
+    let mut current = Arc::clone(node);
+
+    while let Some(parent) = current.get_parent().upgrade() {
+        // The root element is a child of the node holding the whole document and is not a part of the path
+        if parent.get_parent().upgrade().is_none() {
+            break;
+        }
+
+        let position = parent
+            .get_children()
+            .iter()
+            .position(|child| Arc::ptr_eq(child, &current))
+            .unwrap_or_default();
+        steps.push(CfiStep::element(position, &current));
+
+        current = parent;
+    }
+
+    steps.reverse();
+    steps
+}
+
+/// Steps leading from the node to the text containing the character at `offset` of its `text_content`,
+/// together with the offset of the character inside of that text, as it is in the document
+fn text_steps(node: &ChapterNode, offset: usize) -> (Vec<CfiStep>, usize) {
+    let mut consumed = 0;
+    let mut elements_before = 0;
+    let mut trailing_text = None;
+
+    for child in node.get_child_nodes() {
+        match child {
+            ChapterNodeChild::Text(text) => {
+                let length = displayed_text(&text).chars().count();
+                if offset < consumed + length {
+                    return (
+                        vec![CfiStep::text(elements_before)],
+                        document_offset(&text, offset - consumed),
+                    );
+                }
+                consumed += length;
+                trailing_text = Some(text);
+            }
+            ChapterNodeChild::Element(element) => {
+                let length = element.text_content().chars().count();
+                if offset < consumed + length {
+                    let (mut steps, text_offset) = text_steps(&element, offset - consumed);
+                    steps.insert(0, CfiStep::element(elements_before, &element));
+
+                    return (steps, text_offset);
+                }
+                consumed += length;
+                elements_before += 1;
+                trailing_text = None;
+            }
+        }
+    }
+
+    let end_offset = trailing_text.map_or(0, |text| text.encode_utf16().count());
+
+    (vec![CfiStep::text(elements_before)], end_offset)
+}
+
+/// Offset inside of the `text_content` of the node of the character placed at `text_offset` of the text
+/// following `elements_before` child elements
+fn text_content_offset(
+    node: &ChapterNode,
+    elements_before: usize,
+    text_offset: usize,
+) -> Option<usize> {
+    let children = node.get_children();
+    if elements_before > children.len() {
+        return None;
+    }
+
+    let mut offset = 0;
+    let mut elements_seen = 0;
+
+    for child in node.get_child_nodes() {
+        match child {
+            ChapterNodeChild::Text(text) if elements_seen == elements_before => {
+                return Some(offset + displayed_offset(&text, text_offset));
+            }
+            ChapterNodeChild::Text(text) => offset += displayed_text(&text).chars().count(),
+            ChapterNodeChild::Element(_) if elements_seen == elements_before => break,
+            ChapterNodeChild::Element(element) => {
+                offset += element.text_content().chars().count();
+                elements_seen += 1;
+            }
+        }
+    }
+
+    Some(offset)
+}
+
+/// Offset in UTF-16 code units of the text as it is in the document, of the character placed at `offset`
+/// of its displayed text, which does not contain soft hyphens
+fn document_offset(text: &str, offset: usize) -> usize {
+    let mut displayed = 0;
+    let mut document_offset = 0;
+
+    for c in text.chars() {
+        if c != SOFT_HYPHEN {
+            if displayed == offset {
+                break;
+            }
+            displayed += 1;
+        }
+        document_offset += c.len_utf16();
+    }
+
+    document_offset
+}
+
+/// Number of displayed characters placed before `document_offset`, given in UTF-16 code units of the text
+fn displayed_offset(text: &str, document_offset: usize) -> usize {
+    let mut displayed = 0;
+    let mut consumed = 0;
+
+    for c in text.chars() {
+        if consumed >= document_offset {
+            break;
+        }
+        consumed += c.len_utf16();
+        if c != SOFT_HYPHEN {
+            displayed += 1;
+        }
+    }
+
+    displayed
+}
+
+#[cfg(test)]
+mod cfi_tests {
+    use super::*;
+    use crate::test_utils::chapter_from_content;
+
+    const CHAPTER_CONTENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml"><head><title>Loomings</title></head><body id="body01"><h1>Loomings</h1><p id="para02">Call me <em>Ishmael</em>. Some years ago</p><p id="para03">Har&#173;poon 𝄞 whale</p></body></html>"#;
+
+    #[test]
+    fn should_round_trip_cfi_of_other_readers() {
+        //arrange
+        let values = [
+            "epubcfi(/6/4[chap01ref]!/4[body01]/10[para05]/3:10)",
+            "epubcfi(/6/2!/4/2/1:0)",
+            "epubcfi(/6/14[id^[1^]]!/4)",
+        ];
+
+        //act
+        let round_tripped = values
+            .iter()
+            .map(|value| value.parse::<Cfi>().unwrap().to_string())
+            .collect::<Vec<String>>();
+
+        //assert
+        assert_eq!(round_tripped, values);
+    }
+
+    #[test]
+    fn should_parse_steps_and_offset() {
+        //act
+        let cfi: Cfi = "#epubcfi(/6/4[chap01ref]!/4[body01]/10[para05]/3:10[yes,no;s=a])"
+            .parse()
+            .unwrap();
+
+        //assert
+        assert_eq!(cfi.spine_position, 1);
+        assert_eq!(cfi.spine_item_id.as_deref(), Some("chap01ref"));
+        assert_eq!(
+            cfi.steps,
+            vec![
+                CfiStep {
+                    index: 4,
+                    id: Some("body01".to_string())
+                },
+                CfiStep {
+                    index: 10,
+                    id: Some("para05".to_string())
+                },
+                CfiStep { index: 3, id: None },
+            ]
+        );
+        assert_eq!(cfi.offset, Some(10));
+    }
+
+    #[test]
+    fn should_reject_invalid_cfi() {
+        for value in [
+            "",
+            "epubcfi(/6/4)",
+            "epubcfi(/4!/4)",
+            "epubcfi(/6/4!/4/0)",
+            "epubcfi(/6/4!/4[body)",
+            "epubcfi(/6/4!/4,/2/1:0,/2/1:5)",
+        ] {
+            assert!(
+                matches!(value.parse::<Cfi>(), Err(EpubError::InvalidCfi(_))),
+                "{} should be rejected",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn should_compare_locations_in_reading_order() {
+        //arrange
+        let parse = |value: &str| value.parse::<Cfi>().unwrap();
+
+        //assert
+        assert!(parse("epubcfi(/6/2!/4/10/3:10)") < parse("epubcfi(/6/4!/4/2)"));
+        assert!(parse("epubcfi(/6/4!/4/2)") < parse("epubcfi(/6/4!/4/2/1:0)"));
+        assert!(parse("epubcfi(/6/4!/4/2/1:0)") < parse("epubcfi(/6/4!/4/2/1:5)"));
+        assert!(parse("epubcfi(/6/4!/4/2/1:5)") < parse("epubcfi(/6/4!/4/4)"));
+        assert_eq!(
+            parse("epubcfi(/6/4!/4/2/1:5)").cmp(&parse("epubcfi(/6/4!/4/2/1:5)")),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn should_generate_and_resolve_text_locations() {
+        //arrange
+        let chapter = chapter_from_content("OEBPS/chapter_1.xhtml", CHAPTER_CONTENT);
+        let paragraph = chapter.find_by_id("para02").unwrap();
+        let ishmael_offset = "Call me ".len();
+        let years_offset = "Call me Ishmael. Some ".len();
+
+        //act
+        let ishmael_cfi = Cfi::from_text_offset(0, Some("chapter_1"), &paragraph, ishmael_offset);
+        let years_cfi = Cfi::from_text_offset(0, Some("chapter_1"), &paragraph, years_offset);
+        let ishmael_target = ishmael_cfi.resolve(&chapter).unwrap();
+        let years_target = years_cfi.resolve(&chapter).unwrap();
+
+        //assert
+        assert_eq!(
+            ishmael_cfi.to_string(),
+            "epubcfi(/6/2[chapter_1]!/4[body01]/4[para02]/2/1:0)"
+        );
+        assert_eq!(
+            years_cfi.to_string(),
+            "epubcfi(/6/2[chapter_1]!/4[body01]/4[para02]/3:7)"
+        );
+        assert_eq!(ishmael_target.node.tag, "em");
+        assert_eq!(ishmael_target.offset, Some(0));
+        assert!(Arc::ptr_eq(&years_target.node, &paragraph));
+        assert_eq!(years_target.offset, Some(years_offset));
+    }
+
+    #[test]
+    fn should_count_offsets_in_utf16_units_of_the_document_text() {
+        //arrange
+        let chapter = chapter_from_content("OEBPS/chapter_1.xhtml", CHAPTER_CONTENT);
+        let paragraph = chapter.find_by_id("para03").unwrap();
+        let whale_offset = "Harpoon \u{1d11e} ".chars().count();
+        let saved_by_other_reader: Cfi = "epubcfi(/6/2!/4[body01]/6[para03]/1:12)".parse().unwrap();
+
+        //act
+        let whale_cfi = Cfi::from_text_offset(0, None, &paragraph, whale_offset);
+        let whale_target = saved_by_other_reader.resolve(&chapter).unwrap();
+
+        //assert
+        assert_eq!(whale_cfi, saved_by_other_reader);
+        assert!(Arc::ptr_eq(&whale_target.node, &paragraph));
+        assert_eq!(whale_target.offset, Some(whale_offset));
+        assert_eq!(
+            paragraph.text_content().chars().nth(whale_offset),
+            Some('w')
+        );
+    }
+
+    #[test]
+    fn should_resolve_elements_by_asserted_ids_when_indices_changed() {
+        //arrange
+        let chapter = chapter_from_content("OEBPS/chapter_1.xhtml", CHAPTER_CONTENT);
+        let element_cfi = Cfi::from_node(0, None, &chapter.find_by_id("para02").unwrap());
+        let moved_cfi: Cfi = "epubcfi(/6/2!/4[body01]/8[para02])".parse().unwrap();
+
+        //act
+        let element_target = element_cfi.resolve(&chapter).unwrap();
+        let moved_target = moved_cfi.resolve(&chapter).unwrap();
+
+        //assert
+        assert_eq!(
+            element_cfi.to_string(),
+            "epubcfi(/6/2!/4[body01]/4[para02])"
+        );
+        assert_eq!(element_target.node.id(), Some("para02"));
+        assert_eq!(element_target.offset, None);
+        assert_eq!(moved_target.node.id(), Some("para02"));
+        assert!("epubcfi(/6/2!/4/8)"
+            .parse::<Cfi>()
+            .unwrap()
+            .resolve(&chapter)
+            .is_none());
+    }
+}
@@ -1,9 +1,11 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
+use scraper::{ElementRef, Html, Node};
 
 use crate::epub::EBook;
+use crate::error::EpubError;
 use crate::table_of_contents::table_of_contents_item::TableOfContentsItem;
 
 use super::chapter_node::ChapterNode;
@@ -13,6 +15,8 @@ pub struct Chapter {
     pub path: String,
     pub label: String,
     pub recreated_structure: Arc<ChapterNode>,
+    /// Problems found while parsing the content, e.g. stray closing tags. The structure is recreated despite them.
+    pub warnings: Vec<ChapterParseWarning>,
     pub(crate) _raw_content: String,
 }
 
@@ -22,83 +26,222 @@ impl PartialEq for Chapter {
     }
 }
 
+/// Parser used to recreate the structure of the chapter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChapterParsingMode {
+    /// Parses the content as XHTML, which is required by the spec.
+    /// Content that is not well formed is parsed again in the `Html` mode.
+    #[default]
+    Xhtml,
+    /// Parses the content as HTML5, accepting unclosed and stray tags, void elements like `<br>` and HTML entities
+    Html,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChapterParseWarning {
+    pub message: String,
+}
+
+impl ChapterParseWarning {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
 impl Chapter {
-    pub fn from_item(item: TableOfContentsItem, ebook: &mut EBook) -> Chapter {
+    pub fn from_item(item: TableOfContentsItem, ebook: &EBook) -> Result<Chapter, EpubError> {
         //TODO: Consider moving chapter creation to this method invocation, since from this point on TocItem is not itself anymore
-        let content = ebook.get_content_by_toc_item(&item).unwrap();
+        let content = ebook.get_content_by_toc_item(&item)?;
 
-        Chapter {
-            path: item.path.clone(),
-            label: item.label.clone(),
-            recreated_structure: Chapter::recreate_structure(&content),
-            _raw_content: content,
-        }
+        Ok(Chapter::from_item_with_content(item, content))
     }
 
     pub fn from_item_with_content(item: TableOfContentsItem, content: String) -> Chapter {
+        Chapter::from_item_with_content_and_mode(item, content, ChapterParsingMode::default())
+    }
+
+    pub fn from_item_with_content_and_mode(
+        item: TableOfContentsItem,
+        content: String,
+        mode: ChapterParsingMode,
+    ) -> Chapter {
+        let (recreated_structure, warnings) = Chapter::parse_structure(&content, mode);
+
         Chapter {
             path: item.path.clone(),
             label: item.label.clone(),
-            recreated_structure: Chapter::recreate_structure(&content),
+            recreated_structure,
+            warnings,
             _raw_content: content,
         }
     }
 
+    pub(crate) fn parse_structure(
+        chapter_content: &str,
+        mode: ChapterParsingMode,
+    ) -> (Arc<ChapterNode>, Vec<ChapterParseWarning>) {
+        if mode == ChapterParsingMode::Html {
+            return Chapter::recreate_structure_from_html(chapter_content);
+        }
+
+        match Chapter::recreate_structure_from_xhtml(chapter_content) {
+            Ok(result) => result,
+            Err(not_well_formed) => {
+                let (structure, mut warnings) =
+                    Chapter::recreate_structure_from_html(chapter_content);
+                warnings.insert(0, not_well_formed);
+
+                (structure, warnings)
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn recreate_structure(chapter_content: &str) -> Arc<ChapterNode> {
+        Chapter::parse_structure(chapter_content, ChapterParsingMode::Xhtml).0
+    }
+
     //TODO: Introduce placeholders for the children inside parents
     //TODO: Create a different version of this method that would flatten the structure, so every element should have a contnet,
     // then child content and at the end there would be still a place for parent content, resulting in 3 potential elements in a place of 1 and its children.
     // Alternatively, this can be dane in other method, when translating the structure to the elements in bevy
-    fn recreate_structure(chapter_content: &str) -> Arc<ChapterNode> {
+    /// Returns a warning describing the first error when the content is not well formed XHTML
+    fn recreate_structure_from_xhtml(
+        chapter_content: &str,
+    ) -> Result<(Arc<ChapterNode>, Vec<ChapterParseWarning>), ChapterParseWarning> {
         let root = Arc::new(ChapterNode::new("root".to_string(), vec![], String::new()));
         let mut current_node = root.clone();
+        let mut warnings: Vec<ChapterParseWarning> = vec![];
 
         let mut reader = Reader::from_str(chapter_content);
         reader.trim_text(false);
 
         let mut buf = Vec::new();
-        let mut tag: String;
-        let mut content: String;
 
-        while let Ok(event) = reader.read_event_into(&mut buf) {
+        let not_well_formed = |error: &dyn std::fmt::Display, position: usize| {
+            ChapterParseWarning::new(format!(
+                "content is not well formed XHTML ({} at byte {}), it was parsed as HTML",
+                error, position
+            ))
+        };
+
+        loop {
+            let event = match reader.read_event_into(&mut buf) {
+                Ok(event) => event,
+                Err(e) => return Err(not_well_formed(&e, reader.buffer_position())),
+            };
+
             match event {
                 Event::Start(ref e) => {
-                    let mut classes: Vec<String> = vec![];
-
-                    for attr in e.attributes() {
-                        let attr = attr.unwrap();
-                        let key = String::from_utf8(attr.key.0.to_vec()).unwrap();
-                        let value = String::from_utf8(attr.value.to_vec()).unwrap();
-
-                        println!("{}: {}", key, value);
-
-                        if key == "class" {
-                            classes = value.split_whitespace().map(|s| s.to_string()).collect();
-                        }
-                    }
-
-                    tag = String::from_utf8(e.name().0.to_vec()).unwrap();
-
-                    let new_node = Arc::new(ChapterNode::new(tag, classes, String::new()));
-
+                    let new_node = Chapter::create_node_from_xhtml(e);
                     ChapterNode::add_child(&current_node, &new_node);
 
                     current_node = new_node;
                 }
+                Event::Empty(ref e) => {
+                    ChapterNode::add_child(&current_node, &Chapter::create_node_from_xhtml(e));
+                }
                 Event::Text(e) => {
-                    content = e.unescape().unwrap().replace("\u{ad}", "").to_string();
+                    let content = match e.unescape() {
+                        Ok(content) => content,
+                        Err(e) => return Err(not_well_formed(&e, reader.buffer_position())),
+                    };
                     current_node.append_to_content(&content);
                 }
-                Event::End(ref _e) => {
-                    let parent = current_node.get_parent().upgrade().unwrap();
-                    current_node = parent;
+                Event::CData(e) => {
+                    current_node.append_to_content(&String::from_utf8_lossy(&e));
                 }
+                Event::End(ref e) => match current_node.get_parent().upgrade() {
+                    Some(parent) => current_node = parent,
+                    None => warnings.push(ChapterParseWarning::new(format!(
+                        "closing tag </{}> without an opening one was ignored",
+                        String::from_utf8_lossy(e.name().as_ref())
+                    ))),
+                },
                 Event::Eof => break,
                 _ => {}
             }
             buf.clear();
         }
 
-        current_node
+        if !Arc::ptr_eq(&current_node, &root) {
+            warnings.push(ChapterParseWarning::new(format!(
+                "<{}> was not closed before the end of the content",
+                current_node.tag
+            )));
+        }
+
+        Ok((root, warnings))
+    }
+
+    fn create_node_from_xhtml(e: &BytesStart<'_>) -> Arc<ChapterNode> {
+        let mut classes: Vec<String> = vec![];
+        let mut attributes: HashMap<String, String> = HashMap::new();
+
+        for attr in e.attributes().flatten() {
+            let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+            let value = match attr.unescape_value() {
+                Ok(value) => value.to_string(),
+                Err(_) => String::from_utf8_lossy(&attr.value).to_string(),
+            };
+
+            if key == "class" {
+                classes = value.split_whitespace().map(|s| s.to_string()).collect();
+            }
+
+            attributes.insert(key, value);
+        }
+
+        let tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
+
+        Arc::new(ChapterNode::new(tag, classes, String::new()).with_attributes(attributes))
+    }
+
+    /// Parses the content the way browsers do, so it never fails. Errors reported by the parser become warnings.
+    fn recreate_structure_from_html(
+        chapter_content: &str,
+    ) -> (Arc<ChapterNode>, Vec<ChapterParseWarning>) {
+        let root = Arc::new(ChapterNode::new("root".to_string(), vec![], String::new()));
+        let html = Html::parse_document(chapter_content);
+
+        Chapter::append_html_element(&root, html.root_element());
+
+        let warnings = html
+            .errors
+            .iter()
+            .map(|error| ChapterParseWarning::new(error.to_string()))
+            .collect();
+
+        (root, warnings)
+    }
+
+    fn append_html_element(parent: &Arc<ChapterNode>, element: ElementRef) {
+        let classes = element
+            .value()
+            .classes()
+            .map(|class| class.to_string())
+            .collect();
+        let attributes = element
+            .value()
+            .attrs()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let node = Arc::new(
+            ChapterNode::new(element.value().name().to_string(), classes, String::new())
+                .with_attributes(attributes),
+        );
+
+        ChapterNode::add_child(parent, &node);
+
+        for child in element.children() {
+            if let Some(child_element) = ElementRef::wrap(child) {
+                Chapter::append_html_element(&node, child_element);
+            } else if let Node::Text(text) = child.value() {
+                node.append_to_content(text);
+            }
+        }
     }
 
     pub fn get_body(&self) -> Option<Arc<ChapterNode>> {
@@ -120,6 +263,22 @@ impl Chapter {
 
         None
     }
+
+    /// Finds the element with given id, the target of `#id` anchors pointing into the chapter
+    pub fn find_by_id(&self, id: &str) -> Option<Arc<ChapterNode>> {
+        let mut nodes = vec![Arc::clone(&self.recreated_structure)];
+
+        while let Some(node) = nodes.pop() {
+            if node.id() == Some(id) {
+                return Some(node);
+            }
+
+            // Children are pushed in reverse, so the first element in document order wins
+            nodes.extend(node.get_children().into_iter().rev());
+        }
+
+        None
+    }
 }
 
 #[cfg(test)]
@@ -163,6 +322,7 @@ mod chapter_tests {
             Chapter {
                 path: path.to_string(),
                 label: label.to_string(),
+                warnings: vec![],
                 _raw_content: content.to_string(),
                 recreated_structure: Arc::new(ChapterNode::new(
                     "tag".to_string(),
@@ -173,6 +333,37 @@ mod chapter_tests {
         }
     }
 
+    mod find_by_id {
+        use crate::chapters::chapter::Chapter;
+        use crate::table_of_contents::table_of_contents_item::TableOfContentsItem;
+
+        #[test]
+        fn should_find_first_element_with_given_id() {
+            //arrange
+            let chapter_content: &str = r#"
+                <html>
+                    <body>
+                        <section id="part_1"><h1>Part 1</h1></section>
+                        <section id="part_2"><h1 id="part_2_title">Part 2</h1></section>
+                        <p id="part_2">Duplicate</p>
+                    </body>
+                </html>
+            "#;
+            let toc_item = TableOfContentsItem::new(String::new(), String::new(), None);
+
+            //act
+            let sut = Chapter::from_item_with_content(toc_item, chapter_content.to_string());
+
+            //assert
+            assert_eq!(sut.find_by_id("part_2").unwrap().tag, "section");
+            assert_eq!(
+                sut.find_by_id("part_2_title").unwrap().text_content(),
+                "Part 2"
+            );
+            assert!(sut.find_by_id("part_3").is_none());
+        }
+    }
+
     mod get_body {
         use crate::chapters::chapter::Chapter;
 
@@ -202,6 +393,7 @@ mod chapter_tests {
                 path: "path".to_string(),
                 label: "label".to_string(),
                 recreated_structure: chapter_node,
+                warnings: vec![],
                 _raw_content: chapter_content.to_string(),
             };
 
@@ -239,6 +431,7 @@ mod chapter_tests {
                 path: "path".to_string(),
                 label: "label".to_string(),
                 recreated_structure: chapter_node,
+                warnings: vec![],
                 _raw_content: chapter_content.to_string(),
             };
 
@@ -419,4 +612,112 @@ mod chapter_tests {
             assert_eq!(first_paragraph.get_children().len(), 0);
         }
     }
+
+    mod tolerant_parsing_tests {
+        use crate::chapters::chapter::{Chapter, ChapterParsingMode};
+
+        #[test]
+        fn should_keep_self_closing_elements_in_xhtml() {
+            //arrange
+            let chapter_content: &str = r#"<p>First line<br/>second line</p>"#;
+
+            //act
+            let (sut, warnings) =
+                Chapter::parse_structure(chapter_content, ChapterParsingMode::Xhtml);
+
+            //assert
+            let paragraph = &sut.get_children()[0];
+            assert!(warnings.is_empty());
+            assert_eq!(paragraph.get_content(), "First linesecond line");
+            assert_eq!(paragraph.get_children()[0].tag, "br");
+        }
+
+        #[test]
+        fn should_fall_back_to_html_when_void_element_is_not_closed() {
+            //arrange
+            let chapter_content: &str =
+                r#"<html><body><p>First line<br>second line</p></body></html>"#;
+
+            //act
+            let (sut, warnings) =
+                Chapter::parse_structure(chapter_content, ChapterParsingMode::Xhtml);
+
+            //assert
+            let body = &sut.get_children()[0].get_children()[1];
+            let paragraph = &body.get_children()[0];
+            assert!(warnings[0].message.contains("parsed as HTML"));
+            assert_eq!(body.tag, "body");
+            assert_eq!(paragraph.get_content(), "First linesecond line");
+            assert_eq!(paragraph.get_children()[0].tag, "br");
+        }
+
+        #[test]
+        fn should_not_panic_on_stray_closing_tag() {
+            //arrange
+            let chapter_content: &str =
+                r#"<html><body><p class="line">Text</p></div></body></html>"#;
+
+            //act
+            let (sut, warnings) =
+                Chapter::parse_structure(chapter_content, ChapterParsingMode::Xhtml);
+
+            //assert
+            let body = &sut.get_children()[0].get_children()[1];
+            assert!(!warnings.is_empty());
+            assert_eq!(body.get_children().len(), 1);
+            assert_eq!(body.get_children()[0].classes, vec!["line".to_string()]);
+            assert_eq!(body.get_children()[0].get_content(), "Text");
+        }
+
+        #[test]
+        fn should_decode_html_entities_unknown_to_xml() {
+            //arrange
+            let chapter_content: &str =
+                r#"<html><body><p>Mr.&nbsp;Queequeg&hellip;</p></body></html>"#;
+
+            //act
+            let (sut, warnings) =
+                Chapter::parse_structure(chapter_content, ChapterParsingMode::Xhtml);
+
+            //assert
+            let paragraph = &sut.get_children()[0].get_children()[1].get_children()[0];
+            assert!(!warnings.is_empty());
+            assert_eq!(paragraph.get_content(), "Mr.\u{a0}Queequeg\u{2026}");
+        }
+
+        #[test]
+        fn should_report_unclosed_elements_in_xhtml() {
+            //arrange
+            let chapter_content: &str = r#"<body><p>Text"#;
+
+            //act
+            let (sut, warnings) =
+                Chapter::parse_structure(chapter_content, ChapterParsingMode::Xhtml);
+
+            //assert
+            assert_eq!(sut.tag, "root");
+            assert_eq!(sut.get_children()[0].tag, "body");
+            assert_eq!(warnings.len(), 1);
+            assert!(warnings[0].message.contains("<p>"));
+        }
+
+        #[test]
+        fn should_parse_as_html_when_requested() {
+            //arrange
+            let chapter_content: &str =
+                "<!DOCTYPE html><html><head><title>T</title></head><body><p>One<p>Two</body></html>";
+
+            //act
+            let (sut, warnings) =
+                Chapter::parse_structure(chapter_content, ChapterParsingMode::Html);
+
+            //assert
+            let html = &sut.get_children()[0];
+            let body = &html.get_children()[1];
+            assert!(warnings.is_empty());
+            assert_eq!(html.tag, "html");
+            assert_eq!(body.get_children().len(), 2);
+            assert_eq!(body.get_children()[1].get_content(), "Two");
+        }
+    }
 }
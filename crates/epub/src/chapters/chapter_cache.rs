@@ -0,0 +1,127 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use super::chapter::Chapter;
+
+/// Bounded cache of parsed chapters keyed by their path inside of the archive.
+/// When it is full, the least recently used chapter is evicted.
+#[derive(Debug)]
+pub struct ChapterCache {
+    capacity: usize,
+    /// Most recently used chapters are kept at the back
+    chapters: VecDeque<Arc<Chapter>>,
+}
+
+impl ChapterCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            chapters: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn get(&mut self, path: &str) -> Option<Arc<Chapter>> {
+        let index = self
+            .chapters
+            .iter()
+            .position(|chapter| chapter.path == path)?;
+        let chapter = self.chapters.remove(index)?;
+
+        self.chapters.push_back(Arc::clone(&chapter));
+
+        Some(chapter)
+    }
+
+    pub fn insert(&mut self, chapter: Arc<Chapter>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.chapters.retain(|cached| cached.path != chapter.path);
+
+        if self.chapters.len() >= self.capacity {
+            self.chapters.pop_front();
+        }
+
+        self.chapters.push_back(chapter);
+    }
+
+    pub fn len(&self) -> usize {
+        self.chapters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chapters.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.chapters.clear();
+    }
+}
+
+#[cfg(test)]
+mod chapter_cache_tests {
+    use super::*;
+
+    fn chapter(path: &str) -> Arc<Chapter> {
+        let item = crate::table_of_contents::table_of_contents_item::TableOfContentsItem::new(
+            path.to_string(),
+            path.to_string(),
+            None,
+        );
+
+        Arc::new(Chapter::from_item_with_content(
+            item,
+            "<html><body><p>Text</p></body></html>".to_string(),
+        ))
+    }
+
+    #[test]
+    fn get_should_return_inserted_chapter() {
+        let mut cache = ChapterCache::new(2);
+        let first = chapter("first.xhtml");
+
+        cache.insert(Arc::clone(&first));
+
+        assert!(Arc::ptr_eq(&cache.get("first.xhtml").unwrap(), &first));
+        assert!(cache.get("second.xhtml").is_none());
+    }
+
+    #[test]
+    fn insert_should_evict_least_recently_used_chapter_when_full() {
+        let mut cache = ChapterCache::new(2);
+
+        cache.insert(chapter("first.xhtml"));
+        cache.insert(chapter("second.xhtml"));
+        cache.get("first.xhtml");
+        cache.insert(chapter("third.xhtml"));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("first.xhtml").is_some());
+        assert!(cache.get("second.xhtml").is_none());
+        assert!(cache.get("third.xhtml").is_some());
+    }
+
+    #[test]
+    fn insert_should_replace_chapter_with_the_same_path() {
+        let mut cache = ChapterCache::new(2);
+        let replacement = chapter("first.xhtml");
+
+        cache.insert(chapter("first.xhtml"));
+        cache.insert(Arc::clone(&replacement));
+
+        assert_eq!(cache.len(), 1);
+        assert!(Arc::ptr_eq(
+            &cache.get("first.xhtml").unwrap(),
+            &replacement
+        ));
+    }
+
+    #[test]
+    fn cache_with_zero_capacity_should_not_keep_anything() {
+        let mut cache = ChapterCache::new(0);
+
+        cache.insert(chapter("first.xhtml"));
+
+        assert!(cache.is_empty());
+    }
+}
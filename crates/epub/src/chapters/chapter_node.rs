@@ -1,25 +1,86 @@
-use std::sync::{Arc, RwLock, Weak};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock, Weak},
+};
+
+/// U+00AD, kept in the text nodes as it is in the document, but never displayed
+pub(crate) const SOFT_HYPHEN: char = '\u{ad}';
 
 #[derive(Debug)]
 pub struct ChapterNode {
     pub tag: String,
     pub classes: Vec<String>,
+    /// Attributes of the element with their prefixes kept in the names, e.g. `epub:type` or `xml:lang`
+    pub attributes: HashMap<String, String>,
+    /// All text placed directly inside of the node joined together, without the text of its children and soft hyphens
     pub content: RwLock<String>,
     pub(crate) parent: RwLock<Weak<ChapterNode>>,
     pub(crate) children: RwLock<Vec<Arc<ChapterNode>>>,
+    /// Elements and text runs in the document order, with the text kept as it is in the document
+    pub(crate) child_nodes: RwLock<Vec<ChapterNodeChild>>,
+}
+
+/// Child of the node in the document order, either a nested element or a run of text between elements
+#[derive(Debug, Clone)]
+pub enum ChapterNodeChild {
+    Element(Arc<ChapterNode>),
+    /// Text as it is in the document, including soft hyphens, see `displayed_text`
+    Text(String),
+}
+
+/// Text without the characters which are not displayed, i.e. soft hyphens
+pub(crate) fn displayed_text(text: &str) -> String {
+    text.chars().filter(|c| *c != SOFT_HYPHEN).collect()
+}
+
+/// Continuous part of the text, placed inside of the same elements
+#[derive(Debug, Clone)]
+pub struct TextRun {
+    pub text: String,
+    /// Character offset of the run inside of the `text_content` of the node the runs were created for
+    pub offset: usize,
+    /// Elements containing the run, from the outermost to the innermost one, e.g. `em` inside of `a`
+    pub elements: Vec<Arc<ChapterNode>>,
+}
+
+impl TextRun {
+    pub fn is_inside(&self, tag: &str) -> bool {
+        self.elements.iter().any(|element| element.tag == tag)
+    }
+
+    /// Innermost `<a href>` element containing the run
+    pub fn link(&self) -> Option<&Arc<ChapterNode>> {
+        self.elements
+            .iter()
+            .rev()
+            .find(|element| element.tag == "a" && element.href().is_some())
+    }
 }
 
 impl ChapterNode {
     pub(crate) fn new(tag: String, classes: Vec<String>, content: String) -> ChapterNode {
+        let child_nodes = if content.is_empty() {
+            vec![]
+        } else {
+            vec![ChapterNodeChild::Text(content.clone())]
+        };
+
         ChapterNode {
             tag: tag,
             classes,
+            attributes: HashMap::new(),
             content: RwLock::new(content),
             parent: RwLock::new(Weak::new()),
             children: RwLock::new(vec![]),
+            child_nodes: RwLock::new(child_nodes),
         }
     }
 
+    pub(crate) fn with_attributes(mut self, attributes: HashMap<String, String>) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
     pub(crate) fn add_child(parent_node: &Arc<ChapterNode>, child_node: &Arc<ChapterNode>) {
         let child_parent_weak = Arc::downgrade(&parent_node);
         let child = Arc::clone(child_node);
@@ -30,12 +91,24 @@ impl ChapterNode {
 
         let mut children = parent.children.write().expect("Lock poisoned");
         children.push(Arc::clone(&child));
+
+        let mut child_nodes = parent.child_nodes.write().expect("Lock poisoned");
+        child_nodes.push(ChapterNodeChild::Element(Arc::clone(&child)));
     }
+    /// Appends the text as it is in the document, soft hyphens are left out only from the `content`
     pub(crate) fn append_to_content(&self, content: &str) {
         self.content
             .write()
             .expect("Lock poisoned")
-            .push_str(content);
+            .push_str(&displayed_text(content));
+
+        let mut child_nodes = self.child_nodes.write().expect("Lock poisoned");
+
+        // Text split into multiple events, e.g. around an entity, still is a single run
+        match child_nodes.last_mut() {
+            Some(ChapterNodeChild::Text(text)) => text.push_str(content),
+            _ => child_nodes.push(ChapterNodeChild::Text(content.to_string())),
+        }
     }
 
     pub fn get_content(&self) -> String {
@@ -46,16 +119,123 @@ impl ChapterNode {
         self.children.read().expect("Lock poisoned").clone()
     }
 
+    pub fn get_child_nodes(&self) -> Vec<ChapterNodeChild> {
+        self.child_nodes.read().expect("Lock poisoned").clone()
+    }
+
     pub fn get_parent(&self) -> Weak<ChapterNode> {
         self.parent.read().expect("Lock poisoned").clone()
     }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|value| value.as_str())
+    }
+
+    /// Target of the internal links pointing to `#id`
+    pub fn id(&self) -> Option<&str> {
+        self.attribute("id")
+    }
+
+    pub fn href(&self) -> Option<&str> {
+        self.attribute("href")
+            .or_else(|| self.attribute("xlink:href"))
+    }
+
+    pub fn src(&self) -> Option<&str> {
+        self.attribute("src")
+    }
+
+    pub fn alt(&self) -> Option<&str> {
+        self.attribute("alt")
+    }
+
+    /// Language declared on the element, `xml:lang` takes precedence over `lang` as in XHTML
+    pub fn lang(&self) -> Option<&str> {
+        self.attribute("xml:lang")
+            .or_else(|| self.attribute("lang"))
+    }
+
+    /// Language declared on the element or on the closest of its ancestors
+    pub fn inherited_lang(&self) -> Option<String> {
+        match self.lang() {
+            Some(lang) => Some(lang.to_string()),
+            None => self.get_parent().upgrade()?.inherited_lang(),
+        }
+    }
+
+    /// Text direction, `ltr`, `rtl` or `auto`
+    pub fn dir(&self) -> Option<&str> {
+        self.attribute("dir")
+    }
+
+    /// Space separated values of `epub:type`, e.g. `footnote` or `noteref`
+    pub fn epub_types(&self) -> Vec<&str> {
+        self.attribute("epub:type")
+            .map(|value| value.split_whitespace().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn has_epub_type(&self, epub_type: &str) -> bool {
+        self.epub_types().contains(&epub_type)
+    }
+
+    /// Text of the node and all of its descendants in the document order
+    pub fn text_content(&self) -> String {
+        self.text_runs()
+            .into_iter()
+            .map(|run| run.text)
+            .collect::<Vec<String>>()
+            .concat()
+    }
+
+    /// Splits the text of the node and all of its descendants into runs placed inside of the same elements,
+    /// e.g. `<p>Call me <em>Ishmael</em>.</p>` gives `Call me `, `Ishmael` inside of `em` and `.`
+    pub fn text_runs(&self) -> Vec<TextRun> {
+        let mut runs: Vec<TextRun> = vec![];
+        let mut offset = 0;
+
+        self.collect_text_runs(&mut vec![], &mut runs, &mut offset);
+
+        runs
+    }
+
+    fn collect_text_runs(
+        &self,
+        elements: &mut Vec<Arc<ChapterNode>>,
+        runs: &mut Vec<TextRun>,
+        offset: &mut usize,
+    ) {
+        for child in self.get_child_nodes() {
+            match child {
+                ChapterNodeChild::Text(text) => {
+                    let text = displayed_text(&text);
+                    if text.is_empty() {
+                        continue;
+                    }
+                    let length = text.chars().count();
+
+                    runs.push(TextRun {
+                        text,
+                        offset: *offset,
+                        elements: elements.clone(),
+                    });
+                    *offset += length;
+                }
+                ChapterNodeChild::Element(element) => {
+                    elements.push(Arc::clone(&element));
+                    element.collect_text_runs(elements, runs, offset);
+                    elements.pop();
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod chapter_node_tests {
     use std::sync::Arc;
 
-    use crate::chapters::chapter_node::ChapterNode;
+    use crate::chapters::chapter_node::{ChapterNode, ChapterNodeChild};
 
     #[test]
     fn should_create_chapter_node() {
@@ -90,4 +270,180 @@ mod chapter_node_tests {
         );
         assert_eq!(child.get_parent().upgrade().unwrap().tag, "h1".to_string());
     }
+
+    mod inline_content {
+        use super::*;
+        use crate::chapters::chapter::{Chapter, ChapterParsingMode};
+
+        const PARAGRAPH: &str = r#"<p>Call me <em>Ishmael</em>. Some years ago&#8212;<a href="note.xhtml"><b>never</b> mind</a> how long.</p>"#;
+
+        fn paragraph() -> Arc<ChapterNode> {
+            let (root, _) = Chapter::parse_structure(PARAGRAPH, ChapterParsingMode::Xhtml);
+            Arc::clone(&root.get_children()[0])
+        }
+
+        #[test]
+        fn child_nodes_should_keep_document_order_of_text_and_elements() {
+            let sut = paragraph();
+
+            let child_nodes: Vec<String> = sut
+                .get_child_nodes()
+                .iter()
+                .map(|child| match child {
+                    ChapterNodeChild::Element(element) => format!("<{}>", element.tag),
+                    ChapterNodeChild::Text(text) => text.clone(),
+                })
+                .collect();
+
+            assert_eq!(
+                child_nodes,
+                vec![
+                    "Call me ",
+                    "<em>",
+                    ". Some years ago\u{2014}",
+                    "<a>",
+                    " how long."
+                ]
+            );
+        }
+
+        #[test]
+        fn text_content_should_join_text_of_descendants_in_order() {
+            let sut = paragraph();
+
+            assert_eq!(
+                sut.text_content(),
+                "Call me Ishmael. Some years ago\u{2014}never mind how long."
+            );
+            assert_eq!(
+                sut.get_content(),
+                "Call me . Some years ago\u{2014} how long."
+            );
+        }
+
+        #[test]
+        fn text_runs_should_point_to_their_offsets_and_elements() {
+            let sut = paragraph();
+            let text_content = sut.text_content();
+
+            let runs = sut.text_runs();
+
+            let described_runs: Vec<(&str, Vec<&str>)> = runs
+                .iter()
+                .map(|run| {
+                    (
+                        run.text.as_str(),
+                        run.elements
+                            .iter()
+                            .map(|element| element.tag.as_str())
+                            .collect(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                described_runs,
+                vec![
+                    ("Call me ", vec![]),
+                    ("Ishmael", vec!["em"]),
+                    (". Some years ago\u{2014}", vec![]),
+                    ("never", vec!["a", "b"]),
+                    (" mind", vec!["a"]),
+                    (" how long.", vec![]),
+                ]
+            );
+            for run in runs.iter() {
+                let run_text: String = text_content
+                    .chars()
+                    .skip(run.offset)
+                    .take(run.text.chars().count())
+                    .collect();
+                assert_eq!(run_text, run.text);
+            }
+            assert_eq!(
+                runs[3].offset,
+                "Call me Ishmael. Some years ago\u{2014}".chars().count()
+            );
+            assert!(runs[3].is_inside("a"));
+            assert!(!runs[0].is_inside("em"));
+        }
+
+        #[test]
+        fn link_should_return_innermost_link_of_the_run() {
+            let sut = paragraph();
+
+            let runs = sut.text_runs();
+
+            assert_eq!(runs[3].link().unwrap().href(), Some("note.xhtml"));
+            assert_eq!(runs[4].link().unwrap().href(), Some("note.xhtml"));
+            assert!(runs[0].link().is_none());
+        }
+    }
+
+    mod attributes {
+        use super::*;
+        use crate::chapters::chapter::{Chapter, ChapterParsingMode};
+
+        const CHAPTER: &str = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="en">
+            <body>
+                <section epub:type="bodymatter chapter" id="chapter_1" dir="ltr">
+                    <p>Call me Ishmael.<a epub:type="noteref" href="notes.xhtml#note_1">1</a></p>
+                    <img src="../images/whale.jpg" alt="The &quot;white&quot; whale"/>
+                    <p lang="fr">Bonjour</p>
+                </section>
+            </body>
+        </html>"#;
+
+        /// Returns the root together with the section, since parents are only weakly referenced by their children
+        fn section(mode: ChapterParsingMode) -> (Arc<ChapterNode>, Arc<ChapterNode>) {
+            let (root, _) = Chapter::parse_structure(CHAPTER, mode);
+            let html = &root.get_children()[0];
+            let body = html
+                .get_children()
+                .into_iter()
+                .find(|child| child.tag == "body")
+                .unwrap();
+
+            let section = Arc::clone(&body.get_children()[0]);
+
+            (root, section)
+        }
+
+        #[test]
+        fn should_keep_attributes_of_xhtml_elements() {
+            let (_root, sut) = section(ChapterParsingMode::Xhtml);
+            let link = &sut.get_children()[0].get_children()[0];
+            let image = &sut.get_children()[1];
+
+            assert_eq!(sut.id(), Some("chapter_1"));
+            assert_eq!(sut.dir(), Some("ltr"));
+            assert_eq!(sut.epub_types(), vec!["bodymatter", "chapter"]);
+            assert!(link.has_epub_type("noteref"));
+            assert_eq!(link.href(), Some("notes.xhtml#note_1"));
+            assert_eq!(image.src(), Some("../images/whale.jpg"));
+            assert_eq!(image.alt(), Some(r#"The "white" whale"#));
+        }
+
+        #[test]
+        fn should_keep_attributes_of_html_elements() {
+            let (_root, sut) = section(ChapterParsingMode::Html);
+            let link = &sut.get_children()[0].get_children()[0];
+            let image = &sut.get_children()[1];
+
+            assert_eq!(sut.id(), Some("chapter_1"));
+            assert_eq!(sut.epub_types(), vec!["bodymatter", "chapter"]);
+            assert_eq!(link.href(), Some("notes.xhtml#note_1"));
+            assert_eq!(image.alt(), Some(r#"The "white" whale"#));
+        }
+
+        #[test]
+        fn inherited_lang_should_use_closest_declaration() {
+            let (_root, sut) = section(ChapterParsingMode::Xhtml);
+            let english_paragraph = &sut.get_children()[0];
+            let french_paragraph = &sut.get_children()[2];
+
+            assert_eq!(sut.lang(), None);
+            assert_eq!(english_paragraph.inherited_lang(), Some("en".to_string()));
+            assert_eq!(french_paragraph.inherited_lang(), Some("fr".to_string()));
+        }
+    }
 }
@@ -1,2 +1,4 @@
 pub mod chapter;
+pub mod chapter_cache;
 pub mod chapter_node;
+pub mod plain_text;
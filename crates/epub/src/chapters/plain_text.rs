@@ -0,0 +1,393 @@
+use std::sync::Arc;
+
+use crate::{
+    epub::EBook, error::EpubError, table_of_contents::table_of_contents_item::TableOfContentsItem,
+};
+
+use super::{
+    chapter::Chapter,
+    chapter_node::{ChapterNode, ChapterNodeChild, SOFT_HYPHEN},
+};
+
+/// Elements starting a new line of the plain text
+const BLOCK_TAGS: [&str; 30] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "caption",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// Elements which are not displayed, so their content is not a part of the plain text
+const HIDDEN_TAGS: [&str; 5] = ["head", "script", "style", "title", "template"];
+
+/// Text of a document as it is displayed: whitespace collapsed and every block placed in its own line
+#[derive(Debug, Clone, Default)]
+pub struct PlainText {
+    pub text: String,
+    /// Parts of the text placed directly inside of a single element, in the document order
+    pub segments: Vec<TextSegment>,
+}
+
+/// Part of the plain text together with the element containing it
+#[derive(Debug, Clone)]
+pub struct TextSegment {
+    /// Character offset of the segment inside of the plain text
+    pub offset: usize,
+    /// Number of characters of the segment
+    pub length: usize,
+    /// Innermost element containing the text of the segment
+    pub node: Arc<ChapterNode>,
+}
+
+/// Word and character counts of a chapter or a whole book
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextStatistics {
+    /// Whitespace separated words containing at least one letter or digit, so that dashes are not counted
+    pub words: usize,
+    /// Characters of the text without whitespace
+    pub characters: usize,
+}
+
+/// Plain text of a document of the spine
+#[derive(Debug, Clone)]
+pub struct DocumentText {
+    pub spine_position: usize,
+    /// Full path of the document inside of the archive
+    pub path: String,
+    pub text: PlainText,
+}
+
+impl PlainText {
+    /// Creates the plain text of the node and all of its descendants
+    pub fn from_node(node: &Arc<ChapterNode>) -> PlainText {
+        let mut builder = PlainTextBuilder::default();
+        builder.append_node(node, false);
+
+        builder.finish()
+    }
+
+    /// Element containing the character at given offset, `None` for the line breaks between blocks
+    pub fn node_at(&self, offset: usize) -> Option<&Arc<ChapterNode>> {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.offset + segment.length <= offset);
+
+        self.segments
+            .get(index)
+            .filter(|segment| segment.offset <= offset)
+            .map(|segment| &segment.node)
+    }
+
+    /// Character offset of the first text placed inside of the node, `None` when the node has no text
+    pub fn offset_of(&self, node: &Arc<ChapterNode>) -> Option<usize> {
+        self.segments
+            .iter()
+            .find(|segment| is_within(&segment.node, node))
+            .map(|segment| segment.offset)
+    }
+
+    pub fn statistics(&self) -> TextStatistics {
+        TextStatistics::of(&self.text)
+    }
+}
+
+impl TextStatistics {
+    pub fn of(text: &str) -> TextStatistics {
+        TextStatistics {
+            words: text
+                .split_whitespace()
+                .filter(|word| word.chars().any(char::is_alphanumeric))
+                .count(),
+            characters: text.chars().filter(|c| !c.is_whitespace()).count(),
+        }
+    }
+}
+
+impl std::ops::Add for TextStatistics {
+    type Output = TextStatistics;
+
+    fn add(self, other: TextStatistics) -> TextStatistics {
+        TextStatistics {
+            words: self.words + other.words,
+            characters: self.characters + other.characters,
+        }
+    }
+}
+
+impl std::iter::Sum for TextStatistics {
+    fn sum<I: Iterator<Item = TextStatistics>>(iter: I) -> TextStatistics {
+        iter.fold(TextStatistics::default(), |sum, statistics| {
+            sum + statistics
+        })
+    }
+}
+
+impl Chapter {
+    /// Text of the body of the chapter, with every block placed in its own line
+    pub fn plain_text(&self) -> PlainText {
+        let body = self
+            .get_body()
+            .unwrap_or_else(|| Arc::clone(&self.recreated_structure));
+
+        PlainText::from_node(&body)
+    }
+}
+
+impl EBook {
+    /// Plain text of every document of the spine in the reading order, including the non-linear ones.
+    /// Documents are read and parsed lazily, one at a time.
+    pub fn plain_texts(&self) -> impl Iterator<Item = Result<DocumentText, EpubError>> + '_ {
+        self.spine
+            .items
+            .iter()
+            .enumerate()
+            .map(|(spine_position, spine_item)| {
+                let path = self
+                    .resolve_href(self.opf_path(), &spine_item.value.href)
+                    .path;
+                let chapter =
+                    self.chapter(&TableOfContentsItem::new(path.clone(), String::new(), None))?;
+
+                Ok(DocumentText {
+                    spine_position,
+                    path,
+                    text: chapter.plain_text(),
+                })
+            })
+    }
+
+    /// Word and character counts of all documents of the spine
+    pub fn text_statistics(&self) -> Result<TextStatistics, EpubError> {
+        self.plain_texts()
+            .map(|document| document.map(|document| document.text.statistics()))
+            .sum()
+    }
+}
+
+#[derive(Default)]
+struct PlainTextBuilder {
+    text: String,
+    /// Number of characters of the text, kept since `String::len` counts bytes
+    length: usize,
+    segments: Vec<TextSegment>,
+    /// Whitespace is written only before the next visible character, so that it is not left at the end of lines
+    pending_space: bool,
+}
+
+impl PlainTextBuilder {
+    fn append_node(&mut self, node: &Arc<ChapterNode>, preformatted: bool) {
+        let tag = node.tag.as_str();
+        if HIDDEN_TAGS.contains(&tag) {
+            return;
+        }
+
+        let is_block = BLOCK_TAGS.contains(&tag);
+        let preformatted = preformatted || tag == "pre";
+
+        if is_block {
+            self.break_line();
+        }
+
+        for child in node.get_child_nodes() {
+            match child {
+                ChapterNodeChild::Text(text) => self.append_text(&text, node, preformatted),
+                ChapterNodeChild::Element(element) => self.append_node(&element, preformatted),
+            }
+        }
+
+        if is_block {
+            self.break_line();
+        }
+    }
+
+    fn append_text(&mut self, text: &str, node: &Arc<ChapterNode>, preformatted: bool) {
+        let mut segment_offset = None;
+
+        for c in text.chars().filter(|c| *c != SOFT_HYPHEN) {
+            if c.is_whitespace() && !preformatted {
+                self.pending_space = true;
+                continue;
+            }
+
+            if self.pending_space && !self.text.is_empty() && !self.text.ends_with('\n') {
+                self.push(' ');
+            }
+            self.pending_space = false;
+
+            segment_offset.get_or_insert(self.length);
+            self.push(c);
+        }
+
+        if let Some(offset) = segment_offset {
+            self.segments.push(TextSegment {
+                offset,
+                length: self.length - offset,
+                node: Arc::clone(node),
+            });
+        }
+    }
+
+    fn break_line(&mut self) {
+        self.pending_space = false;
+
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.push('\n');
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        self.text.push(c);
+        self.length += 1;
+    }
+
+    fn finish(mut self) -> PlainText {
+        if self.text.ends_with('\n') {
+            self.text.pop();
+        }
+
+        PlainText {
+            text: self.text,
+            segments: self.segments,
+        }
+    }
+}
+
+/// Whether `node` is `ancestor` itself or one of its descendants
+fn is_within(node: &Arc<ChapterNode>, ancestor: &Arc<ChapterNode>) -> bool {
+    std::iter::successors(Some(Arc::clone(node)), |node| node.get_parent().upgrade())
+        .any(|node| Arc::ptr_eq(&node, ancestor))
+}
+
+#[cfg(test)]
+mod plain_text_tests {
+    use super::*;
+    use crate::test_utils::chapter_from_content;
+
+    const MOBY_DICK_PATH: &str = "./test_data/epub/moby-dick.epub";
+
+    const CHAPTER_CONTENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <html xmlns="http://www.w3.org/1999/xhtml">
+            <head><title>Loomings</title><style>p { margin: 0; }</style></head>
+            <body>
+                <h1>Loomings</h1>
+                <p>Call me <em>Ishmael</em>.   Some years ago —
+                    never mind how long.</p>
+                <pre>line 1
+line 2</pre>
+                <p>Żółw<br/>and <span>whale</span></p>
+            </body>
+        </html>"#;
+
+    #[test]
+    fn should_keep_block_boundaries_and_collapse_whitespace() {
+        //arrange
+        let chapter = chapter_from_content("OEBPS/chapter_1.xhtml", CHAPTER_CONTENT);
+
+        //act
+        let plain_text = chapter.plain_text();
+
+        //assert
+        assert_eq!(
+            plain_text.text,
+            "Loomings\nCall me Ishmael. Some years ago — never mind how long.\nline 1\nline 2\nŻółw\nand whale"
+        );
+    }
+
+    #[test]
+    fn should_map_character_offsets_to_nodes() {
+        //arrange
+        let chapter = chapter_from_content("OEBPS/chapter_1.xhtml", CHAPTER_CONTENT);
+        let plain_text = chapter.plain_text();
+        let ishmael_offset = plain_text.text.chars().position(|c| c == 'I').unwrap();
+        let whale_offset = plain_text.text.chars().count() - "whale".len();
+
+        //act
+        let ishmael_node = plain_text.node_at(ishmael_offset).unwrap();
+        let whale_node = plain_text.node_at(whale_offset).unwrap();
+
+        //assert
+        assert_eq!(ishmael_node.tag, "em");
+        assert_eq!(whale_node.tag, "span");
+        assert_eq!(
+            plain_text.node_at("Loomings".len()).map(|node| &node.tag),
+            None
+        );
+        assert_eq!(plain_text.offset_of(whale_node), Some(whale_offset));
+
+        let paragraph = ishmael_node.get_parent().upgrade().unwrap();
+        assert_eq!(plain_text.offset_of(&paragraph), Some("Loomings\n".len()));
+    }
+
+    #[test]
+    fn should_count_words_and_characters() {
+        //arrange
+        let chapter = chapter_from_content("OEBPS/chapter_1.xhtml", CHAPTER_CONTENT);
+
+        //act
+        let statistics = chapter.plain_text().statistics();
+
+        //assert
+        assert_eq!(
+            statistics,
+            TextStatistics {
+                words: 18,
+                characters: 74,
+            }
+        );
+    }
+
+    #[test]
+    fn should_iterate_over_text_of_every_spine_document() {
+        //arrange
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+
+        //act
+        let documents = book
+            .plain_texts()
+            .collect::<Result<Vec<DocumentText>, EpubError>>()
+            .unwrap();
+        let statistics = book.text_statistics().unwrap();
+
+        //assert
+        assert_eq!(documents.len(), book.spine.items.len());
+        assert_eq!(documents[0].path, "OPS/cover.xhtml");
+        assert!(documents[9]
+            .text
+            .text
+            .starts_with("Chapter 4. The Counterpane.\n"));
+        assert_eq!(
+            statistics,
+            documents
+                .iter()
+                .map(|document| document.text.statistics())
+                .sum()
+        );
+        assert!(statistics.words > 200_000);
+    }
+}
@@ -0,0 +1,378 @@
+use std::fmt;
+
+use scraper::{ElementRef, Html};
+
+use crate::{
+    encoding::decode_document, epub::EBook, error::EpubError, manifest::ManifestItemProperty,
+    resources::ResourcePath,
+};
+
+/// Cover image of the book together with its media type, e.g. `image/jpeg`
+#[derive(Clone, PartialEq)]
+pub struct CoverImage {
+    pub data: Vec<u8>,
+    pub media_type: String,
+}
+
+impl fmt::Debug for CoverImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoverImage")
+            .field("data", &format!("{} bytes", self.data.len()))
+            .field("media_type", &self.media_type)
+            .finish()
+    }
+}
+
+const GUIDE_COVER_TYPE: &str = "cover";
+const IMAGE_MEDIA_TYPE_PREFIX: &str = "image/";
+
+impl EBook {
+    /// Looks for the cover image of the book in following order:
+    /// 1. Epub 3 manifest item with `properties="cover-image"`
+    /// 2. Epub 2 manifest item pointed by `<meta name="cover">`
+    /// 3. Image referenced by the `<guide>` cover reference, either directly or from the referenced page
+    /// 4. First image of the first spine document
+    ///
+    /// A source that can not be read, e.g. a malformed cover page, is skipped like a missing one.
+    pub fn cover(&self) -> Result<Option<CoverImage>, EpubError> {
+        let cover_image_item = self
+            .manifest
+            .find_by_property(&ManifestItemProperty::CoverImage);
+        let meta_cover_item = self
+            .metadata
+            .cover
+            .as_ref()
+            .and_then(|cover_id| self.manifest.find_by_id(cover_id));
+        let guide_cover_href = self
+            .guide
+            .find_by_type(GUIDE_COVER_TYPE)
+            .map(|reference| reference.href.as_str());
+        let first_spine_item = self.spine.items.first().map(|item| &item.value);
+
+        let candidate_hrefs = [
+            cover_image_item.as_ref().map(|item| item.href.as_str()),
+            meta_cover_item.as_ref().map(|item| item.href.as_str()),
+            guide_cover_href,
+            first_spine_item.map(|item| item.href.as_str()),
+        ];
+
+        for href in candidate_hrefs.into_iter().flatten() {
+            let path = self.resolve_href(self.opf_path(), href).path;
+
+            if let Ok(Some(cover)) = self.read_image_from_path(&path) {
+                return Ok(Some(cover));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the image placed at `path`, or the first image of the document placed there.
+    /// Also used for the pages of fixed-layout books, which usually consist of a single image.
+    pub(crate) fn read_image_from_path(&self, path: &str) -> Result<Option<CoverImage>, EpubError> {
+        let media_type = self.media_type_of(path);
+
+        if media_type.starts_with(IMAGE_MEDIA_TYPE_PREFIX) {
+            return Ok(self
+                .read_optional_resource(path)?
+                .map(|data| CoverImage { data, media_type }));
+        }
+
+        let document = match self.read_optional_resource(path)? {
+            Some(document) => decode_document(&document)?,
+            None => return Ok(None),
+        };
+
+        match find_first_image_href(&document) {
+            Some(image_href) => {
+                let image_path = self.resolve_href(path, &image_href).path;
+                let image_media_type = self.media_type_of(&image_path);
+
+                Ok(self
+                    .read_optional_resource(&image_path)?
+                    .map(|data| CoverImage {
+                        data,
+                        media_type: image_media_type,
+                    }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Missing resources are not an error here, since a broken cover reference should not prevent looking further
+    fn read_optional_resource(&self, path: &str) -> Result<Option<Vec<u8>>, EpubError> {
+        match self.read_resource_at(path) {
+            Ok(data) => Ok(Some(data)),
+            Err(EpubError::MissingResource(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Media type declared in the manifest, or guessed from the file extension when the file is not in the manifest
+    fn media_type_of(&self, path: &str) -> String {
+        let manifest_item = self
+            .manifest
+            .items
+            .iter()
+            .find(|item| ResourcePath::resolve(self.opf_path(), &item.href).path == path);
+
+        match manifest_item {
+            Some(item) => item.media_type.clone(),
+            None => guess_media_type(path).to_string(),
+        }
+    }
+}
+
+/// Finds the first `<img src>` or SVG `<image href>` in the document.
+/// The document is parsed as HTML, so pages that are not well-formed XML can still provide the cover.
+fn find_first_image_href(document: &str) -> Option<String> {
+    let html = Html::parse_document(document);
+
+    html.root_element()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .find_map(|element| match element.value().name() {
+            "img" => element.value().attr("src"),
+            // `xlink:href` is only known by its local name
+            "image" => element
+                .value()
+                .attrs()
+                .find(|(name, _)| *name == "href")
+                .map(|(_, value)| value),
+            _ => None,
+        })
+        .map(|href| href.to_string())
+}
+
+fn guess_media_type(path: &str) -> &'static str {
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "xhtml" | "html" | "htm" => "application/xhtml+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod cover_tests {
+    use super::*;
+    use crate::test_utils::create_epub;
+
+    const MOBY_DICK_PATH: &str = "./test_data/epub/moby-dick.epub";
+    const IMAGE: &[u8] = b"not really a png, but the bytes are what matters";
+
+    fn create_opf(manifest: &str, spine: &str, metadata: &str, guide: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+                    <dc:title>Cover test</dc:title>
+                    {}
+                </metadata>
+                <manifest>
+                    <item id="toc" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+                    {}
+                </manifest>
+                <spine toc="toc">{}</spine>
+                {}
+            </package>"#,
+            metadata, manifest, spine, guide
+        )
+    }
+
+    const TOC_NCX: &str = r#"<ncx><navMap>
+        <navPoint id="n1" playOrder="1"><navLabel><text>Start</text></navLabel><content src="Text/page.xhtml"/></navPoint>
+    </navMap></ncx>"#;
+
+    #[test]
+    fn should_return_epub3_cover_image() {
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+
+        let cover = book.cover().unwrap().unwrap();
+
+        assert_eq!(cover.media_type, "image/jpeg");
+        assert_eq!(cover.data.len(), 348700);
+    }
+
+    #[test]
+    fn should_return_cover_pointed_by_epub2_meta() {
+        let opf = create_opf(
+            r#"<item id="cover-img" href="Images/cover.png" media-type="image/png"/>
+               <item id="page" href="Text/page.xhtml" media-type="application/xhtml+xml"/>"#,
+            r#"<itemref idref="page"/>"#,
+            r#"<meta name="cover" content="cover-img"/>"#,
+            "",
+        );
+        let path = create_epub(
+            "lore_leaf_cover_meta.epub",
+            &opf,
+            &[
+                ("toc.ncx", TOC_NCX.as_bytes()),
+                ("Images/cover.png", IMAGE),
+                ("Text/page.xhtml", b"<html><body><p>Text</p></body></html>"),
+            ],
+        );
+        let book = EBook::read_epub(path).unwrap();
+
+        let cover = book.cover().unwrap();
+
+        assert_eq!(
+            cover,
+            Some(CoverImage {
+                data: IMAGE.to_vec(),
+                media_type: "image/png".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn should_return_image_from_page_referenced_by_guide() {
+        let opf = create_opf(
+            r#"<item id="cover-page" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+               <item id="page" href="Text/page.xhtml" media-type="application/xhtml+xml"/>
+               <item id="cover-img" href="Images/cover.png" media-type="image/png"/>"#,
+            r#"<itemref idref="page"/>"#,
+            "",
+            r#"<guide><reference type="cover" title="Cover" href="Text/cover.xhtml"/></guide>"#,
+        );
+        let cover_page = r#"<html><body><svg xmlns:xlink="http://www.w3.org/1999/xlink">
+            <image width="600" height="800" xlink:href="../Images/cover.png"/>
+        </svg></body></html>"#;
+        let path = create_epub(
+            "lore_leaf_cover_guide.epub",
+            &opf,
+            &[
+                ("toc.ncx", TOC_NCX.as_bytes()),
+                ("Images/cover.png", IMAGE),
+                ("Text/cover.xhtml", cover_page.as_bytes()),
+                ("Text/page.xhtml", b"<html><body><p>Text</p></body></html>"),
+            ],
+        );
+        let book = EBook::read_epub(path).unwrap();
+
+        let cover = book.cover().unwrap().unwrap();
+
+        assert_eq!(cover.data, IMAGE.to_vec());
+        assert_eq!(cover.media_type, "image/png");
+    }
+
+    #[test]
+    fn should_find_image_in_guide_page_that_is_not_well_formed() {
+        let opf = create_opf(
+            r#"<item id="cover-page" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+               <item id="page" href="Text/page.xhtml" media-type="application/xhtml+xml"/>"#,
+            r#"<itemref idref="page"/>"#,
+            "",
+            r#"<guide><reference type="cover" title="Cover" href="Text/cover.xhtml"/></guide>"#,
+        );
+        let cover_page = r#"<html><body><p>Tom & Jerry&nbsp;<br>
+            <img alt="Tom & Jerry" src="../Images/cover.png">
+        </body></html>"#;
+        let path = create_epub(
+            "lore_leaf_cover_malformed_guide.epub",
+            &opf,
+            &[
+                ("toc.ncx", TOC_NCX.as_bytes()),
+                ("Images/cover.png", IMAGE),
+                ("Text/cover.xhtml", cover_page.as_bytes()),
+                ("Text/page.xhtml", b"<html><body><p>Text</p></body></html>"),
+            ],
+        );
+        let book = EBook::read_epub(path).unwrap();
+
+        let cover = book.cover().unwrap().unwrap();
+
+        assert_eq!(cover.data, IMAGE.to_vec());
+        assert_eq!(cover.media_type, "image/png");
+    }
+
+    #[test]
+    fn should_skip_unreadable_guide_page_and_return_first_image_of_first_spine_document() {
+        let opf = create_opf(
+            r#"<item id="cover-page" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+               <item id="page" href="Text/page.xhtml" media-type="application/xhtml+xml"/>"#,
+            r#"<itemref idref="page"/>"#,
+            "",
+            r#"<guide><reference type="cover" title="Cover" href="Text/cover.xhtml"/></guide>"#,
+        );
+        let page = r#"<html><body><img src="../Images/first.jpg"/></body></html>"#;
+        let path = create_epub(
+            "lore_leaf_cover_unreadable_guide.epub",
+            &opf,
+            &[
+                ("toc.ncx", TOC_NCX.as_bytes()),
+                ("Images/first.jpg", IMAGE),
+                // Latin-1 without any declared encoding is not valid UTF-8
+                (
+                    "Text/cover.xhtml",
+                    b"<html><body><p>Caf\xe9</p></body></html>",
+                ),
+                ("Text/page.xhtml", page.as_bytes()),
+            ],
+        );
+        let book = EBook::read_epub(path).unwrap();
+
+        let cover = book.cover().unwrap().unwrap();
+
+        assert_eq!(cover.data, IMAGE.to_vec());
+        assert_eq!(cover.media_type, "image/jpeg");
+    }
+
+    #[test]
+    fn should_return_first_image_of_first_spine_document() {
+        let opf = create_opf(
+            r#"<item id="page" href="Text/page.xhtml" media-type="application/xhtml+xml"/>"#,
+            r#"<itemref idref="page"/>"#,
+            "",
+            "",
+        );
+        let page = r#"<html><body><p>Text</p><img src="../Images/first.jpg"/><img src="../Images/second.jpg"/></body></html>"#;
+        let path = create_epub(
+            "lore_leaf_cover_spine.epub",
+            &opf,
+            &[
+                ("toc.ncx", TOC_NCX.as_bytes()),
+                ("Images/first.jpg", IMAGE),
+                ("Images/second.jpg", b"second"),
+                ("Text/page.xhtml", page.as_bytes()),
+            ],
+        );
+        let book = EBook::read_epub(path).unwrap();
+
+        let cover = book.cover().unwrap().unwrap();
+
+        assert_eq!(cover.data, IMAGE.to_vec());
+        assert_eq!(cover.media_type, "image/jpeg");
+    }
+
+    #[test]
+    fn should_return_none_when_book_has_no_images() {
+        let opf = create_opf(
+            r#"<item id="page" href="Text/page.xhtml" media-type="application/xhtml+xml"/>"#,
+            r#"<itemref idref="page"/>"#,
+            "",
+            "",
+        );
+        let path = create_epub(
+            "lore_leaf_cover_none.epub",
+            &opf,
+            &[
+                ("toc.ncx", TOC_NCX.as_bytes()),
+                ("Text/page.xhtml", b"<html><body><p>Text</p></body></html>"),
+            ],
+        );
+        let book = EBook::read_epub(path).unwrap();
+
+        let cover = book.cover().unwrap();
+
+        assert!(cover.is_none());
+    }
+}
@@ -0,0 +1,199 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+use crate::error::EpubError;
+
+/// Number of bytes at the beginning of the document searched for the encoding declaration
+const PRESCAN_LENGTH: usize = 1024;
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// Decodes text document read from the archive, e.g. the OPF, NCX or a chapter.
+/// The encoding is taken from the byte order mark, the XML declaration or the HTML `<meta>` charset,
+/// falling back to UTF-8, which is the default for epub documents.
+pub fn decode_document(bytes: &[u8]) -> Result<String, EpubError> {
+    let encoding = detect_encoding(bytes);
+
+    if encoding == UTF_8 {
+        let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+        return Ok(String::from_utf8(bytes.to_vec())?);
+    }
+
+    let (decoded, _) = encoding.decode_with_bom_removal(bytes);
+
+    Ok(decoded.into_owned())
+}
+
+pub fn detect_encoding(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    // UTF-16 without the byte order mark can be still recognized by the `<?` of the XML declaration
+    if bytes.starts_with(&[0x00, b'<', 0x00, b'?']) {
+        return UTF_16BE;
+    }
+    if bytes.starts_with(&[b'<', 0x00, b'?', 0x00]) {
+        return UTF_16LE;
+    }
+
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(PRESCAN_LENGTH)]);
+    let declared_encoding = find_xml_declaration_encoding(&head)
+        .or_else(|| find_meta_charset(&head))
+        .and_then(|label| Encoding::for_label(label.as_bytes()));
+
+    match declared_encoding {
+        // Declaration readable as ASCII means the document is not in UTF-16, whatever it says
+        Some(encoding) if encoding == UTF_16LE || encoding == UTF_16BE => UTF_8,
+        Some(encoding) => encoding,
+        None => UTF_8,
+    }
+}
+
+/// Reads `encoding` from `<?xml version="1.0" encoding="ISO-8859-2"?>`
+fn find_xml_declaration_encoding(head: &str) -> Option<String> {
+    let declaration = head.trim_start().strip_prefix("<?xml")?;
+    let declaration = &declaration[..declaration.find("?>")?];
+
+    read_value_after(declaration, "encoding")
+}
+
+/// Reads the charset from `<meta charset="windows-1250">` or
+/// `<meta http-equiv="Content-Type" content="text/html; charset=windows-1250">`
+fn find_meta_charset(head: &str) -> Option<String> {
+    let lowercase_head = head.to_ascii_lowercase();
+
+    lowercase_head
+        .match_indices("<meta")
+        .find_map(|(start, _)| {
+            let tag = &lowercase_head[start..];
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+
+            read_value_after(tag, "charset")
+        })
+}
+
+/// Reads value of `key=value`, `key="value"` or `key='value'` found in the text
+fn read_value_after(text: &str, key: &str) -> Option<String> {
+    let after_key = &text[text.find(key)? + key.len()..];
+    let after_equals = after_key.trim_start().strip_prefix('=')?.trim_start();
+    let value = after_equals.trim_start_matches(['"', '\'']);
+
+    let value: String = value
+        .chars()
+        .take_while(|c| !matches!(c, '"' | '\'' | ';' | '>' | '/') && !c.is_whitespace())
+        .collect();
+
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+    use crate::{
+        epub::{EBook, META_INF_CONTAINER_PATH},
+        test_utils::{create_archive, CONTAINER_CONTENT},
+    };
+
+    const ENCODING_TEST_DATA_PATH: &str = "./test_data/encoding";
+
+    fn read_fixture(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/{}", ENCODING_TEST_DATA_PATH, name)).unwrap()
+    }
+
+    #[test]
+    fn should_decode_utf8_without_declaration() {
+        let document = decode_document("<p>Zażółć</p>".as_bytes()).unwrap();
+
+        assert_eq!(document, "<p>Zażółć</p>");
+    }
+
+    #[test]
+    fn should_strip_utf8_byte_order_mark() {
+        let bytes = [UTF8_BOM, "<p>Zażółć</p>".as_bytes()].concat();
+
+        let document = decode_document(&bytes).unwrap();
+
+        assert_eq!(document, "<p>Zażółć</p>");
+    }
+
+    #[test]
+    fn should_return_error_for_invalid_utf8() {
+        let document = decode_document(&[b'<', b'p', b'>', 0xC0, 0xAF]);
+
+        assert!(matches!(document, Err(EpubError::InvalidUtf8(_))));
+    }
+
+    #[test]
+    fn should_decode_using_xml_declaration() {
+        let bytes = read_fixture("chapter_iso_8859_2.xhtml");
+
+        let document = decode_document(&bytes).unwrap();
+
+        assert_eq!(detect_encoding(&bytes), encoding_rs::ISO_8859_2);
+        assert!(document.contains("<p>Zażółć gęślą jaźń.</p>"));
+    }
+
+    #[test]
+    fn should_decode_using_meta_charset() {
+        let bytes = read_fixture("chapter_meta_charset.html");
+
+        let document = decode_document(&bytes).unwrap();
+
+        assert_eq!(detect_encoding(&bytes), encoding_rs::WINDOWS_1250);
+        assert!(document.contains("Śnieg padał na Łódź."));
+    }
+
+    #[test]
+    fn should_decode_utf16_with_byte_order_mark() {
+        let bytes = read_fixture("toc_utf16le_bom.ncx");
+
+        let document = decode_document(&bytes).unwrap();
+
+        assert!(document.starts_with("<?xml"));
+        assert!(document.contains("<text>Rozdział pierwszy</text>"));
+    }
+
+    #[test]
+    fn should_decode_utf16_without_byte_order_mark() {
+        let bytes = read_fixture("content_utf16be.opf");
+
+        let document = decode_document(&bytes).unwrap();
+
+        assert!(document.contains("<dc:title>Zażółć gęślą jaźń</dc:title>"));
+    }
+
+    #[test]
+    fn should_ignore_utf16_declaration_of_ascii_readable_document() {
+        let bytes = br#"<?xml version="1.0" encoding="UTF-16"?><p>Text</p>"#;
+
+        assert_eq!(detect_encoding(bytes), UTF_8);
+    }
+
+    #[test]
+    fn should_read_book_with_documents_in_legacy_encodings() {
+        let opf = read_fixture("content_utf16be.opf");
+        let toc = read_fixture("toc_utf16le_bom.ncx");
+        let chapter = read_fixture("chapter_iso_8859_2.xhtml");
+        let path = create_archive(
+            "lore_leaf_legacy_encodings.epub",
+            &[
+                ("mimetype", b"application/epub+zip"),
+                (META_INF_CONTAINER_PATH, CONTAINER_CONTENT.as_bytes()),
+                ("OEBPS/content.opf", &opf),
+                ("OEBPS/toc.ncx", &toc),
+                ("OEBPS/chapter.xhtml", &chapter),
+            ],
+        );
+
+        let book = EBook::read_epub(path).unwrap();
+        let toc_item = book.table_of_contents.items[0].clone();
+        let content = book.get_content_by_toc_item(&toc_item).unwrap();
+
+        assert_eq!(book.metadata.title, Some("Zażółć gęślą jaźń".to_string()));
+        assert_eq!(toc_item.label, "Rozdział pierwszy");
+        assert!(content.contains("<h1>Rozdział pierwszy</h1>"));
+    }
+}
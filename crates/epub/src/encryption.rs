@@ -0,0 +1,618 @@
+use std::{fmt, fs::File};
+
+use quick_xml::{events::Event, Reader};
+use sha1::{Digest, Sha1};
+use zip::{result::ZipError, ZipArchive};
+
+use crate::{
+    epub::EBook, error::EpubError, metadata::BookMetadata, resources::ResourcePath,
+    xml::attribute_value,
+};
+
+/// Path of the file listing encrypted and obfuscated resources of the book
+/// https://www.w3.org/TR/epub-33/#sec-container-metainf-encryption.xml
+pub const META_INF_ENCRYPTION_PATH: &str = "META-INF/encryption.xml";
+/// Path of the file with the rights of the book, used by Adobe DRM
+pub const META_INF_RIGHTS_PATH: &str = "META-INF/rights.xml";
+/// License of the book protected with Readium LCP
+const META_INF_LCP_LICENSE_PATH: &str = "META-INF/license.lcpl";
+/// Signatures of the book protected with Apple FairPlay
+const META_INF_SINF_PATH: &str = "META-INF/sinf.xml";
+
+const ADOBE_ADEPT_NAMESPACE: &str = "http://ns.adobe.com/adept";
+
+const IDPF_FONT_OBFUSCATION: &str = "http://www.idpf.org/2008/embedding";
+const ADOBE_FONT_OBFUSCATION: &str = "http://ns.adobe.com/pdf/enc#RC";
+
+/// Number of obfuscated bytes at the beginning of the font, the rest of the file is left untouched
+const IDPF_OBFUSCATED_LENGTH: usize = 1040;
+const ADOBE_OBFUSCATED_LENGTH: usize = 1024;
+
+/// Resources listed in `META-INF/encryption.xml`, together with the DRM detected from the other `META-INF` files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookEncryption {
+    pub resources: Vec<EncryptedResource>,
+    /// DRM declared by the rights or license file of the book
+    pub rights: Option<DrmScheme>,
+}
+
+/// How the resource is protected, based on the algorithm listed for it in `META-INF/encryption.xml`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResourceProtection {
+    Plain,
+    /// Font obfuscated to prevent extracting it from the book, it can be restored without any external key
+    FontObfuscated,
+    /// Resource encrypted by DRM, it can not be read without a key of the user
+    DrmEncrypted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrmScheme {
+    AdobeAdept,
+    ReadiumLcp,
+    AppleFairPlay,
+    /// Resources are encrypted, but the DRM could not be recognized
+    Unknown,
+}
+
+impl fmt::Display for DrmScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrmScheme::AdobeAdept => write!(f, "Adobe DRM"),
+            DrmScheme::ReadiumLcp => write!(f, "Readium LCP"),
+            DrmScheme::AppleFairPlay => write!(f, "Apple FairPlay"),
+            DrmScheme::Unknown => write!(f, "unknown DRM"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptedResource {
+    /// Full path of the archive entry, e.g. `OEBPS/Fonts/font.otf`
+    pub path: String,
+    pub algorithm: EncryptionAlgorithm,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncryptionAlgorithm {
+    /// Font obfuscation defined by the epub standard, keyed with the unique identifier of the book
+    IdpfFontObfuscation,
+    /// Font obfuscation used by Adobe, keyed with the `urn:uuid` identifier of the book
+    AdobeFontObfuscation,
+    /// Any other algorithm, usually an actual encryption used by DRM
+    Other(String),
+}
+
+impl EncryptionAlgorithm {
+    pub fn from_uri(uri: &str) -> EncryptionAlgorithm {
+        match uri {
+            IDPF_FONT_OBFUSCATION => EncryptionAlgorithm::IdpfFontObfuscation,
+            ADOBE_FONT_OBFUSCATION => EncryptionAlgorithm::AdobeFontObfuscation,
+            _ => EncryptionAlgorithm::Other(uri.to_string()),
+        }
+    }
+
+    /// Whether the resource is only obfuscated and can be restored without any external key
+    pub fn is_obfuscation(&self) -> bool {
+        matches!(
+            self,
+            EncryptionAlgorithm::IdpfFontObfuscation | EncryptionAlgorithm::AdobeFontObfuscation
+        )
+    }
+}
+
+impl BookEncryption {
+    pub fn from_xml(encryption_content: &str) -> Result<BookEncryption, EpubError> {
+        let mut reader = Reader::from_str(encryption_content);
+        reader.trim_text(true);
+
+        let mut buf = Vec::new();
+        let mut resources: Vec<EncryptedResource> = vec![];
+        let mut algorithm: Option<EncryptionAlgorithm> = None;
+        let mut uri: Option<String> = None;
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(ref e) if e.local_name().as_ref() == b"EncryptedData" => {
+                    algorithm = None;
+                    uri = None;
+                }
+                Event::Start(ref e) | Event::Empty(ref e)
+                    if e.local_name().as_ref() == b"EncryptionMethod" =>
+                {
+                    if let Some(value) = attribute_value(e, b"Algorithm")? {
+                        algorithm = Some(EncryptionAlgorithm::from_uri(&value));
+                    }
+                }
+                Event::Start(ref e) | Event::Empty(ref e)
+                    if e.local_name().as_ref() == b"CipherReference" =>
+                {
+                    uri = attribute_value(e, b"URI")?;
+                }
+                Event::End(ref e) if e.local_name().as_ref() == b"EncryptedData" => {
+                    if let (Some(algorithm), Some(uri)) = (algorithm.take(), uri.take()) {
+                        // Uris are relative to the root of the container, not to the META-INF directory
+                        let resource = ResourcePath::resolve_in_directory("", &uri);
+
+                        resources.push(EncryptedResource {
+                            path: resource.path,
+                            algorithm,
+                        });
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(BookEncryption {
+            resources,
+            rights: None,
+        })
+    }
+
+    /// Reads `META-INF/encryption.xml` and looks for the rights and license files of known DRM schemes.
+    /// Books without any of those files are not encrypted.
+    pub(crate) fn from_archive(zip: &mut ZipArchive<File>) -> Result<BookEncryption, EpubError> {
+        let mut encryption = match EBook::get_archive_file_content(zip, META_INF_ENCRYPTION_PATH) {
+            Err(EpubError::Zip(ZipError::FileNotFound)) => BookEncryption::default(),
+            result => BookEncryption::from_xml(&result?)?,
+        };
+
+        encryption.rights = match EBook::get_archive_file_bytes(zip, META_INF_RIGHTS_PATH) {
+            Ok(rights) => Some(DrmScheme::from_rights(&String::from_utf8_lossy(&rights))),
+            Err(EpubError::Zip(ZipError::FileNotFound)) => None,
+            Err(e) => return Err(e),
+        };
+
+        if encryption.rights.is_none() {
+            let file_names: Vec<&str> = zip.file_names().collect();
+
+            if file_names.contains(&META_INF_LCP_LICENSE_PATH) {
+                encryption.rights = Some(DrmScheme::ReadiumLcp);
+            } else if file_names.contains(&META_INF_SINF_PATH) {
+                encryption.rights = Some(DrmScheme::AppleFairPlay);
+            }
+        }
+
+        Ok(encryption)
+    }
+
+    pub fn find(&self, path: &str) -> Option<&EncryptedResource> {
+        self.resources.iter().find(|resource| resource.path == path)
+    }
+
+    pub fn protection_of(&self, path: &str) -> ResourceProtection {
+        match self.find(path) {
+            None => ResourceProtection::Plain,
+            Some(resource) if resource.algorithm.is_obfuscation() => {
+                ResourceProtection::FontObfuscated
+            }
+            Some(_) => ResourceProtection::DrmEncrypted,
+        }
+    }
+
+    /// DRM protecting the book, declared by its rights file or detected from encrypted resources.
+    /// Books with only obfuscated fonts are not protected.
+    pub fn drm_scheme(&self) -> Option<DrmScheme> {
+        let has_encrypted_resources = self
+            .resources
+            .iter()
+            .any(|resource| !resource.algorithm.is_obfuscation());
+
+        match self.rights {
+            Some(scheme) => Some(scheme),
+            None if has_encrypted_resources => Some(DrmScheme::Unknown),
+            None => None,
+        }
+    }
+}
+
+impl DrmScheme {
+    fn from_rights(rights_content: &str) -> DrmScheme {
+        if rights_content.contains(ADOBE_ADEPT_NAMESPACE) {
+            DrmScheme::AdobeAdept
+        } else {
+            DrmScheme::Unknown
+        }
+    }
+}
+
+impl EBook {
+    /// DRM protecting the book, `None` when the book can be read
+    pub fn drm_scheme(&self) -> Option<DrmScheme> {
+        self.encryption.drm_scheme()
+    }
+
+    pub fn resource_protection(&self, path: &str) -> ResourceProtection {
+        self.encryption.protection_of(path)
+    }
+}
+
+/// Restores the content of the obfuscated font. Content obfuscated with other algorithms is returned unchanged.
+pub fn deobfuscate(
+    content: &mut [u8],
+    algorithm: &EncryptionAlgorithm,
+    metadata: &BookMetadata,
+) -> Result<(), EpubError> {
+    let (key, obfuscated_length) = match algorithm {
+        EncryptionAlgorithm::IdpfFontObfuscation => {
+            // Key of an empty identifier would only turn the font into garbage
+            let identifier = metadata
+                .identifier
+                .as_deref()
+                .filter(|identifier| !identifier.trim().is_empty())
+                .ok_or(EpubError::MissingObfuscationKey)?;
+            (idpf_key(identifier), IDPF_OBFUSCATED_LENGTH)
+        }
+        EncryptionAlgorithm::AdobeFontObfuscation => {
+            let key = adobe_key(metadata).ok_or(EpubError::MissingObfuscationKey)?;
+            (key, ADOBE_OBFUSCATED_LENGTH)
+        }
+        EncryptionAlgorithm::Other(_) => return Ok(()),
+    };
+
+    for (index, byte) in content.iter_mut().take(obfuscated_length).enumerate() {
+        *byte ^= key[index % key.len()];
+    }
+
+    Ok(())
+}
+
+/// SHA-1 of the unique identifier with all of the whitespace removed
+/// https://www.w3.org/TR/epub-33/#sec-key-generation
+fn idpf_key(unique_identifier: &str) -> Vec<u8> {
+    let identifier: String = unique_identifier
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'))
+        .collect();
+
+    Sha1::digest(identifier.as_bytes()).to_vec()
+}
+
+/// Bytes of the uuid from the `urn:uuid` identifier, the unique one is preferred
+fn adobe_key(metadata: &BookMetadata) -> Option<Vec<u8>> {
+    let identifiers = metadata.identifier.iter().chain(
+        metadata
+            .identifiers
+            .iter()
+            .map(|identifier| &identifier.value),
+    );
+
+    identifiers
+        .filter_map(|identifier| parse_uuid(identifier))
+        .next()
+}
+
+fn parse_uuid(identifier: &str) -> Option<Vec<u8>> {
+    let identifier = identifier.trim();
+    let uuid = identifier
+        .strip_prefix("urn:uuid:")
+        .or_else(|| identifier.strip_prefix("uuid:"))
+        .unwrap_or(identifier);
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+
+    if hex.len() != 32 || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod encryption_tests {
+    use super::*;
+    use crate::metadata::Identifier;
+
+    const ENCRYPTION_CONTENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+    <encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+        xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+        <enc:EncryptedData>
+            <enc:EncryptionMethod Algorithm="http://www.idpf.org/2008/embedding"/>
+            <enc:CipherData><enc:CipherReference URI="OEBPS/Fonts/Kufi%20Regular.otf"/></enc:CipherData>
+        </enc:EncryptedData>
+        <enc:EncryptedData>
+            <enc:EncryptionMethod Algorithm="http://ns.adobe.com/pdf/enc#RC"/>
+            <enc:CipherData><enc:CipherReference URI="OEBPS/Fonts/Stix.ttf"/></enc:CipherData>
+        </enc:EncryptedData>
+        <enc:EncryptedData>
+            <enc:EncryptionMethod Algorithm="http://www.w3.org/2001/04/xmlenc#aes128-cbc"/>
+            <enc:CipherData><enc:CipherReference URI="OEBPS/Text/chapter_1.xhtml"/></enc:CipherData>
+        </enc:EncryptedData>
+    </encryption>"#;
+
+    fn create_metadata(identifier: &str) -> BookMetadata {
+        BookMetadata {
+            identifier: Some(identifier.to_string()),
+            identifiers: vec![Identifier {
+                value: identifier.to_string(),
+                scheme: None,
+            }],
+            ..BookMetadata::default()
+        }
+    }
+
+    #[test]
+    fn should_read_encrypted_resources_with_algorithms() {
+        let encryption = BookEncryption::from_xml(ENCRYPTION_CONTENT).unwrap();
+
+        assert_eq!(
+            encryption.resources,
+            vec![
+                EncryptedResource {
+                    path: "OEBPS/Fonts/Kufi Regular.otf".to_string(),
+                    algorithm: EncryptionAlgorithm::IdpfFontObfuscation,
+                },
+                EncryptedResource {
+                    path: "OEBPS/Fonts/Stix.ttf".to_string(),
+                    algorithm: EncryptionAlgorithm::AdobeFontObfuscation,
+                },
+                EncryptedResource {
+                    path: "OEBPS/Text/chapter_1.xhtml".to_string(),
+                    algorithm: EncryptionAlgorithm::Other(
+                        "http://www.w3.org/2001/04/xmlenc#aes128-cbc".to_string()
+                    ),
+                },
+            ]
+        );
+        assert!(encryption.find("OEBPS/Fonts/Stix.ttf").is_some());
+        assert!(!encryption.resources[2].algorithm.is_obfuscation());
+    }
+
+    #[test]
+    fn should_classify_resources_by_protection() {
+        let encryption = BookEncryption::from_xml(ENCRYPTION_CONTENT).unwrap();
+
+        assert_eq!(
+            encryption.protection_of("OEBPS/Fonts/Kufi Regular.otf"),
+            ResourceProtection::FontObfuscated
+        );
+        assert_eq!(
+            encryption.protection_of("OEBPS/Text/chapter_1.xhtml"),
+            ResourceProtection::DrmEncrypted
+        );
+        assert_eq!(
+            encryption.protection_of("OEBPS/Text/chapter_2.xhtml"),
+            ResourceProtection::Plain
+        );
+        assert_eq!(encryption.drm_scheme(), Some(DrmScheme::Unknown));
+    }
+
+    #[test]
+    fn should_not_treat_obfuscated_fonts_as_drm() {
+        let encryption = BookEncryption {
+            resources: vec![EncryptedResource {
+                path: "OEBPS/Fonts/Stix.ttf".to_string(),
+                algorithm: EncryptionAlgorithm::AdobeFontObfuscation,
+            }],
+            rights: None,
+        };
+
+        assert_eq!(encryption.drm_scheme(), None);
+    }
+
+    #[test]
+    fn should_recognize_adobe_rights() {
+        let rights = r#"<?xml version="1.0"?>
+        <adept:rights xmlns:adept="http://ns.adobe.com/adept">
+            <adept:licenseToken><adept:user>urn:uuid:0000</adept:user></adept:licenseToken>
+        </adept:rights>"#;
+
+        assert_eq!(DrmScheme::from_rights(rights), DrmScheme::AdobeAdept);
+        assert_eq!(DrmScheme::from_rights("<rights/>"), DrmScheme::Unknown);
+    }
+
+    #[test]
+    fn should_generate_idpf_key_from_identifier_without_whitespace() {
+        let expected = Sha1::digest(b"urn:uuid:12345").to_vec();
+
+        assert_eq!(idpf_key(" urn:uuid:\t123 45\n"), expected);
+        assert_eq!(expected.len(), 20);
+    }
+
+    #[test]
+    fn should_restore_font_obfuscated_with_idpf_algorithm() {
+        let metadata = create_metadata("urn:uuid:2e9fe5b4-6d9c-4a71-9b2c-1dd7d8d6e3f1");
+        let font: Vec<u8> = (0..2000).map(|index| (index % 251) as u8).collect();
+        let mut content = font.clone();
+
+        deobfuscate(
+            &mut content,
+            &EncryptionAlgorithm::IdpfFontObfuscation,
+            &metadata,
+        )
+        .unwrap();
+        assert_ne!(content[..1040], font[..1040]);
+        assert_eq!(content[1040..], font[1040..]);
+
+        deobfuscate(
+            &mut content,
+            &EncryptionAlgorithm::IdpfFontObfuscation,
+            &metadata,
+        )
+        .unwrap();
+        assert_eq!(content, font);
+    }
+
+    #[test]
+    fn should_restore_font_obfuscated_with_adobe_algorithm() {
+        let metadata = create_metadata("urn:uuid:00112233-4455-6677-8899-aabbccddeeff");
+        let mut content = vec![0u8; 1100];
+
+        deobfuscate(
+            &mut content,
+            &EncryptionAlgorithm::AdobeFontObfuscation,
+            &metadata,
+        )
+        .unwrap();
+
+        assert_eq!(content[..4], [0x00, 0x11, 0x22, 0x33]);
+        assert_eq!(content[16..18], [0x00, 0x11]);
+        assert_eq!(content[1023], 0xff);
+        assert_eq!(content[1024..], [0u8; 76]);
+    }
+
+    #[test]
+    fn should_return_error_when_there_is_no_uuid_for_adobe_algorithm() {
+        let metadata = create_metadata("isbn:9788324026589");
+        let mut content = vec![0u8; 10];
+
+        let result = deobfuscate(
+            &mut content,
+            &EncryptionAlgorithm::AdobeFontObfuscation,
+            &metadata,
+        );
+
+        assert!(matches!(result, Err(EpubError::MissingObfuscationKey)));
+    }
+
+    #[test]
+    fn should_return_error_when_there_is_no_identifier_for_idpf_algorithm() {
+        let metadata = BookMetadata::default();
+        let mut content = vec![0u8; 10];
+
+        let result = deobfuscate(
+            &mut content,
+            &EncryptionAlgorithm::IdpfFontObfuscation,
+            &metadata,
+        );
+
+        assert!(matches!(result, Err(EpubError::MissingObfuscationKey)));
+        assert_eq!(content, vec![0u8; 10]);
+    }
+
+    mod drm {
+        use super::*;
+        use crate::{
+            epub::META_INF_CONTAINER_PATH,
+            table_of_contents::table_of_contents_item::TableOfContentsItem,
+            test_utils::{create_archive, CONTAINER_CONTENT},
+        };
+
+        const OPF_CONTENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+            <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Protected</dc:title></metadata>
+            <manifest>
+                <item id="toc" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+                <item id="chapter" href="chapter.xhtml" media-type="application/xhtml+xml"/>
+            </manifest>
+            <spine toc="toc"><itemref idref="chapter"/></spine>
+        </package>"#;
+
+        fn encryption_of(paths: &[&str]) -> String {
+            let encrypted_data: Vec<String> = paths
+                .iter()
+                .map(|path| {
+                    format!(
+                        r#"<enc:EncryptedData>
+                            <enc:EncryptionMethod Algorithm="http://www.w3.org/2001/04/xmlenc#aes128-cbc"/>
+                            <enc:CipherData><enc:CipherReference URI="{}"/></enc:CipherData>
+                        </enc:EncryptedData>"#,
+                        path
+                    )
+                })
+                .collect();
+
+            format!(
+                r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container" xmlns:enc="http://www.w3.org/2001/04/xmlenc#">{}</encryption>"#,
+                encrypted_data.concat()
+            )
+        }
+
+        fn create_protected_epub(
+            name: &str,
+            encryption: &str,
+            extra_entries: &[(&str, &[u8])],
+        ) -> String {
+            let mut entries: Vec<(&str, &[u8])> = vec![
+                ("mimetype", b"application/epub+zip"),
+                (META_INF_CONTAINER_PATH, CONTAINER_CONTENT.as_bytes()),
+                (META_INF_ENCRYPTION_PATH, encryption.as_bytes()),
+                ("OEBPS/content.opf", OPF_CONTENT.as_bytes()),
+                ("OEBPS/toc.ncx", &[0x8f, 0x03, 0xa1, 0x77, 0x00, 0x3c]),
+                ("OEBPS/chapter.xhtml", &[0x12, 0xfe, 0x3c, 0x00, 0x9a]),
+            ];
+            entries.extend_from_slice(extra_entries);
+
+            create_archive(name, &entries)
+        }
+
+        #[test]
+        fn read_epub_should_open_book_with_encrypted_content_and_report_drm() {
+            let path = create_protected_epub(
+                "lore_leaf_drm_adobe.epub",
+                &encryption_of(&["OEBPS/toc.ncx", "OEBPS/chapter.xhtml"]),
+                &[(
+                    META_INF_RIGHTS_PATH,
+                    br#"<adept:rights xmlns:adept="http://ns.adobe.com/adept"/>"#,
+                )],
+            );
+
+            let book = EBook::read_epub(path).unwrap();
+            let chapter = book.chapter(&TableOfContentsItem::new(
+                "OEBPS/chapter.xhtml".to_string(),
+                "Chapter".to_string(),
+                None,
+            ));
+
+            assert_eq!(book.drm_scheme(), Some(DrmScheme::AdobeAdept));
+            assert_eq!(
+                book.resource_protection("OEBPS/chapter.xhtml"),
+                ResourceProtection::DrmEncrypted
+            );
+            assert_eq!(
+                book.resource_protection("OEBPS/content.opf"),
+                ResourceProtection::Plain
+            );
+            assert!(book.table_of_contents.items.is_empty());
+            assert!(matches!(
+                chapter,
+                Err(EpubError::EncryptedResource(path)) if path == "OEBPS/chapter.xhtml"
+            ));
+        }
+
+        #[test]
+        fn read_epub_should_recognize_lcp_license() {
+            let path = create_protected_epub(
+                "lore_leaf_drm_lcp.epub",
+                &encryption_of(&["OEBPS/chapter.xhtml"]),
+                &[("META-INF/license.lcpl", b"{}")],
+            );
+
+            let book = EBook::read_epub(path);
+
+            assert_eq!(book.unwrap().drm_scheme(), Some(DrmScheme::ReadiumLcp));
+        }
+
+        #[test]
+        fn read_epub_should_return_drm_error_when_package_document_is_encrypted() {
+            let path = create_protected_epub(
+                "lore_leaf_drm_opf.epub",
+                &encryption_of(&["OEBPS/content.opf", "OEBPS/chapter.xhtml"]),
+                &[],
+            );
+
+            let result = EBook::read_epub(path);
+
+            assert!(matches!(
+                result,
+                Err(EpubError::DrmProtected(DrmScheme::Unknown))
+            ));
+        }
+
+        #[test]
+        fn read_epub_should_not_report_drm_for_moby_dick() {
+            let book = EBook::read_epub("./test_data/epub/moby-dick.epub".to_string()).unwrap();
+
+            assert_eq!(book.drm_scheme(), None);
+            assert_eq!(
+                book.resource_protection("OPS/fonts/STIXGeneral.otf"),
+                ResourceProtection::Plain
+            );
+        }
+    }
+}
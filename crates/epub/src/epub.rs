@@ -1,18 +1,26 @@
 use std::{
     borrow::{Borrow, BorrowMut},
-    cell::RefCell,
     fs::File,
     io::Read,
     path::PathBuf,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex},
 };
 
 use quick_xml::{events::Event, name::QName, Reader};
-use zip::ZipArchive;
+use zip::{result::ZipError, ZipArchive};
 
 use crate::{
+    chapters::{
+        chapter::{Chapter, ChapterParsingMode},
+        chapter_cache::ChapterCache,
+    },
+    encoding::decode_document,
+    encryption::{BookEncryption, DrmScheme, ResourceProtection},
+    error::EpubError,
+    guide::BookGuide,
     manifest::BookManifest,
     metadata::BookMetadata,
+    options::EBookOptions,
     spine::BookSpine,
     table_of_contents::{
         table_of_contents::TableOfContents, table_of_contents_item::TableOfContentsItem,
@@ -22,11 +30,18 @@ use crate::{
 pub struct EBook {
     pub metadata: BookMetadata,
     pub path: String,
-    archive: RwLock<ZipArchive<File>>, //This probably should be moved to a separate struct
-    spine: BookSpine,
+    /// Reading an entry requires exclusive access to the archive, so reads from multiple threads are serialized
+    pub(crate) archive: Mutex<ZipArchive<File>>, //This probably should be moved to a separate struct
+    pub(crate) chapter_cache: Mutex<ChapterCache>,
+    pub(crate) chapter_parsing_mode: ChapterParsingMode,
+    pub spine: BookSpine,
     pub manifest: BookManifest,
+    pub(crate) guide: BookGuide,
     pub table_of_contents: TableOfContents,
-    _content_dir: PathBuf,
+    /// Resources listed in `META-INF/encryption.xml`, empty when the book has no such file
+    pub encryption: BookEncryption,
+    pub(crate) _content_dir: PathBuf,
+    pub(crate) opf_path: String,
     // pub reader_current_item: Option<TableOfContentsItem>,
 }
 
@@ -38,36 +53,42 @@ pub struct EBook {
 pub const META_INF_CONTAINER_PATH: &str = "META-INF/container.xml";
 
 impl EBook {
-    pub fn read_epub(epub_path: String) -> Result<EBook, Box<dyn std::error::Error>> {
+    pub fn read_epub(epub_path: String) -> Result<EBook, EpubError> {
+        EBook::read_epub_with_options(epub_path, &EBookOptions::default())
+    }
+
+    pub fn read_epub_with_options(
+        epub_path: String,
+        options: &EBookOptions,
+    ) -> Result<EBook, EpubError> {
         let epub_file = File::open(epub_path.clone())?;
         let mut archive = ZipArchive::new(epub_file)?;
 
         let opf_path = EBook::parse_container(&mut archive)?;
-        let book = EBook::parse_opf(archive, &opf_path, epub_path)?;
+        let book = EBook::parse_opf(archive, &opf_path, epub_path, options)?;
 
         Ok(book)
     }
 
-    fn parse_container(zip: &mut ZipArchive<File>) -> Result<String, Box<dyn std::error::Error>> {
+    pub(crate) fn parse_container(zip: &mut ZipArchive<File>) -> Result<String, EpubError> {
         //TODO: Check whether that should be dynamic of is it a standard for EPUBs
-        let mut container_file = zip.by_name(META_INF_CONTAINER_PATH)?;
-        let mut contents = String::new();
-        container_file.read_to_string(&mut contents)?;
+        let contents = match EBook::get_archive_file_content(zip, META_INF_CONTAINER_PATH) {
+            Err(EpubError::Zip(ZipError::FileNotFound)) => return Err(EpubError::MissingContainer),
+            result => result?,
+        };
 
         let mut reader = Reader::from_str(&contents);
         reader.trim_text(true);
 
-        let mut txt = Vec::new();
         let mut buf = Vec::new();
 
         // Find the OPF file path in the container XML
         let mut opf_path = String::new();
 
         loop {
-            match reader.read_event_into(&mut buf) {
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-                Ok(Event::Eof) => break,
-                Ok(Event::Start(e) | Event::Empty(e)) => {
+            match reader.read_event_into(&mut buf)? {
+                Event::Eof => break,
+                Event::Start(e) | Event::Empty(e) => {
                     if let b"rootfile" = e.name().as_ref() {
                         for attribute in e.attributes() {
                             let attr = attribute?;
@@ -78,14 +99,13 @@ impl EBook {
                         }
                     }
                 }
-                Ok(Event::Text(e)) => txt.push(e.unescape().unwrap().into_owned()),
                 _ => (),
             }
             buf.clear();
         }
 
         if opf_path.is_empty() {
-            Err("OPF file not found".into())
+            Err(EpubError::MissingOpf(None))
         } else {
             Ok(opf_path)
         }
@@ -96,82 +116,148 @@ impl EBook {
         mut zip: ZipArchive<File>,
         opf_path: &str,
         epub_path: String,
-    ) -> Result<EBook, Box<dyn std::error::Error>> {
-        let opf_content = EBook::get_archive_file_content(zip.borrow_mut(), opf_path)
-            .unwrap_or_else(|err| {
-                eprintln!("{:?}", err);
-                "NONE".to_string()
-            });
+        options: &EBookOptions,
+    ) -> Result<EBook, EpubError> {
+        let encryption = BookEncryption::from_archive(zip.borrow_mut())?;
+
+        // Some DRM schemes encrypt the package document too, there is nothing more to read from such book
+        if encryption.protection_of(opf_path) == ResourceProtection::DrmEncrypted {
+            return Err(EpubError::DrmProtected(
+                encryption.drm_scheme().unwrap_or(DrmScheme::Unknown),
+            ));
+        }
+
+        let opf_content = match EBook::get_archive_file_content(zip.borrow_mut(), opf_path) {
+            Err(EpubError::Zip(ZipError::FileNotFound)) => {
+                return Err(EpubError::MissingOpf(Some(opf_path.to_string())))
+            }
+            result => result?,
+        };
 
         //TODO: Looks really junky to do it like this, potential for improvement in getting content_dir path
         //If OPS directory is a common thing for all books it should be hardcoded, if not, then some better way for getting root directory for book resources will be needed
-        let content_dir = std::path::Path::new(opf_path).parent().unwrap().to_owned();
-
-        let (manifest, spine, metadata) = EBook::create_from_opf(&opf_content);
-
-        let (table_of_contents_href, table_of_contents_content) =
-            TableOfContents::read_table_of_contents_from_manifest(
-                zip.borrow_mut(),
-                &manifest,
-                content_dir.borrow(),
-            );
-
-        let table_of_contents = TableOfContents::from_content(
-            table_of_contents_href,
-            table_of_contents_content,
-            content_dir.to_str().unwrap().to_owned(),
-        );
+        let content_dir = std::path::Path::new(opf_path)
+            .parent()
+            .unwrap_or(std::path::Path::new(""))
+            .to_owned();
+
+        let (manifest, spine, metadata) = EBook::create_from_opf(&opf_content)?;
+        let guide = BookGuide::from_opf(&opf_content)?;
+
+        let table_of_contents = match TableOfContents::read_table_of_contents_from_manifest(
+            zip.borrow_mut(),
+            &manifest,
+            &spine,
+            opf_path,
+            options.preferred_table_of_contents,
+        ) {
+            // Encrypted navigation can not be parsed, but the book is still opened to tell why it can not be read
+            Err(_) if encryption.drm_scheme().is_some() => TableOfContents::default(),
+            result => result?,
+        };
 
         Ok(Self {
             manifest,
             spine,
+            guide,
             metadata,
             path: epub_path,
             table_of_contents,
+            encryption,
             _content_dir: content_dir,
-            archive: RwLock::new(zip),
+            opf_path: opf_path.to_string(),
+            archive: Mutex::new(zip),
+            chapter_cache: Mutex::new(ChapterCache::new(options.chapter_cache_capacity)),
+            chapter_parsing_mode: options.chapter_parsing_mode,
         })
     }
 
     pub(crate) fn get_archive_file_content(
         zip: &mut ZipArchive<File>,
         resource_path: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
+    ) -> Result<String, EpubError> {
+        let contents = EBook::get_archive_file_bytes(zip, resource_path)?;
+
+        decode_document(&contents)
+    }
+
+    pub(crate) fn get_archive_file_bytes(
+        zip: &mut ZipArchive<File>,
+        resource_path: &str,
+    ) -> Result<Vec<u8>, EpubError> {
         let mut resource_file = zip.by_name(resource_path)?;
-        let mut contents = String::new();
+        let mut contents = Vec::new();
 
-        resource_file.read_to_string(&mut contents)?;
+        resource_file.read_to_end(&mut contents)?;
 
         Ok(contents)
     }
 
-    fn create_from_opf(opf_content: &String) -> (BookManifest, BookSpine, BookMetadata) {
-        let manifest = BookManifest::from_opf(opf_content);
-        let metadata = BookMetadata::from_opf(opf_content);
-        let spine = BookSpine::from_opf_and_manifest(opf_content, manifest.borrow());
+    fn create_from_opf(
+        opf_content: &str,
+    ) -> Result<(BookManifest, BookSpine, BookMetadata), EpubError> {
+        let manifest = BookManifest::from_opf(opf_content)?;
+        let metadata = BookMetadata::from_opf(opf_content)?;
+        let spine = BookSpine::from_opf_and_manifest(opf_content, manifest.borrow())?;
 
-        (manifest, spine, metadata)
+        Ok((manifest, spine, metadata))
     }
 
     pub fn get_content_by_toc_item(
-        &mut self,
+        &self,
         toc_item: &TableOfContentsItem,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        //TODO: Fix it, so that subdirectories of epub file are detected automatically
+    ) -> Result<String, EpubError> {
+        let content = self.read_resource_at(&toc_item.path)?;
 
-        let archive = self.archive.get_mut().expect("Could not get archive");
-        let mut target_file_content = archive.by_name(&toc_item.path)?;
-        let mut contents = String::new();
+        decode_document(&content)
+    }
 
-        target_file_content.read_to_string(&mut contents)?;
+    /// Returns parsed chapter pointed by given item. Recently used chapters are served from the cache,
+    /// without reading the archive and parsing the content again.
+    pub fn chapter(&self, toc_item: &TableOfContentsItem) -> Result<Arc<Chapter>, EpubError> {
+        let cached = self
+            .chapter_cache
+            .lock()
+            .expect("Lock poisoned")
+            .get(&toc_item.path);
+
+        let chapter = match cached {
+            Some(chapter) => chapter,
+            None => {
+                let content = self.get_content_by_toc_item(toc_item)?;
+                let chapter = Arc::new(Chapter::from_item_with_content_and_mode(
+                    toc_item.clone(),
+                    content,
+                    self.chapter_parsing_mode,
+                ));
+
+                self.chapter_cache
+                    .lock()
+                    .expect("Lock poisoned")
+                    .insert(Arc::clone(&chapter));
+
+                chapter
+            }
+        };
 
-        Ok(contents)
+        // The same document can be labeled differently, e.g. when the table of contents points to its sections
+        if chapter.label == toc_item.label {
+            return Ok(chapter);
+        }
+
+        Ok(Arc::new(Chapter {
+            label: toc_item.label.clone(),
+            ..(*chapter).clone()
+        }))
     }
 }
 
 #[cfg(test)]
 mod book_tests {
-    use crate::manifest::ManifestItem;
+    use crate::{
+        manifest::{ManifestItem, ManifestItemProperty},
+        test_utils::create_archive,
+    };
 
     use super::*;
 
@@ -225,17 +311,45 @@ mod book_tests {
             "This work is shared with the public using the Attribution-ShareAlike 3.0 Unported (CC BY-SA 3.0) license.".to_string());
     }
 
+    #[test]
+    fn parse_opf_should_return_book_with_refined_creators_and_contributors() {
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+
+        let book_metadata = book.metadata;
+
+        assert_eq!(book_metadata.creators.len(), 1);
+        assert_eq!(book_metadata.creators[0].name, "Herman Melville");
+        assert_eq!(
+            book_metadata.creators[0].file_as,
+            Some("MELVILLE, HERMAN".to_string())
+        );
+        assert_eq!(book_metadata.creators[0].roles, vec!["aut".to_string()]);
+        assert_eq!(book_metadata.contributors.len(), 1);
+        assert_eq!(book_metadata.contributors[0].name, "Dave Cramer");
+        assert_eq!(book_metadata.contributors[0].roles, vec!["mrk".to_string()]);
+        assert_eq!(
+            book_metadata.modified,
+            Some("2012-01-18T12:47:00Z".to_string())
+        );
+    }
+
     #[test]
     fn parse_opf_should_return_book_with_correct_spine() {
         let expected_cover_manifest_item = ManifestItem {
             id: "cover".to_string(),
             href: "cover.xhtml".to_string(),
             media_type: "application/xhtml+xml".to_string(),
+            properties: vec![],
+            fallback: None,
+            media_overlay: None,
         };
         let expected_toc_manifest_item = ManifestItem {
             id: "toc".to_string(),
             href: "toc.xhtml".to_string(),
             media_type: "application/xhtml+xml".to_string(),
+            properties: vec![ManifestItemProperty::Nav],
+            fallback: None,
+            media_overlay: None,
         };
 
         let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
@@ -272,4 +386,153 @@ mod book_tests {
         assert_eq!(manifest.items[149].id, "xchapter_136");
         assert_eq!(manifest.items[150].id, "brief-toc");
     }
+
+    #[test]
+    fn read_epub_should_return_io_error_when_file_does_not_exist() {
+        let result = EBook::read_epub("./test_data/epub/missing.epub".to_string());
+
+        assert!(matches!(result, Err(EpubError::Io(_))));
+    }
+
+    #[test]
+    fn read_epub_should_return_zip_error_when_file_is_not_an_archive() {
+        let path = std::env::temp_dir().join("lore_leaf_not_an_archive.epub");
+        std::fs::write(&path, "definitely not a zip").unwrap();
+
+        let result = EBook::read_epub(path.to_str().unwrap().to_string());
+
+        assert!(matches!(result, Err(EpubError::Zip(_))));
+    }
+
+    #[test]
+    fn read_epub_should_return_missing_container_error_when_there_is_no_container() {
+        let path = create_archive(
+            "lore_leaf_missing_container.epub",
+            &[("mimetype", "application/epub+zip".as_bytes())],
+        );
+
+        let result = EBook::read_epub(path);
+
+        assert!(matches!(result, Err(EpubError::MissingContainer)));
+    }
+
+    #[test]
+    fn read_epub_should_return_missing_opf_error_when_opf_is_not_in_archive() {
+        const CONTAINER: &str = r#"<?xml version="1.0"?>
+            <container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+                <rootfiles>
+                    <rootfile full-path="OPS/package.opf" media-type="application/oebps-package+xml"/>
+                </rootfiles>
+            </container>"#;
+        let path = create_archive(
+            "lore_leaf_missing_opf.epub",
+            &[
+                ("mimetype", "application/epub+zip".as_bytes()),
+                (META_INF_CONTAINER_PATH, CONTAINER.as_bytes()),
+            ],
+        );
+
+        let result = EBook::read_epub(path);
+
+        match result {
+            Err(EpubError::MissingOpf(Some(opf_path))) => assert_eq!(opf_path, "OPS/package.opf"),
+            _ => panic!("Expected missing OPF error"),
+        }
+    }
+
+    #[test]
+    fn read_epub_should_return_xml_error_when_container_is_malformed() {
+        let path = create_archive(
+            "lore_leaf_malformed_container.epub",
+            &[
+                ("mimetype", "application/epub+zip".as_bytes()),
+                (
+                    META_INF_CONTAINER_PATH,
+                    "<container><rootfiles></container>".as_bytes(),
+                ),
+            ],
+        );
+
+        let result = EBook::read_epub(path);
+
+        assert!(matches!(result, Err(EpubError::Xml(_))));
+    }
+
+    #[test]
+    fn chapter_should_be_served_from_cache_when_read_again() {
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let toc_item = book.table_of_contents.items[4].clone();
+
+        let first_read = book.chapter(&toc_item).unwrap();
+        let second_read = book.chapter(&toc_item).unwrap();
+
+        assert!(Arc::ptr_eq(&first_read, &second_read));
+        assert_eq!(first_read.path, "OPS/chapter_001.xhtml");
+    }
+
+    #[test]
+    fn chapter_should_keep_label_of_requested_item_when_served_from_cache() {
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let toc_item = book.table_of_contents.items[4].clone();
+        let relabeled_item =
+            TableOfContentsItem::new(toc_item.path.clone(), "Loomings".to_string(), None);
+
+        let chapter = book.chapter(&toc_item).unwrap();
+        let relabeled_chapter = book.chapter(&relabeled_item).unwrap();
+
+        assert_eq!(relabeled_chapter.label, "Loomings");
+        assert!(Arc::ptr_eq(
+            &chapter.recreated_structure,
+            &relabeled_chapter.recreated_structure
+        ));
+    }
+
+    #[test]
+    fn chapter_should_be_parsed_again_when_cache_is_disabled() {
+        let options = EBookOptions {
+            chapter_cache_capacity: 0,
+            ..EBookOptions::default()
+        };
+        let book = EBook::read_epub_with_options(MOBY_DICK_PATH.to_string(), &options).unwrap();
+        let toc_item = book.table_of_contents.items[4].clone();
+
+        let first_read = book.chapter(&toc_item).unwrap();
+        let second_read = book.chapter(&toc_item).unwrap();
+
+        assert!(!Arc::ptr_eq(&first_read, &second_read));
+        assert_eq!(first_read, second_read);
+    }
+
+    #[test]
+    fn book_should_be_readable_from_multiple_threads() {
+        fn assert_send_and_sync<T: Send + Sync>() {}
+        assert_send_and_sync::<EBook>();
+
+        let book = Arc::new(EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap());
+
+        let handles: Vec<_> = (0..4)
+            .map(|index| {
+                let book = Arc::clone(&book);
+                std::thread::spawn(move || {
+                    let toc_item = book.table_of_contents.items[index + 4].clone();
+                    book.chapter(&toc_item).unwrap().path.clone()
+                })
+            })
+            .collect();
+
+        let paths: Vec<String> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "OPS/chapter_001.xhtml",
+                "OPS/chapter_002.xhtml",
+                "OPS/chapter_003.xhtml",
+                "OPS/chapter_004.xhtml"
+            ]
+        );
+    }
 }
@@ -0,0 +1,131 @@
+use std::{fmt, io, string::FromUtf8Error};
+
+use quick_xml::events::attributes::AttrError;
+use zip::result::ZipError;
+
+use crate::encryption::DrmScheme;
+
+/// Errors that can occur while opening and parsing an epub file
+#[derive(Debug)]
+pub enum EpubError {
+    /// The epub file could not be opened or read
+    Io(io::Error),
+    /// The epub file is not a valid zip archive, or an entry could not be read from it
+    Zip(ZipError),
+    /// One of the XML documents inside of the epub is malformed
+    Xml(quick_xml::Error),
+    /// There is no `META-INF/container.xml` file inside of the archive
+    MissingContainer,
+    /// The OPF file is not declared in the container, or the declared path does not exist in the archive
+    MissingOpf(Option<String>),
+    /// The spine references a manifest item with given id, but there is no such item in the manifest
+    DanglingSpineReference(String),
+    /// One of the documents inside of the epub is not a valid UTF-8
+    InvalidUtf8(FromUtf8Error),
+    /// The table of contents is declared in the manifest, but it is missing from the archive
+    MissingTableOfContents,
+    /// Resource with given path is referenced by the book, but it is missing from the archive
+    MissingResource(String),
+    /// Resource with given path is encrypted, e.g. by DRM, and can not be read without a key
+    EncryptedResource(String),
+    /// The book is protected with DRM, so its content can not be read
+    DrmProtected(DrmScheme),
+    /// Font is obfuscated, but the book has no identifier to restore it: the unique one for the IDPF algorithm,
+    /// or a `urn:uuid` one for the Adobe algorithm
+    MissingObfuscationKey,
+    /// There is no item at given position of the spine, e.g. because the spine is empty
+    MissingSpineItem(usize),
+    /// Reading location is not a valid EPUB CFI, e.g. `epubcfi(/6/4!/4/2/1:0)`
+    InvalidCfi(String),
+}
+
+impl fmt::Display for EpubError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpubError::Io(e) => write!(f, "could not read the epub file: {}", e),
+            EpubError::Zip(e) => write!(f, "epub file is not a valid zip archive: {}", e),
+            EpubError::Xml(e) => write!(f, "epub contains a malformed XML document: {}", e),
+            EpubError::MissingContainer => {
+                write!(
+                    f,
+                    "epub does not contain {}",
+                    crate::epub::META_INF_CONTAINER_PATH
+                )
+            }
+            EpubError::MissingOpf(None) => write!(f, "container does not declare an OPF file"),
+            EpubError::MissingOpf(Some(path)) => {
+                write!(f, "OPF file {} is missing from the epub", path)
+            }
+            EpubError::DanglingSpineReference(idref) => write!(
+                f,
+                "spine references item {} which is missing from the manifest",
+                idref
+            ),
+            EpubError::InvalidUtf8(e) => write!(f, "epub contains invalid UTF-8 text: {}", e),
+            EpubError::MissingTableOfContents => {
+                write!(f, "epub does not contain a table of contents")
+            }
+            EpubError::MissingResource(path) => {
+                write!(f, "resource {} is missing from the epub", path)
+            }
+            EpubError::EncryptedResource(path) => {
+                write!(f, "resource {} is encrypted and can not be read", path)
+            }
+            EpubError::DrmProtected(scheme) => {
+                write!(f, "epub is protected with {} and can not be read", scheme)
+            }
+            EpubError::MissingObfuscationKey => {
+                write!(
+                    f,
+                    "epub has no identifier needed to restore obfuscated fonts"
+                )
+            }
+            EpubError::MissingSpineItem(position) => {
+                write!(f, "spine has no item at position {}", position)
+            }
+            EpubError::InvalidCfi(value) => write!(f, "{} is not a valid EPUB CFI", value),
+        }
+    }
+}
+
+impl std::error::Error for EpubError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EpubError::Io(e) => Some(e),
+            EpubError::Zip(e) => Some(e),
+            EpubError::Xml(e) => Some(e),
+            EpubError::InvalidUtf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EpubError {
+    fn from(e: io::Error) -> Self {
+        EpubError::Io(e)
+    }
+}
+
+impl From<ZipError> for EpubError {
+    fn from(e: ZipError) -> Self {
+        EpubError::Zip(e)
+    }
+}
+
+impl From<quick_xml::Error> for EpubError {
+    fn from(e: quick_xml::Error) -> Self {
+        EpubError::Xml(e)
+    }
+}
+
+impl From<AttrError> for EpubError {
+    fn from(e: AttrError) -> Self {
+        EpubError::Xml(quick_xml::Error::InvalidAttr(e))
+    }
+}
+
+impl From<FromUtf8Error> for EpubError {
+    fn from(e: FromUtf8Error) -> Self {
+        EpubError::InvalidUtf8(e)
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{
+    encoding::decode_document,
+    encryption::deobfuscate,
+    epub::EBook,
+    error::EpubError,
+    styles::{computed_style::FontStyle, stylesheet::Stylesheet},
+};
+
+const STYLESHEET_MEDIA_TYPE: &str = "text/css";
+
+/// Font embedded in the book and declared in one of its stylesheets with `@font-face`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedFont {
+    pub family: String,
+    pub font_weight: u16,
+    pub font_style: FontStyle,
+    /// Full path of the font file in the archive
+    pub path: String,
+}
+
+impl EBook {
+    /// Reads `@font-face` declarations from all of the stylesheets in the manifest and returns the fonts
+    /// embedded in the archive. The first source of the declaration found in the archive is used.
+    pub fn embedded_fonts(&self) -> Result<Vec<EmbeddedFont>, EpubError> {
+        let mut fonts: Vec<EmbeddedFont> = vec![];
+
+        let stylesheet_items = self
+            .manifest
+            .items
+            .iter()
+            .filter(|item| item.media_type == STYLESHEET_MEDIA_TYPE);
+
+        for item in stylesheet_items {
+            let stylesheet_path = self.resolve_href(self.opf_path(), &item.href).path;
+            let content = match self.read_resource_at(&stylesheet_path) {
+                Ok(content) => content,
+                Err(EpubError::MissingResource(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            let stylesheet = Stylesheet::parse(&decode_document(&content)?);
+
+            for font_face in stylesheet.font_faces {
+                let path = font_face
+                    .sources
+                    .iter()
+                    .map(|source| self.resolve_href(&stylesheet_path, source).path)
+                    .find(|path| self.contains_resource(path));
+
+                let Some(path) = path else {
+                    continue;
+                };
+
+                let font = EmbeddedFont {
+                    family: font_face.family,
+                    font_weight: font_face.font_weight,
+                    font_style: font_face.font_style,
+                    path,
+                };
+
+                if !fonts.contains(&font) {
+                    fonts.push(font);
+                }
+            }
+        }
+
+        Ok(fonts)
+    }
+
+    /// Reads bytes of the font, restoring them when the font is obfuscated
+    pub fn read_font(&self, font: &EmbeddedFont) -> Result<Vec<u8>, EpubError> {
+        let mut content = self.read_resource_at(&font.path)?;
+
+        if let Some(resource) = self.encryption.find(&font.path) {
+            deobfuscate(&mut content, &resource.algorithm, &self.metadata)?;
+        }
+
+        Ok(content)
+    }
+
+    fn contains_resource(&self, path: &str) -> bool {
+        self.archive
+            .lock()
+            .expect("Lock poisoned")
+            .file_names()
+            .any(|name| name == path)
+    }
+}
+
+#[cfg(test)]
+mod fonts_tests {
+    use sha1::{Digest, Sha1};
+
+    use super::*;
+    use crate::{
+        encryption::META_INF_ENCRYPTION_PATH,
+        epub::META_INF_CONTAINER_PATH,
+        test_utils::{create_archive, CONTAINER_CONTENT},
+    };
+
+    const MOBY_DICK_PATH: &str = "./test_data/epub/moby-dick.epub";
+
+    const UNIQUE_IDENTIFIER: &str = "urn:uuid:2e9fe5b4-6d9c-4a71-9b2c-1dd7d8d6e3f1";
+
+    fn create_book_with_fonts(name: &str, encryption: &str, fonts: &[(&str, &[u8])]) -> EBook {
+        let opf = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+                    <dc:title>Fonts</dc:title>
+                    <dc:identifier id="isbn">isbn:9788324026589</dc:identifier>
+                    <dc:identifier id="uid">{}</dc:identifier>
+                </metadata>
+                <manifest>
+                    <item id="chapter" href="Text/chapter.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="style" href="Styles/fonts.css" media-type="text/css"/>
+                    <item id="kufi" href="Fonts/kufi.otf" media-type="font/otf"/>
+                    <item id="stix" href="Fonts/stix.ttf" media-type="font/ttf"/>
+                </manifest>
+                <spine><itemref idref="chapter"/></spine>
+            </package>"#,
+            UNIQUE_IDENTIFIER
+        );
+        let stylesheet = r#"
+            @font-face { font-family: "Kufi"; src: url(../Fonts/kufi.woff2), url(../Fonts/kufi.otf); }
+            @font-face { font-family: "Stix"; font-weight: bold; src: url(../Fonts/stix.ttf); }
+            @font-face { font-family: "Missing"; src: url(../Fonts/missing.ttf); }
+        "#;
+        let mut entries: Vec<(String, &[u8])> = vec![
+            ("mimetype".to_string(), b"application/epub+zip"),
+            (
+                META_INF_CONTAINER_PATH.to_string(),
+                CONTAINER_CONTENT.as_bytes(),
+            ),
+            ("OEBPS/content.opf".to_string(), opf.as_bytes()),
+            ("OEBPS/Text/chapter.xhtml".to_string(), b"<html/>"),
+            ("OEBPS/Styles/fonts.css".to_string(), stylesheet.as_bytes()),
+        ];
+        if !encryption.is_empty() {
+            entries.push((META_INF_ENCRYPTION_PATH.to_string(), encryption.as_bytes()));
+        }
+        for (path, content) in fonts {
+            entries.push((format!("OEBPS/{}", path), content));
+        }
+        let entries: Vec<(&str, &[u8])> = entries
+            .iter()
+            .map(|(path, content)| (path.as_str(), *content))
+            .collect();
+
+        EBook::read_epub(create_archive(name, &entries)).unwrap()
+    }
+
+    fn encryption_of(path: &str, algorithm: &str) -> String {
+        format!(
+            r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container" xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+                <enc:EncryptedData>
+                    <enc:EncryptionMethod Algorithm="{}"/>
+                    <enc:CipherData><enc:CipherReference URI="{}"/></enc:CipherData>
+                </enc:EncryptedData>
+            </encryption>"#,
+            algorithm, path
+        )
+    }
+
+    fn create_font() -> Vec<u8> {
+        (0..1500).map(|index| (index % 256) as u8).collect()
+    }
+
+    #[test]
+    fn embedded_fonts_should_return_fonts_declared_in_stylesheets_and_present_in_archive() {
+        let book = create_book_with_fonts(
+            "lore_leaf_fonts_declared.epub",
+            "",
+            &[("Fonts/kufi.otf", b"kufi"), ("Fonts/stix.ttf", b"stix")],
+        );
+
+        let fonts = book.embedded_fonts().unwrap();
+
+        assert_eq!(
+            fonts,
+            vec![
+                EmbeddedFont {
+                    family: "Kufi".to_string(),
+                    font_weight: 400,
+                    font_style: FontStyle::Normal,
+                    path: "OEBPS/Fonts/kufi.otf".to_string(),
+                },
+                EmbeddedFont {
+                    family: "Stix".to_string(),
+                    font_weight: 700,
+                    font_style: FontStyle::Normal,
+                    path: "OEBPS/Fonts/stix.ttf".to_string(),
+                },
+            ]
+        );
+        assert_eq!(book.read_font(&fonts[0]).unwrap(), b"kufi");
+    }
+
+    #[test]
+    fn read_font_should_restore_font_obfuscated_with_idpf_algorithm() {
+        let font = create_font();
+        let key = Sha1::digest(UNIQUE_IDENTIFIER.as_bytes());
+        let obfuscated: Vec<u8> = font
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| {
+                if index < 1040 {
+                    byte ^ key[index % 20]
+                } else {
+                    *byte
+                }
+            })
+            .collect();
+        let book = create_book_with_fonts(
+            "lore_leaf_fonts_idpf.epub",
+            &encryption_of("OEBPS/Fonts/kufi.otf", "http://www.idpf.org/2008/embedding"),
+            &[("Fonts/kufi.otf", &obfuscated)],
+        );
+
+        let fonts = book.embedded_fonts().unwrap();
+
+        assert_eq!(book.read_font(&fonts[0]).unwrap(), font);
+    }
+
+    #[test]
+    fn read_font_should_restore_font_obfuscated_with_adobe_algorithm() {
+        let font = create_font();
+        let key: [u8; 16] = [
+            0x2e, 0x9f, 0xe5, 0xb4, 0x6d, 0x9c, 0x4a, 0x71, 0x9b, 0x2c, 0x1d, 0xd7, 0xd8, 0xd6,
+            0xe3, 0xf1,
+        ];
+        let obfuscated: Vec<u8> = font
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| {
+                if index < 1024 {
+                    byte ^ key[index % 16]
+                } else {
+                    *byte
+                }
+            })
+            .collect();
+        let book = create_book_with_fonts(
+            "lore_leaf_fonts_adobe.epub",
+            &encryption_of("OEBPS/Fonts/stix.ttf", "http://ns.adobe.com/pdf/enc#RC"),
+            &[("Fonts/stix.ttf", &obfuscated)],
+        );
+
+        let fonts = book.embedded_fonts().unwrap();
+
+        assert_eq!(book.read_font(&fonts[0]).unwrap(), font);
+    }
+
+    #[test]
+    fn read_font_should_return_error_when_font_is_encrypted() {
+        let book = create_book_with_fonts(
+            "lore_leaf_fonts_encrypted.epub",
+            &encryption_of(
+                "OEBPS/Fonts/kufi.otf",
+                "http://www.w3.org/2001/04/xmlenc#aes128-cbc",
+            ),
+            &[("Fonts/kufi.otf", b"encrypted")],
+        );
+
+        let fonts = book.embedded_fonts().unwrap();
+
+        assert!(matches!(
+            book.read_font(&fonts[0]),
+            Err(EpubError::EncryptedResource(path)) if path == "OEBPS/Fonts/kufi.otf"
+        ));
+    }
+
+    #[test]
+    fn embedded_fonts_should_return_stix_fonts_of_moby_dick() {
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+
+        let fonts = book.embedded_fonts().unwrap();
+
+        assert_eq!(fonts.len(), 4);
+        assert!(fonts.iter().all(|font| font.family == "Stix"));
+        assert!(fonts
+            .iter()
+            .any(|font| font.path == "OPS/fonts/STIXGeneralBolIta.otf"
+                && font.font_weight == 700
+                && font.font_style == FontStyle::Italic));
+        // STIX fonts are not obfuscated, so they start with the OpenType signature
+        assert_eq!(book.read_font(&fonts[0]).unwrap()[..4], *b"OTTO");
+    }
+}
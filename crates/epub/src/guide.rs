@@ -0,0 +1,119 @@
+use quick_xml::{events::Event, name::QName, Reader};
+
+use crate::error::EpubError;
+
+/// Struct to represent the epub 2 `<guide>` element of the OPF.
+/// It is deprecated in epub 3, but a lot of books still use it to point to the cover and other landmarks.
+#[derive(Debug, Default)]
+pub struct BookGuide {
+    pub references: Vec<GuideReference>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuideReference {
+    /// Value of the `type` attribute, e.g. `cover`, `toc` or `text`
+    pub reference_type: String,
+    pub title: Option<String>,
+    /// Href relative to the OPF file, it may contain a fragment
+    pub href: String,
+}
+
+impl BookGuide {
+    pub fn from_opf(opf_content: &str) -> Result<BookGuide, EpubError> {
+        let mut reader = Reader::from_str(opf_content);
+        reader.trim_text(true);
+
+        let mut buf = Vec::new();
+        let mut references: Vec<GuideReference> = vec![];
+        let mut is_inside_guide = false;
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(ref e) if e.local_name().as_ref() == b"guide" => {
+                    is_inside_guide = true;
+                }
+                Event::End(ref e) if e.local_name().as_ref() == b"guide" => break,
+                Event::Start(ref e) | Event::Empty(ref e)
+                    if is_inside_guide && e.local_name().as_ref() == b"reference" =>
+                {
+                    references.push(BookGuide::recreate_reference(e)?);
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(BookGuide { references })
+    }
+
+    fn recreate_reference(
+        e: &quick_xml::events::BytesStart<'_>,
+    ) -> Result<GuideReference, EpubError> {
+        let mut reference = GuideReference {
+            reference_type: String::new(),
+            title: None,
+            href: String::new(),
+        };
+
+        for attribute in e.attributes() {
+            let attr = attribute?;
+            let value = attr.unescape_value()?.to_string();
+
+            match attr.key {
+                QName(b"type") => reference.reference_type = value,
+                QName(b"title") => reference.title = Some(value),
+                QName(b"href") => reference.href = value,
+                _ => {}
+            }
+        }
+
+        Ok(reference)
+    }
+
+    /// Type comparison is case insensitive, since books use both `cover` and `Cover`
+    pub fn find_by_type(&self, reference_type: &str) -> Option<&GuideReference> {
+        self.references.iter().find(|reference| {
+            reference
+                .reference_type
+                .eq_ignore_ascii_case(reference_type)
+        })
+    }
+}
+
+#[cfg(test)]
+mod guide_tests {
+    use super::*;
+
+    #[test]
+    fn should_read_references_from_guide() {
+        const OPF_CONTENT: &str = r#"
+        <package version="2.0">
+            <manifest>
+                <item id="cover" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+            </manifest>
+            <guide>
+                <reference type="cover" title="Cover" href="Text/cover.xhtml"/>
+                <reference type="text" href="Text/chapter_1.xhtml#start"/>
+            </guide>
+        </package>
+        "#;
+
+        let guide = BookGuide::from_opf(OPF_CONTENT).unwrap();
+
+        assert_eq!(guide.references.len(), 2);
+        assert_eq!(
+            guide.find_by_type("Cover"),
+            Some(&GuideReference {
+                reference_type: "cover".to_string(),
+                title: Some("Cover".to_string()),
+                href: "Text/cover.xhtml".to_string(),
+            })
+        );
+        assert_eq!(
+            guide.find_by_type("text").unwrap().href,
+            "Text/chapter_1.xhtml#start"
+        );
+        assert!(guide.find_by_type("toc").is_none());
+    }
+}
@@ -1,10 +1,28 @@
+pub mod cfi;
 pub mod chapters;
+pub mod cover;
+pub mod encoding;
+pub mod encryption;
 pub mod epub;
-mod manifest;
-mod metadata;
+pub mod error;
+pub mod fonts;
+mod guide;
+pub mod manifest;
+pub mod media_overlay;
+pub mod metadata;
+pub mod notes;
+pub mod options;
 pub mod reader;
-mod spine;
+pub mod rendition;
+pub mod resources;
+pub mod spine;
 mod strings;
+pub mod styles;
 pub mod table_of_contents;
+#[cfg(test)]
+mod test_utils;
+pub mod validation;
+pub mod writer;
+mod xml;
 
 use manifest::{BookManifest, ManifestItem};
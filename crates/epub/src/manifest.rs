@@ -2,6 +2,8 @@ use std::sync::Arc;
 
 use quick_xml::{events::Event, name::QName, Reader};
 
+use crate::error::EpubError;
+
 /// Struct to represent all items from the manifest
 pub struct BookManifest {
     /// Each epub needs to have a list of manifest items
@@ -10,18 +12,18 @@ pub struct BookManifest {
 }
 
 impl BookManifest {
-    pub fn from_opf(opf_content: &str) -> BookManifest {
+    pub fn from_opf(opf_content: &str) -> Result<BookManifest, EpubError> {
         let mut reader = Reader::from_str(opf_content);
         reader.trim_text(true);
 
         let mut buf = Vec::new();
         let mut manifest_items: Vec<Arc<ManifestItem>> = vec![];
 
-        while let Ok(event) = reader.read_event_into(&mut buf) {
-            match event {
+        loop {
+            match reader.read_event_into(&mut buf)? {
                 Event::Start(ref e) | Event::Empty(ref e) => {
                     if let b"item" = e.name().as_ref() {
-                        BookManifest::recreate_manifest_entry(e, &mut manifest_items);
+                        BookManifest::recreate_manifest_entry(e, &mut manifest_items)?;
                     }
                 }
                 Event::Eof => break,
@@ -30,32 +32,38 @@ impl BookManifest {
             buf.clear();
         }
 
-        BookManifest {
+        Ok(BookManifest {
             items: manifest_items,
-        }
+        })
     }
 
     //TODO: Consider moving to ManifestItem
     fn recreate_manifest_entry(
         e: &quick_xml::events::BytesStart<'_>,
         manifest_items: &mut Vec<Arc<ManifestItem>>,
-    ) {
+    ) -> Result<(), EpubError> {
         let mut id = String::new();
         let mut href = String::new();
         let mut media_type = String::new();
+        let mut properties: Vec<ManifestItemProperty> = vec![];
+        let mut fallback: Option<String> = None;
+        let mut media_overlay: Option<String> = None;
 
         for attribute_result in e.attributes() {
-            let attribute = attribute_result.unwrap();
+            let attribute = attribute_result?;
+            let value = attribute.unescape_value()?.to_string();
             match attribute.key {
-                QName(b"id") => {
-                    id = String::from_utf8(attribute.value.into_owned()).unwrap();
-                }
-                QName(b"href") => {
-                    href = String::from_utf8(attribute.value.into_owned()).unwrap();
-                }
-                QName(b"media-type") => {
-                    media_type = String::from_utf8(attribute.value.into_owned()).unwrap();
+                QName(b"id") => id = value,
+                QName(b"href") => href = value,
+                QName(b"media-type") => media_type = value,
+                QName(b"properties") => {
+                    properties = value
+                        .split_whitespace()
+                        .map(ManifestItemProperty::from_value)
+                        .collect();
                 }
+                QName(b"fallback") => fallback = Some(value),
+                QName(b"media-overlay") => media_overlay = Some(value),
                 _ => {}
             }
         }
@@ -64,8 +72,63 @@ impl BookManifest {
             id,
             href,
             media_type,
+            properties,
+            fallback,
+            media_overlay,
         };
-        manifest_items.push(Arc::new(manifest_item))
+        manifest_items.push(Arc::new(manifest_item));
+
+        Ok(())
+    }
+
+    pub fn find_by_id(&self, id: &str) -> Option<Arc<ManifestItem>> {
+        self.items.iter().find(|item| item.id == id).cloned()
+    }
+
+    /// Returns the first item with given property, e.g. the navigation document for `ManifestItemProperty::Nav`
+    pub fn find_by_property(&self, property: &ManifestItemProperty) -> Option<Arc<ManifestItem>> {
+        self.items
+            .iter()
+            .find(|item| item.has_property(property))
+            .cloned()
+    }
+
+    pub fn items_with_property(&self, property: &ManifestItemProperty) -> Vec<Arc<ManifestItem>> {
+        self.items
+            .iter()
+            .filter(|item| item.has_property(property))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns items that should be used, in order, when the item with given id cannot be displayed.
+    /// The chain stops on a missing item or on an item that was already visited, since cycles are not allowed by the spec.
+    pub fn fallback_chain(&self, id: &str) -> Vec<Arc<ManifestItem>> {
+        let mut chain: Vec<Arc<ManifestItem>> = vec![];
+        let mut visited: Vec<String> = vec![id.to_string()];
+        let mut current = self.find_by_id(id);
+
+        while let Some(fallback_id) = current.as_ref().and_then(|item| item.fallback.clone()) {
+            if visited.contains(&fallback_id) {
+                break;
+            }
+
+            current = self.find_by_id(&fallback_id);
+
+            if let Some(fallback) = &current {
+                visited.push(fallback.id.clone());
+                chain.push(fallback.clone());
+            }
+        }
+
+        chain
+    }
+
+    /// Returns the SMIL document with media overlay of the item with given id
+    pub fn media_overlay_of(&self, id: &str) -> Option<Arc<ManifestItem>> {
+        self.find_by_id(id)
+            .and_then(|item| item.media_overlay.clone())
+            .and_then(|overlay_id| self.find_by_id(&overlay_id))
     }
 
     /// Search for an item in the manifest by part of its id or href.
@@ -86,6 +149,45 @@ pub struct ManifestItem {
     pub id: String,
     pub href: String,
     pub media_type: String,
+    pub properties: Vec<ManifestItemProperty>,
+    /// Id of the item to use when this one cannot be displayed by the reading system
+    pub fallback: Option<String>,
+    /// Id of the SMIL document synchronizing this item with audio
+    pub media_overlay: Option<String>,
+}
+
+impl ManifestItem {
+    pub fn has_property(&self, property: &ManifestItemProperty) -> bool {
+        self.properties.contains(property)
+    }
+}
+
+/// Values of the manifest item `properties` attribute defined by the epub 3 spec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestItemProperty {
+    Nav,
+    CoverImage,
+    Scripted,
+    Svg,
+    MathMl,
+    RemoteResources,
+    Switch,
+    Other(String),
+}
+
+impl ManifestItemProperty {
+    pub fn from_value(value: &str) -> ManifestItemProperty {
+        match value {
+            "nav" => ManifestItemProperty::Nav,
+            "cover-image" => ManifestItemProperty::CoverImage,
+            "scripted" => ManifestItemProperty::Scripted,
+            "svg" => ManifestItemProperty::Svg,
+            "mathml" => ManifestItemProperty::MathMl,
+            "remote-resources" => ManifestItemProperty::RemoteResources,
+            "switch" => ManifestItemProperty::Switch,
+            other => ManifestItemProperty::Other(other.to_string()),
+        }
+    }
 }
 
 impl PartialEq for ManifestItem {
@@ -116,4 +218,111 @@ mod manifest_tests {
         assert_eq!(toc_from_manifest.href, "toc.xhtml");
         assert_eq!(toc_from_manifest.media_type, "application/xhtml+xml");
     }
+
+    const OPF_CONTENT: &str = r#"
+    <package version="3.0">
+        <manifest>
+            <item id="toc-image" href="images/toc-image.jpg" media-type="image/jpeg"/>
+            <item id="navigation" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+            <item id="cover" href="images/cover.svg" media-type="image/svg+xml" properties="cover-image svg"/>
+            <item id="chapter_1" href="chapter_1.xhtml" media-type="application/xhtml+xml" properties="scripted mathml remote-resources" media-overlay="chapter_1_overlay"/>
+            <item id="chapter_1_overlay" href="chapter_1.smil" media-type="application/smil+xml"/>
+            <item id="video" href="video.webm" media-type="video/webm" fallback="video_mp4"/>
+            <item id="video_mp4" href="video.mp4" media-type="video/mp4" fallback="poster"/>
+            <item id="poster" href="poster&amp;frame.xhtml" media-type="application/xhtml+xml" properties="custom:poster"/>
+            <item id="loop_a" href="a.xhtml" media-type="application/xhtml+xml" fallback="loop_b"/>
+            <item id="loop_b" href="b.xhtml" media-type="application/xhtml+xml" fallback="loop_a"/>
+        </manifest>
+    </package>
+    "#;
+
+    #[test]
+    fn from_opf_should_read_properties_fallbacks_and_media_overlays() {
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+
+        let cover = manifest.find_by_id("cover").unwrap();
+        let chapter = manifest.find_by_id("chapter_1").unwrap();
+        let video = manifest.find_by_id("video").unwrap();
+        let poster = manifest.find_by_id("poster").unwrap();
+
+        assert_eq!(
+            cover.properties,
+            vec![ManifestItemProperty::CoverImage, ManifestItemProperty::Svg]
+        );
+        assert_eq!(
+            chapter.properties,
+            vec![
+                ManifestItemProperty::Scripted,
+                ManifestItemProperty::MathMl,
+                ManifestItemProperty::RemoteResources
+            ]
+        );
+        assert_eq!(chapter.media_overlay, Some("chapter_1_overlay".to_string()));
+        assert_eq!(video.fallback, Some("video_mp4".to_string()));
+        assert_eq!(
+            poster.properties,
+            vec![ManifestItemProperty::Other("custom:poster".to_string())]
+        );
+        assert_eq!(poster.href, "poster&frame.xhtml");
+    }
+
+    #[test]
+    fn find_by_property_should_not_match_items_by_their_names() {
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+
+        let navigation = manifest
+            .find_by_property(&ManifestItemProperty::Nav)
+            .unwrap();
+
+        assert_eq!(navigation.id, "navigation");
+        assert_eq!(navigation.href, "nav.xhtml");
+    }
+
+    #[test]
+    fn items_with_property_should_return_all_matching_items() {
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+
+        let svg_items = manifest.items_with_property(&ManifestItemProperty::Svg);
+        let switch_items = manifest.items_with_property(&ManifestItemProperty::Switch);
+
+        assert_eq!(svg_items.len(), 1);
+        assert_eq!(svg_items[0].id, "cover");
+        assert!(switch_items.is_empty());
+    }
+
+    #[test]
+    fn fallback_chain_should_follow_fallbacks_in_order() {
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+
+        let chain: Vec<String> = manifest
+            .fallback_chain("video")
+            .iter()
+            .map(|item| item.id.clone())
+            .collect();
+
+        assert_eq!(chain, vec!["video_mp4", "poster"]);
+    }
+
+    #[test]
+    fn fallback_chain_should_stop_on_cycle() {
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+
+        let chain: Vec<String> = manifest
+            .fallback_chain("loop_a")
+            .iter()
+            .map(|item| item.id.clone())
+            .collect();
+
+        assert_eq!(chain, vec!["loop_b"]);
+    }
+
+    #[test]
+    fn media_overlay_of_should_return_smil_item() {
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+
+        let overlay = manifest.media_overlay_of("chapter_1").unwrap();
+
+        assert_eq!(overlay.href, "chapter_1.smil");
+        assert!(manifest.media_overlay_of("cover").is_none());
+    }
 }
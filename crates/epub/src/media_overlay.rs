@@ -0,0 +1,365 @@
+use std::{collections::HashSet, time::Duration};
+
+use quick_xml::{events::Event, Reader};
+
+use crate::{
+    encoding::decode_document, epub::EBook, error::EpubError, resources::ResourcePath,
+    xml::attribute_value,
+};
+
+/// Audio clips synchronized with the elements of content documents, in the playback order.
+/// Built from the SMIL documents referenced by `media-overlay` manifest attributes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaOverlay {
+    pub clips: Vec<OverlayClip>,
+}
+
+/// Single `<par>` of a SMIL document, the element narrated by a range of an audio file
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayClip {
+    /// Content document with the id of the narrated element in the fragment
+    pub text: ResourcePath,
+    /// Full path of the audio file inside of the archive
+    pub audio_path: String,
+    pub clip_begin: Duration,
+    /// `None` when the clip plays until the end of the audio file
+    pub clip_end: Option<Duration>,
+}
+
+impl OverlayClip {
+    /// Whether the clip is playing at given position of its audio file
+    pub fn contains(&self, position: Duration) -> bool {
+        position >= self.clip_begin && self.clip_end.is_none_or(|end| position < end)
+    }
+}
+
+impl MediaOverlay {
+    /// Parses the SMIL document placed at `smil_path` in the archive.
+    /// `<par>` elements without both `<text>` and `<audio>` are skipped, nested `<seq>` elements are flattened.
+    pub fn from_smil(smil_content: &str, smil_path: &str) -> Result<MediaOverlay, EpubError> {
+        let mut reader = Reader::from_str(smil_content);
+        reader.trim_text(true);
+
+        let mut buf = Vec::new();
+        let mut clips: Vec<OverlayClip> = vec![];
+        let mut text: Option<ResourcePath> = None;
+        let mut audio: Option<(String, Duration, Option<Duration>)> = None;
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(ref e) if e.local_name().as_ref() == b"par" => {
+                    text = None;
+                    audio = None;
+                }
+                Event::Start(ref e) | Event::Empty(ref e) if e.local_name().as_ref() == b"text" => {
+                    text = attribute_value(e, b"src")?
+                        .map(|src| ResourcePath::resolve(smil_path, &src));
+                }
+                Event::Start(ref e) | Event::Empty(ref e)
+                    if e.local_name().as_ref() == b"audio" =>
+                {
+                    if let Some(src) = attribute_value(e, b"src")? {
+                        let clip_begin = attribute_value(e, b"clipBegin")?
+                            .and_then(|value| parse_clock_value(&value))
+                            .unwrap_or_default();
+                        let clip_end = attribute_value(e, b"clipEnd")?
+                            .and_then(|value| parse_clock_value(&value));
+                        let audio_path = ResourcePath::resolve(smil_path, &src).path;
+
+                        audio = Some((audio_path, clip_begin, clip_end));
+                    }
+                }
+                Event::End(ref e) if e.local_name().as_ref() == b"par" => {
+                    if let (Some(text), Some((audio_path, clip_begin, clip_end))) =
+                        (text.take(), audio.take())
+                    {
+                        clips.push(OverlayClip {
+                            text,
+                            audio_path,
+                            clip_begin,
+                            clip_end,
+                        });
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(MediaOverlay { clips })
+    }
+
+    /// Clip narrating the element with given id in the content document placed at `document_path`
+    pub fn clip_of_element(&self, document_path: &str, element_id: &str) -> Option<&OverlayClip> {
+        self.clips.iter().find(|clip| {
+            clip.text.path == document_path && clip.text.fragment.as_deref() == Some(element_id)
+        })
+    }
+
+    /// Clip playing at given position of the audio file placed at `audio_path`
+    pub fn clip_at(&self, audio_path: &str, position: Duration) -> Option<&OverlayClip> {
+        self.clips
+            .iter()
+            .find(|clip| clip.audio_path == audio_path && clip.contains(position))
+    }
+
+    /// Clips narrating the content document placed at `document_path`
+    pub fn clips_of_document<'a>(
+        &'a self,
+        document_path: &'a str,
+    ) -> impl Iterator<Item = &'a OverlayClip> + 'a {
+        self.clips
+            .iter()
+            .filter(move |clip| clip.text.path == document_path)
+    }
+}
+
+impl EBook {
+    /// Media overlay of the manifest item with given id, `None` when the item has no `media-overlay`
+    pub fn media_overlay(&self, item_id: &str) -> Result<Option<MediaOverlay>, EpubError> {
+        match self.manifest.media_overlay_of(item_id) {
+            Some(smil_item) => {
+                let smil_path = self.resolve_href(self.opf_path(), &smil_item.href).path;
+                self.read_media_overlay(&smil_path).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Clips of all media overlays in the order of the spine, empty when the book has no media overlays.
+    /// SMIL documents shared by several spine items are read only once.
+    pub fn media_overlay_timeline(&self) -> Result<MediaOverlay, EpubError> {
+        let mut timeline = MediaOverlay::default();
+        let mut read_paths: HashSet<String> = HashSet::new();
+
+        for spine_item in &self.spine.items {
+            if let Some(smil_item) = self.manifest.media_overlay_of(&spine_item.value.id) {
+                let smil_path = self.resolve_href(self.opf_path(), &smil_item.href).path;
+
+                if read_paths.insert(smil_path.clone()) {
+                    timeline
+                        .clips
+                        .extend(self.read_media_overlay(&smil_path)?.clips);
+                }
+            }
+        }
+
+        Ok(timeline)
+    }
+
+    fn read_media_overlay(&self, smil_path: &str) -> Result<MediaOverlay, EpubError> {
+        let smil_content = decode_document(&self.read_resource_at(smil_path)?)?;
+        MediaOverlay::from_smil(&smil_content, smil_path)
+    }
+}
+
+/// Parses SMIL clock values: full (`0:01:02.5`) and partial (`01:02.5`) clock values,
+/// and timecounts with an optional `h`, `min`, `s` or `ms` metric, e.g. `62.5s` or `62500ms`
+pub fn parse_clock_value(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    let seconds = if value.contains(':') {
+        let parts: Vec<&str> = value.split(':').collect();
+        let (hours, minutes, seconds) = match parts.as_slice() {
+            [hours, minutes, seconds] => (hours.parse::<u64>().ok()?, *minutes, *seconds),
+            [minutes, seconds] => (0, *minutes, *seconds),
+            _ => return None,
+        };
+        let minutes = minutes.parse::<u64>().ok()?;
+        let seconds = seconds.parse::<f64>().ok()?;
+
+        let whole_seconds = hours
+            .checked_mul(3600)?
+            .checked_add(minutes.checked_mul(60)?)?;
+
+        whole_seconds as f64 + seconds
+    } else {
+        let (number, multiplier) = if let Some(number) = value.strip_suffix("ms") {
+            (number, 0.001)
+        } else if let Some(number) = value.strip_suffix("min") {
+            (number, 60.0)
+        } else if let Some(number) = value.strip_suffix('h') {
+            (number, 3600.0)
+        } else if let Some(number) = value.strip_suffix('s') {
+            (number, 1.0)
+        } else {
+            (value, 1.0)
+        };
+
+        number.trim().parse::<f64>().ok()? * multiplier
+    };
+
+    // Negative, infinite and too large values are rejected instead of panicking
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+#[cfg(test)]
+mod media_overlay_tests {
+    use super::*;
+    use crate::test_utils::create_epub;
+
+    const SMIL_CONTENT: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+        <smil xmlns="http://www.w3.org/ns/SMIL" xmlns:epub="http://www.idpf.org/2007/ops" version="3.0">
+            <body>
+                <seq id="seq1" epub:textref="../Text/chapter_1.xhtml" epub:type="chapter">
+                    <par id="par1">
+                        <text src="../Text/chapter_1.xhtml#heading"/>
+                        <audio src="../Audio/chapter_1.mp3" clipBegin="0:00:00.000" clipEnd="0:00:02.500"/>
+                    </par>
+                    <seq id="seq2">
+                        <par id="par2">
+                            <text src="../Text/chapter_1.xhtml#sentence_1"/>
+                            <audio src="../Audio/chapter_1.mp3" clipBegin="2.5s" clipEnd="6200ms"/>
+                        </par>
+                    </seq>
+                    <par id="par3">
+                        <text src="../Text/chapter_1.xhtml#sentence_2"/>
+                        <audio src="../Audio/chapter_1.mp3" clipBegin="00:06.2"/>
+                    </par>
+                    <par id="par4">
+                        <text src="../Text/chapter_1.xhtml#no_audio"/>
+                    </par>
+                </seq>
+            </body>
+        </smil>"##;
+
+    fn create_opf(overlay_attribute: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+                    <dc:identifier id="uid">urn:uuid:1</dc:identifier>
+                    <dc:title>Narrated</dc:title>
+                </metadata>
+                <manifest>
+                    <item id="chapter_1" href="Text/chapter_1.xhtml" media-type="application/xhtml+xml" {}/>
+                    <item id="chapter_2" href="Text/chapter_2.xhtml" media-type="application/xhtml+xml" {}/>
+                    <item id="chapter_1_overlay" href="Overlays/chapter_1.smil" media-type="application/smil+xml"/>
+                    <item id="chapter_1_audio" href="Audio/chapter_1.mp3" media-type="audio/mpeg"/>
+                </manifest>
+                <spine>
+                    <itemref idref="chapter_1"/>
+                    <itemref idref="chapter_2"/>
+                </spine>
+            </package>"#,
+            overlay_attribute, overlay_attribute
+        )
+    }
+
+    fn create_narrated_epub(name: &str, overlay_attribute: &str) -> EBook {
+        let path = create_epub(
+            name,
+            &create_opf(overlay_attribute),
+            &[
+                (
+                    "Text/chapter_1.xhtml",
+                    b"<html><body><h1 id=\"heading\">1</h1></body></html>",
+                ),
+                (
+                    "Text/chapter_2.xhtml",
+                    b"<html><body><p>2</p></body></html>",
+                ),
+                ("Overlays/chapter_1.smil", SMIL_CONTENT.as_bytes()),
+            ],
+        );
+
+        EBook::read_epub(path).unwrap()
+    }
+
+    #[test]
+    fn should_parse_clips_of_smil_document() {
+        let overlay =
+            MediaOverlay::from_smil(SMIL_CONTENT, "OEBPS/Overlays/chapter_1.smil").unwrap();
+
+        assert_eq!(overlay.clips.len(), 3);
+        assert_eq!(
+            overlay.clips[1],
+            OverlayClip {
+                text: ResourcePath {
+                    path: "OEBPS/Text/chapter_1.xhtml".to_string(),
+                    fragment: Some("sentence_1".to_string()),
+                },
+                audio_path: "OEBPS/Audio/chapter_1.mp3".to_string(),
+                clip_begin: Duration::from_millis(2500),
+                clip_end: Some(Duration::from_millis(6200)),
+            }
+        );
+        assert_eq!(overlay.clips[2].clip_end, None);
+    }
+
+    #[test]
+    fn should_look_up_clips_by_element_and_by_audio_position() {
+        let overlay =
+            MediaOverlay::from_smil(SMIL_CONTENT, "OEBPS/Overlays/chapter_1.smil").unwrap();
+        let document_path = "OEBPS/Text/chapter_1.xhtml";
+        let audio_path = "OEBPS/Audio/chapter_1.mp3";
+
+        let clip = overlay
+            .clip_of_element(document_path, "sentence_1")
+            .unwrap();
+        assert_eq!(clip.clip_begin, Duration::from_millis(2500));
+        assert!(overlay.clip_of_element(document_path, "no_audio").is_none());
+
+        let clip = overlay.clip_at(audio_path, Duration::from_secs(3)).unwrap();
+        assert_eq!(clip.text.fragment.as_deref(), Some("sentence_1"));
+        let clip = overlay
+            .clip_at(audio_path, Duration::from_millis(6200))
+            .unwrap();
+        assert_eq!(clip.text.fragment.as_deref(), Some("sentence_2"));
+        let clip = overlay
+            .clip_at(audio_path, Duration::from_secs(600))
+            .unwrap();
+        assert_eq!(clip.text.fragment.as_deref(), Some("sentence_2"));
+        assert!(overlay
+            .clip_at("OEBPS/Audio/other.mp3", Duration::ZERO)
+            .is_none());
+
+        assert_eq!(overlay.clips_of_document(document_path).count(), 3);
+    }
+
+    #[test]
+    fn should_parse_clock_values() {
+        assert_eq!(
+            parse_clock_value("1:02:03.5"),
+            Some(Duration::from_millis(3_723_500))
+        );
+        assert_eq!(parse_clock_value("02:03"), Some(Duration::from_secs(123)));
+        assert_eq!(parse_clock_value("3.2h"), Some(Duration::from_secs(11_520)));
+        assert_eq!(parse_clock_value("45min"), Some(Duration::from_secs(2700)));
+        assert_eq!(
+            parse_clock_value("12.5s"),
+            Some(Duration::from_millis(12_500))
+        );
+        assert_eq!(parse_clock_value("86ms"), Some(Duration::from_millis(86)));
+        assert_eq!(parse_clock_value("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse_clock_value("-1s"), None);
+        assert_eq!(parse_clock_value("1:2:3:4"), None);
+        assert_eq!(parse_clock_value("soon"), None);
+        assert_eq!(parse_clock_value("1e300s"), None);
+        assert_eq!(parse_clock_value("6000000000000000000:00:00"), None);
+    }
+
+    #[test]
+    fn should_read_media_overlays_of_the_book() {
+        let ebook = create_narrated_epub(
+            "media_overlay_book.epub",
+            r#"media-overlay="chapter_1_overlay""#,
+        );
+
+        let overlay = ebook.media_overlay("chapter_1").unwrap().unwrap();
+        assert_eq!(overlay.clips.len(), 3);
+
+        // Both chapters reference the same SMIL document, so its clips are in the timeline once
+        let timeline = ebook.media_overlay_timeline().unwrap();
+        assert_eq!(timeline, overlay);
+    }
+
+    #[test]
+    fn should_return_empty_timeline_for_books_without_media_overlays() {
+        let ebook = create_narrated_epub("media_overlay_none.epub", "");
+
+        assert_eq!(ebook.media_overlay("chapter_1").unwrap(), None);
+        assert!(ebook.media_overlay_timeline().unwrap().clips.is_empty());
+    }
+}
@@ -1,53 +1,234 @@
-use quick_xml::{events::Event, Reader};
+use std::collections::HashMap;
 
-#[derive(Debug)]
+use quick_xml::{events::Event, name::QName, Reader};
+
+use crate::{error::EpubError, rendition::RenditionProperties};
+
+#[derive(Debug, Default)]
 pub struct BookMetadata {
+    /// Main title of the book, the first one in the OPF if none of them is marked as `main`
     pub title: Option<String>,
+    /// Name of the first creator of the book
     pub creator: Option<String>,
+    /// Identifier that the package marks as unique, the first one in the OPF otherwise
     pub identifier: Option<String>,
     pub language: Option<String>,
     pub publisher: Option<String>,
     pub rights: Option<String>,
+    pub titles: Vec<String>,
+    pub creators: Vec<Contributor>,
+    pub contributors: Vec<Contributor>,
+    pub identifiers: Vec<Identifier>,
+    pub languages: Vec<String>,
+    pub dates: Vec<MetadataDate>,
+    pub subjects: Vec<String>,
+    pub description: Option<String>,
+    /// Value of `dcterms:modified`, required by epub 3
+    pub modified: Option<String>,
+    pub series: Option<Series>,
+    /// Id of the manifest item declared as the cover by epub 2 `<meta name="cover">`
+    pub cover: Option<String>,
+    /// Global `rendition:*` properties, which spine items may override
+    pub rendition: RenditionProperties,
+}
+
+/// Creator or contributor of the book, together with the roles from `opf:role` or `<meta refines>`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Contributor {
+    pub name: String,
+    /// Name in the form used for sorting, e.g. `MELVILLE, HERMAN`
+    pub file_as: Option<String>,
+    /// MARC relator codes such as `aut` or `ill`
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Identifier {
+    pub value: String,
+    /// Scheme of the identifier such as `ISBN` or `uuid`, when it is known
+    pub scheme: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MetadataDate {
+    pub value: String,
+    /// Epub 2 `opf:event` of the date, e.g. `publication` or `modification`
+    pub event: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Series {
+    pub name: String,
+    pub position: Option<f64>,
+}
+
+/// Single leaf element found inside of the `<metadata>` element
+#[derive(Debug, Default)]
+struct RawMetadataElement {
+    name: String,
+    attributes: HashMap<String, String>,
+    text: String,
+}
+
+impl RawMetadataElement {
+    fn attribute(&self, key: &str) -> Option<&String> {
+        self.attributes.get(key)
+    }
+
+    /// Id of the element that this `<meta>` refines, without the leading `#`
+    fn refines(&self) -> Option<&str> {
+        self.attribute("refines")
+            .map(|refines| refines.trim_start_matches('#'))
+    }
+
+    fn property(&self) -> Option<&str> {
+        self.attribute("property").map(|property| property.as_str())
+    }
 }
 
+const CALIBRE_SERIES: &str = "calibre:series";
+const CALIBRE_SERIES_INDEX: &str = "calibre:series_index";
+const COVER: &str = "cover";
+
 //TODO: Consider adding implementation of 'cleaning up' the metadata to remove characters such as '-' and '_' from raw metadata strings
 //NOTE: It should be applicable only to certain metadata such as Creator and Title
 impl BookMetadata {
-    pub fn from_opf(opf_content: &String) -> BookMetadata {
+    pub fn from_opf(opf_content: &str) -> Result<BookMetadata, EpubError> {
+        let (unique_identifier_id, elements) = BookMetadata::read_raw_elements(opf_content)?;
+
+        let mut metadata = BookMetadata::default();
+        let mut main_title: Option<String> = None;
+        let mut unique_identifier: Option<String> = None;
+
+        for element in elements.iter().filter(|e| e.name != "meta") {
+            let text = element.text.clone();
+            let id = element.attribute("id").map(|id| id.as_str());
+            let refinements = BookMetadata::refinements_of(&elements, id);
+
+            match element.name.as_str() {
+                "title" => {
+                    if main_title.is_none() && refinements.get("title-type") == Some(&"main") {
+                        main_title = Some(text.clone());
+                    }
+                    metadata.titles.push(text);
+                }
+                "creator" | "contributor" => {
+                    let contributor = BookMetadata::create_contributor(element, &elements, id);
+                    if element.name == "creator" {
+                        metadata.creators.push(contributor);
+                    } else {
+                        metadata.contributors.push(contributor);
+                    }
+                }
+                "identifier" => {
+                    if id.is_some() && id == unique_identifier_id.as_deref() {
+                        unique_identifier = Some(text.clone());
+                    }
+
+                    let scheme = element
+                        .attribute("opf:scheme")
+                        .or(element.attribute("scheme"))
+                        .map(|scheme| scheme.to_string())
+                        .or(BookMetadata::identifier_type_of(&elements, id))
+                        .or(BookMetadata::scheme_from_urn(&text));
+
+                    metadata.identifiers.push(Identifier {
+                        value: text,
+                        scheme,
+                    });
+                }
+                "language" => metadata.languages.push(text),
+                "publisher" => {
+                    metadata.publisher.get_or_insert(text);
+                }
+                "rights" => {
+                    metadata.rights.get_or_insert(text);
+                }
+                "date" => metadata.dates.push(MetadataDate {
+                    value: text,
+                    event: element.attribute("opf:event").cloned(),
+                }),
+                "subject" => metadata.subjects.push(text),
+                "description" => {
+                    metadata.description.get_or_insert(text);
+                }
+                _ => {}
+            }
+        }
+
+        metadata.modified = elements
+            .iter()
+            .find(|e| e.refines().is_none() && e.property() == Some("dcterms:modified"))
+            .map(|e| e.text.clone());
+        metadata.series = BookMetadata::find_series(&elements);
+        metadata.cover = BookMetadata::find_meta_content(&elements, COVER).cloned();
+
+        for element in elements.iter().filter(|e| e.refines().is_none()) {
+            if let Some(property) = element.property() {
+                metadata.rendition.apply_meta(property, &element.text);
+            }
+        }
+
+        metadata.title = main_title.or(metadata.titles.first().cloned());
+        metadata.creator = metadata.creators.first().map(|c| c.name.clone());
+        metadata.identifier =
+            unique_identifier.or(metadata.identifiers.first().map(|i| i.value.clone()));
+        metadata.language = metadata.languages.first().cloned();
+
+        Ok(metadata)
+    }
+
+    /// Reads all leaf elements of the `<metadata>` element, along with the id of the unique identifier declared by the package
+    fn read_raw_elements(
+        opf_content: &str,
+    ) -> Result<(Option<String>, Vec<RawMetadataElement>), EpubError> {
         let mut reader = Reader::from_str(opf_content);
         reader.trim_text(true);
 
         let mut buf = Vec::new();
-        let mut metadata = BookMetadata {
-            title: None,
-            creator: None,
-            identifier: None,
-            language: None,
-            publisher: None,
-            rights: None,
-        };
-
-        let mut current_tag = String::new();
-
-        while let Ok(event) = reader.read_event_into(&mut buf) {
-            match event {
-                Event::Start(ref e) | Event::Empty(ref e) => {
-                    current_tag = String::from_utf8(e.name().as_ref().to_vec()).unwrap();
+        let mut unique_identifier_id: Option<String> = None;
+        let mut elements: Vec<RawMetadataElement> = vec![];
+        let mut current_element: Option<RawMetadataElement> = None;
+        let mut is_inside_metadata = false;
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(ref e) | Event::Empty(ref e)
+                    if e.local_name().as_ref() == b"package" =>
+                {
+                    for attribute in e.attributes() {
+                        let attr = attribute?;
+                        if attr.key == QName(b"unique-identifier") {
+                            unique_identifier_id = Some(attr.unescape_value()?.to_string());
+                        }
+                    }
+                }
+                Event::Start(ref e) if e.local_name().as_ref() == b"metadata" => {
+                    is_inside_metadata = true;
+                }
+                Event::End(ref e) if e.local_name().as_ref() == b"metadata" => break,
+                Event::Start(ref e) if is_inside_metadata => {
+                    current_element = Some(BookMetadata::create_raw_element(e)?);
+                }
+                Event::Empty(ref e) if is_inside_metadata => {
+                    elements.push(BookMetadata::create_raw_element(e)?);
                 }
                 Event::Text(e) => {
-                    let text = e.unescape().unwrap().to_string();
-                    match current_tag.as_str() {
-                        "dc:title" => metadata.title = Some(text),
-                        "dc:creator" => metadata.creator = Some(text),
-                        "dc:identifier" => metadata.identifier = Some(text),
-                        "dc:language" => metadata.language = Some(text),
-                        "dc:publisher" => metadata.publisher = Some(text),
-                        "dc:rights" => metadata.rights = Some(text),
-                        _ => {}
+                    if let Some(element) = current_element.as_mut() {
+                        element.text.push_str(&e.unescape()?);
+                    }
+                }
+                Event::CData(e) => {
+                    if let Some(element) = current_element.as_mut() {
+                        element
+                            .text
+                            .push_str(&String::from_utf8(e.into_inner().to_vec())?);
                     }
                 }
                 Event::End(_) => {
-                    current_tag.clear();
+                    if let Some(element) = current_element.take() {
+                        elements.push(element);
+                    }
                 }
                 Event::Eof => break,
                 _ => {}
@@ -55,6 +236,327 @@ impl BookMetadata {
             buf.clear();
         }
 
-        metadata
+        Ok((unique_identifier_id, elements))
+    }
+
+    fn create_raw_element(
+        e: &quick_xml::events::BytesStart<'_>,
+    ) -> Result<RawMetadataElement, EpubError> {
+        let mut attributes = HashMap::new();
+
+        for attribute in e.attributes() {
+            let attr = attribute?;
+            let key = String::from_utf8(attr.key.as_ref().to_vec())?;
+            attributes.insert(key, attr.unescape_value()?.to_string());
+        }
+
+        Ok(RawMetadataElement {
+            name: String::from_utf8(e.local_name().as_ref().to_vec())?,
+            attributes,
+            text: String::new(),
+        })
+    }
+
+    /// Collects `property -> value` pairs of all `<meta>` elements refining element with given id.
+    /// Only the first value of each property is kept, use it for properties that are expected once.
+    fn refinements_of<'a>(
+        elements: &'a [RawMetadataElement],
+        id: Option<&str>,
+    ) -> HashMap<&'a str, &'a str> {
+        let mut refinements = HashMap::new();
+
+        if let Some(id) = id {
+            for element in elements.iter().filter(|e| e.refines() == Some(id)) {
+                if let Some(property) = element.property() {
+                    refinements.entry(property).or_insert(element.text.as_str());
+                }
+            }
+        }
+
+        refinements
+    }
+
+    fn create_contributor(
+        element: &RawMetadataElement,
+        elements: &[RawMetadataElement],
+        id: Option<&str>,
+    ) -> Contributor {
+        let mut roles: Vec<String> = element.attribute("opf:role").into_iter().cloned().collect();
+        let mut file_as = element.attribute("opf:file-as").cloned();
+
+        if let Some(id) = id {
+            for refinement in elements.iter().filter(|e| e.refines() == Some(id)) {
+                match refinement.property() {
+                    Some("role") => roles.push(refinement.text.clone()),
+                    Some("file-as") => {
+                        file_as.get_or_insert(refinement.text.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Contributor {
+            name: element.text.clone(),
+            file_as,
+            roles,
+        }
+    }
+
+    /// Epub 3 `identifier-type` refinements are usually codes from ONIX codelist 5,
+    /// which are translated to the scheme names used by epub 2 `opf:scheme`.
+    fn identifier_type_of(elements: &[RawMetadataElement], id: Option<&str>) -> Option<String> {
+        let id = id?;
+        let element = elements
+            .iter()
+            .find(|e| e.refines() == Some(id) && e.property() == Some("identifier-type"))?;
+        let code = element.text.trim();
+
+        match element.attribute("scheme").map(|scheme| scheme.as_str()) {
+            Some("onix:codelist5") => match code {
+                "01" => Some("proprietary"),
+                "02" | "15" => Some("ISBN"),
+                "03" => Some("GTIN-13"),
+                "06" => Some("DOI"),
+                _ => None,
+            }
+            .map(|scheme| scheme.to_string()),
+            _ if !code.is_empty() => Some(code.to_string()),
+            _ => None,
+        }
+    }
+
+    fn scheme_from_urn(identifier: &str) -> Option<String> {
+        let mut parts = identifier.splitn(3, ':');
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(urn), Some(scheme), Some(_)) if urn.eq_ignore_ascii_case("urn") => {
+                Some(scheme.to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Epub 3 `belongs-to-collection` takes precedence over calibre specific metadata.
+    /// Collections explicitly marked as `series` are preferred over other collections.
+    fn find_series(elements: &[RawMetadataElement]) -> Option<Series> {
+        let collections: Vec<(&RawMetadataElement, HashMap<&str, &str>)> = elements
+            .iter()
+            .filter(|e| e.refines().is_none() && e.property() == Some("belongs-to-collection"))
+            .map(|e| {
+                let id = e.attribute("id").map(|id| id.as_str());
+                (e, BookMetadata::refinements_of(elements, id))
+            })
+            .collect();
+
+        let collection = collections
+            .iter()
+            .find(|(_, refinements)| refinements.get("collection-type") == Some(&"series"))
+            .or(collections.first());
+
+        if let Some((element, refinements)) = collection {
+            return Some(Series {
+                name: element.text.clone(),
+                position: refinements
+                    .get("group-position")
+                    .and_then(|position| position.trim().parse().ok()),
+            });
+        }
+
+        BookMetadata::find_meta_content(elements, CALIBRE_SERIES).map(|name| Series {
+            name: name.clone(),
+            position: BookMetadata::find_meta_content(elements, CALIBRE_SERIES_INDEX)
+                .and_then(|position| position.trim().parse().ok()),
+        })
+    }
+
+    /// Finds `content` of epub 2 `<meta name="..." content="..."/>` element with given name
+    fn find_meta_content<'a>(elements: &'a [RawMetadataElement], name: &str) -> Option<&'a String> {
+        elements
+            .iter()
+            .find(|e| e.name == "meta" && e.attribute("name").map(|n| n.as_str()) == Some(name))
+            .and_then(|e| e.attribute("content"))
+    }
+}
+
+#[cfg(test)]
+mod metadata_tests {
+    use super::*;
+
+    const EPUB2_OPF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+    <package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="uuid_id">
+      <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+        <dc:title>Smocze opowieści</dc:title>
+        <dc:creator opf:role="aut" opf:file-as="Fulińska, Agnieszka">Agnieszka Fulińska</dc:creator>
+        <dc:creator opf:role="aut" opf:file-as="Adamski, Krzysztof">Krzysztof Adamski</dc:creator>
+        <dc:contributor opf:role="ill">Jan Kowalski</dc:contributor>
+        <dc:identifier opf:scheme="ISBN">978-83-0000-000-0</dc:identifier>
+        <dc:identifier id="uuid_id" opf:scheme="uuid">5d2a3f3e-0000-4000-8000-000000000000</dc:identifier>
+        <dc:language>pl</dc:language>
+        <dc:date opf:event="publication">2016-03-01</dc:date>
+        <dc:date opf:event="modification">2016-04-11</dc:date>
+        <dc:subject>Fantasy</dc:subject>
+        <dc:subject>Dragons</dc:subject>
+        <dc:description>&lt;p&gt;Anthology of dragon stories&lt;/p&gt;</dc:description>
+        <meta name="calibre:series" content="Dragoneza"/>
+        <meta name="calibre:series_index" content="2.0"/>
+        <meta name="cover" content="cover-image"/>
+      </metadata>
+    </package>
+    "#;
+
+    const EPUB3_OPF: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+    <package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="pub-id">
+      <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+        <dc:title id="subtitle">Being the Second Part</dc:title>
+        <meta refines="#subtitle" property="title-type">subtitle</meta>
+        <dc:title id="title">The Two Towers</dc:title>
+        <meta refines="#title" property="title-type">main</meta>
+        <dc:creator id="creator">J. R. R. Tolkien</dc:creator>
+        <meta refines="#creator" property="file-as">Tolkien, J. R. R.</meta>
+        <meta refines="#creator" property="role" scheme="marc:relators">aut</meta>
+        <meta refines="#creator" property="role" scheme="marc:relators">ill</meta>
+        <dc:identifier id="isbn">9780000000000</dc:identifier>
+        <meta refines="#isbn" property="identifier-type" scheme="onix:codelist5">15</meta>
+        <dc:identifier id="pub-id">urn:uuid:a3d5b2f0-0000-4000-8000-000000000000</dc:identifier>
+        <dc:language>en</dc:language>
+        <dc:language>pl</dc:language>
+        <meta property="dcterms:modified">2020-01-01T00:00:00Z</meta>
+        <meta property="belongs-to-collection" id="set">Middle-earth</meta>
+        <meta refines="#set" property="collection-type">set</meta>
+        <meta property="belongs-to-collection" id="series">The Lord of the Rings</meta>
+        <meta refines="#series" property="collection-type">series</meta>
+        <meta refines="#series" property="group-position">2</meta>
+      </metadata>
+    </package>
+    "##;
+
+    #[test]
+    fn should_read_every_creator_and_contributor_with_roles_from_epub2_attributes() {
+        let metadata = BookMetadata::from_opf(EPUB2_OPF).unwrap();
+
+        assert_eq!(metadata.creator, Some("Agnieszka Fulińska".to_string()));
+        assert_eq!(
+            metadata.creators,
+            vec![
+                Contributor {
+                    name: "Agnieszka Fulińska".to_string(),
+                    file_as: Some("Fulińska, Agnieszka".to_string()),
+                    roles: vec!["aut".to_string()],
+                },
+                Contributor {
+                    name: "Krzysztof Adamski".to_string(),
+                    file_as: Some("Adamski, Krzysztof".to_string()),
+                    roles: vec!["aut".to_string()],
+                },
+            ]
+        );
+        assert_eq!(metadata.contributors.len(), 1);
+        assert_eq!(metadata.contributors[0].roles, vec!["ill".to_string()]);
+    }
+
+    #[test]
+    fn should_read_identifiers_dates_subjects_and_description_from_epub2() {
+        let metadata = BookMetadata::from_opf(EPUB2_OPF).unwrap();
+
+        assert_eq!(
+            metadata.identifier,
+            Some("5d2a3f3e-0000-4000-8000-000000000000".to_string())
+        );
+        assert_eq!(metadata.identifiers.len(), 2);
+        assert_eq!(metadata.identifiers[0].scheme, Some("ISBN".to_string()));
+        assert_eq!(metadata.identifiers[1].scheme, Some("uuid".to_string()));
+        assert_eq!(
+            metadata.dates,
+            vec![
+                MetadataDate {
+                    value: "2016-03-01".to_string(),
+                    event: Some("publication".to_string()),
+                },
+                MetadataDate {
+                    value: "2016-04-11".to_string(),
+                    event: Some("modification".to_string()),
+                },
+            ]
+        );
+        assert_eq!(metadata.subjects, vec!["Fantasy", "Dragons"]);
+        assert_eq!(
+            metadata.description,
+            Some("<p>Anthology of dragon stories</p>".to_string())
+        );
+        assert_eq!(metadata.modified, None);
+    }
+
+    #[test]
+    fn should_read_calibre_series() {
+        let metadata = BookMetadata::from_opf(EPUB2_OPF).unwrap();
+
+        assert_eq!(
+            metadata.series,
+            Some(Series {
+                name: "Dragoneza".to_string(),
+                position: Some(2.0),
+            })
+        );
+    }
+
+    #[test]
+    fn should_read_cover_id_from_epub2_meta() {
+        let metadata = BookMetadata::from_opf(EPUB2_OPF).unwrap();
+
+        assert_eq!(metadata.cover, Some("cover-image".to_string()));
+    }
+
+    #[test]
+    fn should_prefer_title_marked_as_main() {
+        let metadata = BookMetadata::from_opf(EPUB3_OPF).unwrap();
+
+        assert_eq!(metadata.title, Some("The Two Towers".to_string()));
+        assert_eq!(
+            metadata.titles,
+            vec!["Being the Second Part", "The Two Towers"]
+        );
+    }
+
+    #[test]
+    fn should_read_roles_and_file_as_from_refining_meta_elements() {
+        let metadata = BookMetadata::from_opf(EPUB3_OPF).unwrap();
+
+        assert_eq!(
+            metadata.creators,
+            vec![Contributor {
+                name: "J. R. R. Tolkien".to_string(),
+                file_as: Some("Tolkien, J. R. R.".to_string()),
+                roles: vec!["aut".to_string(), "ill".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn should_read_identifier_schemes_from_refining_meta_elements_and_urns() {
+        let metadata = BookMetadata::from_opf(EPUB3_OPF).unwrap();
+
+        assert_eq!(
+            metadata.identifier,
+            Some("urn:uuid:a3d5b2f0-0000-4000-8000-000000000000".to_string())
+        );
+        assert_eq!(metadata.identifiers[0].scheme, Some("ISBN".to_string()));
+        assert_eq!(metadata.identifiers[1].scheme, Some("uuid".to_string()));
+    }
+
+    #[test]
+    fn should_read_languages_modified_date_and_series_collection() {
+        let metadata = BookMetadata::from_opf(EPUB3_OPF).unwrap();
+
+        assert_eq!(metadata.language, Some("en".to_string()));
+        assert_eq!(metadata.languages, vec!["en", "pl"]);
+        assert_eq!(metadata.modified, Some("2020-01-01T00:00:00Z".to_string()));
+        assert_eq!(
+            metadata.series,
+            Some(Series {
+                name: "The Lord of the Rings".to_string(),
+                position: Some(2.0),
+            })
+        );
     }
 }
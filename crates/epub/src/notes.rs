@@ -0,0 +1,343 @@
+use std::sync::Arc;
+
+use crate::{
+    chapters::{chapter::Chapter, chapter_node::ChapterNode},
+    epub::EBook,
+    error::EpubError,
+    resources::{is_external_href, LinkTarget, ResourcePath},
+    table_of_contents::table_of_contents_item::TableOfContentsItem,
+};
+
+/// `epub:type` values and DPUB-ARIA roles marking the element as a note, together with the kind of the note
+const NOTE_TYPES: [(&str, NoteKind); 8] = [
+    ("footnote", NoteKind::Footnote),
+    ("note", NoteKind::Footnote),
+    ("doc-footnote", NoteKind::Footnote),
+    ("endnote", NoteKind::Endnote),
+    ("rearnote", NoteKind::Endnote),
+    ("doc-endnote", NoteKind::Endnote),
+    ("aside", NoteKind::Aside),
+    ("sidebar", NoteKind::Aside),
+];
+
+/// `epub:type` values and DPUB-ARIA roles of the sections collecting the notes, e.g. `<ol epub:type="endnotes">`
+const NOTE_COLLECTION_TYPES: [&str; 4] = ["footnotes", "endnotes", "rearnotes", "doc-endnotes"];
+
+/// Elements holding the whole text of a note which is not marked as one, e.g. `<p><a id="note3"/>3. Text</p>`
+const BLOCK_TAGS: [&str; 7] = ["p", "li", "dd", "div", "aside", "blockquote", "section"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteKind {
+    Footnote,
+    Endnote,
+    Aside,
+    /// Target of a note reference without any semantics, e.g. a paragraph of a notes file
+    Unmarked,
+}
+
+/// Footnote, endnote or aside, with its text prepared to be shown in a popup
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    /// Document containing the note, with the id of the note element in the fragment
+    pub location: ResourcePath,
+    pub kind: NoteKind,
+    /// Text of the note with the whitespace collapsed and without the links back to the reference
+    pub text: String,
+}
+
+/// Link to a note, found in the content of a chapter
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteReference {
+    /// Text of the reference, usually the number of the note
+    pub label: String,
+    pub href: String,
+    /// Document containing the note, with the id of the note element in the fragment
+    pub target: ResourcePath,
+}
+
+impl Note {
+    /// Creates the note from the element a reference points to.
+    /// Elements marked as notes are used as they are, otherwise the note is the closest block containing the element,
+    /// since notes files commonly place the id on the number of the note instead of its paragraph.
+    pub fn from_target(path: &str, target: &Arc<ChapterNode>) -> Note {
+        let (note_node, kind) = match ancestors(target).find_map(|node| {
+            let kind = node.note_kind()?;
+            Some((node, kind))
+        }) {
+            Some(note) => note,
+            None => {
+                let block = ancestors(target)
+                    .find(|node| BLOCK_TAGS.contains(&node.tag.as_str()))
+                    .unwrap_or_else(|| Arc::clone(target));
+                let kind = if ancestors(&block).any(|node| node.is_note_collection()) {
+                    NoteKind::Endnote
+                } else {
+                    NoteKind::Unmarked
+                };
+
+                (block, kind)
+            }
+        };
+
+        let text = note_node
+            .text_runs()
+            .into_iter()
+            .filter(|run| !run.elements.iter().any(|element| element.is_backlink()))
+            .map(|run| run.text)
+            .collect::<Vec<String>>()
+            .concat();
+
+        Note {
+            location: ResourcePath {
+                path: path.to_string(),
+                fragment: note_node.id().or(target.id()).map(|id| id.to_string()),
+            },
+            kind,
+            text: text.split_whitespace().collect::<Vec<&str>>().join(" "),
+        }
+    }
+}
+
+impl ChapterNode {
+    /// Kind of the note when the element is marked as one by `epub:type`, `role` or being an `<aside>`
+    pub fn note_kind(&self) -> Option<NoteKind> {
+        let role = self.attribute("role");
+
+        NOTE_TYPES
+            .iter()
+            .find(|(note_type, _)| self.has_epub_type(note_type) || role == Some(note_type))
+            .map(|(_, kind)| *kind)
+            .or_else(|| (self.tag == "aside").then_some(NoteKind::Aside))
+    }
+
+    /// Whether the element is a link to a note, either marked as `noteref`
+    /// or a superscript link to an element, e.g. `<sup><a href="notes.xhtml#note3">3</a></sup>`
+    pub fn is_note_reference(&self) -> bool {
+        let href = match (self.tag.as_str(), self.href()) {
+            ("a", Some(href)) => href,
+            _ => return false,
+        };
+
+        if self.has_epub_type("noteref") || self.attribute("role") == Some("doc-noteref") {
+            return true;
+        }
+
+        let points_to_element = !is_external_href(href.trim()) && href.contains('#');
+        let is_superscript = self.get_children().iter().any(|child| child.tag == "sup")
+            || self
+                .get_parent()
+                .upgrade()
+                .is_some_and(|parent| parent.tag == "sup");
+
+        points_to_element && is_superscript
+    }
+
+    fn is_note_collection(&self) -> bool {
+        let role = self.attribute("role");
+
+        NOTE_COLLECTION_TYPES.iter().any(|collection_type| {
+            self.has_epub_type(collection_type) || role == Some(collection_type)
+        })
+    }
+
+    fn is_backlink(&self) -> bool {
+        self.has_epub_type("backlink") || self.attribute("role") == Some("doc-backlink")
+    }
+}
+
+impl Chapter {
+    /// References to notes in the document order
+    pub fn note_references(&self) -> Vec<NoteReference> {
+        descendants(&self.recreated_structure)
+            .filter(|node| node.is_note_reference())
+            .filter_map(|node| {
+                let href = node.href()?.to_string();
+                let target = match LinkTarget::resolve(&self.path, &href) {
+                    LinkTarget::Internal(target) => target,
+                    LinkTarget::External(_) => return None,
+                };
+
+                Some(NoteReference {
+                    label: node.text_content().trim().to_string(),
+                    href,
+                    target,
+                })
+            })
+            .collect()
+    }
+
+    /// Elements of the document marked as notes, nested notes are a part of the outer one
+    pub fn notes(&self) -> Vec<Note> {
+        let mut notes = vec![];
+        let mut nodes = vec![Arc::clone(&self.recreated_structure)];
+
+        while let Some(node) = nodes.pop() {
+            if node.note_kind().is_some() {
+                notes.push(Note::from_target(&self.path, &node));
+            } else {
+                nodes.extend(node.get_children().into_iter().rev());
+            }
+        }
+
+        notes
+    }
+
+    /// Note the element with given id belongs to
+    pub fn note(&self, id: &str) -> Option<Note> {
+        self.find_by_id(id)
+            .map(|target| Note::from_target(&self.path, &target))
+    }
+}
+
+impl EBook {
+    /// Note pointed to by the reference, `None` when the target element does not exist
+    pub fn note(&self, target: &ResourcePath) -> Result<Option<Note>, EpubError> {
+        let id = match target.fragment.as_deref() {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let chapter = self.chapter(&TableOfContentsItem::new(
+            target.path.clone(),
+            String::new(),
+            None,
+        ))?;
+
+        Ok(chapter.note(id))
+    }
+}
+
+/// The node itself followed by its ancestors, from the closest one
+fn ancestors(node: &Arc<ChapterNode>) -> impl Iterator<Item = Arc<ChapterNode>> {
+    std::iter::successors(Some(Arc::clone(node)), |node| node.get_parent().upgrade())
+}
+
+/// The node itself followed by all of its descendants in the document order
+fn descendants(node: &Arc<ChapterNode>) -> impl Iterator<Item = Arc<ChapterNode>> {
+    let mut nodes = vec![Arc::clone(node)];
+
+    std::iter::from_fn(move || {
+        let node = nodes.pop()?;
+        nodes.extend(node.get_children().into_iter().rev());
+        Some(node)
+    })
+}
+
+#[cfg(test)]
+mod notes_tests {
+    use super::*;
+    use crate::test_utils::{chapter_from_content, create_epub};
+
+    const CHAPTER_CONTENT: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+        <html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+            <body>
+                <p>The whale<a epub:type="noteref" href="#fn1">1</a> and the sea<sup><a href="notes.xhtml#note2">2</a></sup>.</p>
+                <p>Back to <a href="chapter_2.xhtml#start">the next chapter</a>.</p>
+                <aside epub:type="footnote" id="fn1">
+                    <p><a epub:type="backlink" href="#ref1">↩</a> Leviathan,   of the <em>deep</em>.</p>
+                </aside>
+            </body>
+        </html>"##;
+
+    const NOTES_CONTENT: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+        <html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+            <body>
+                <ol epub:type="endnotes">
+                    <li><span id="note2"></span>The sea is salty.</li>
+                </ol>
+                <p><a id="note3">3.</a> Unmarked note.</p>
+            </body>
+        </html>"##;
+
+    #[test]
+    fn should_find_semantic_and_superscript_note_references() {
+        //arrange
+        let chapter = chapter_from_content("OEBPS/chapter_1.xhtml", CHAPTER_CONTENT);
+
+        //act
+        let references = chapter.note_references();
+
+        //assert
+        assert_eq!(references.len(), 2);
+        assert_eq!(references[0].label, "1");
+        assert_eq!(
+            references[0].target,
+            ResourcePath::resolve("OEBPS/chapter_1.xhtml", "#fn1")
+        );
+        assert_eq!(references[1].label, "2");
+        assert_eq!(references[1].href, "notes.xhtml#note2");
+        assert_eq!(references[1].target.path, "OEBPS/notes.xhtml");
+        assert_eq!(references[1].target.fragment.as_deref(), Some("note2"));
+    }
+
+    #[test]
+    fn should_extract_text_of_marked_notes_without_backlinks() {
+        //arrange
+        let chapter = chapter_from_content("OEBPS/chapter_1.xhtml", CHAPTER_CONTENT);
+
+        //act
+        let notes = chapter.notes();
+
+        //assert
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].kind, NoteKind::Footnote);
+        assert_eq!(notes[0].text, "Leviathan, of the deep.");
+        assert_eq!(notes[0].location.fragment.as_deref(), Some("fn1"));
+        assert_eq!(chapter.note("fn1"), Some(notes[0].clone()));
+    }
+
+    #[test]
+    fn should_use_closest_block_of_unmarked_note_targets() {
+        //arrange
+        let chapter = chapter_from_content("OEBPS/notes.xhtml", NOTES_CONTENT);
+
+        //act
+        let endnote = chapter.note("note2").unwrap();
+        let unmarked_note = chapter.note("note3").unwrap();
+
+        //assert
+        assert_eq!(endnote.kind, NoteKind::Endnote);
+        assert_eq!(endnote.text, "The sea is salty.");
+        assert_eq!(endnote.location.fragment.as_deref(), Some("note2"));
+        assert_eq!(unmarked_note.kind, NoteKind::Unmarked);
+        assert_eq!(unmarked_note.text, "3. Unmarked note.");
+        assert!(chapter.note("missing").is_none());
+    }
+
+    #[test]
+    fn should_read_notes_placed_in_other_documents() {
+        //arrange
+        let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Notes</dc:title></metadata>
+                <manifest>
+                    <item id="chapter_1" href="chapter_1.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="notes" href="notes.xhtml" media-type="application/xhtml+xml"/>
+                </manifest>
+                <spine>
+                    <itemref idref="chapter_1"/>
+                    <itemref idref="notes"/>
+                </spine>
+            </package>"#;
+        let path = create_epub(
+            "notes_in_other_documents.epub",
+            opf,
+            &[
+                ("chapter_1.xhtml", CHAPTER_CONTENT.as_bytes()),
+                ("notes.xhtml", NOTES_CONTENT.as_bytes()),
+            ],
+        );
+        let book = EBook::read_epub(path).unwrap();
+        let chapter = chapter_from_content("OEBPS/chapter_1.xhtml", CHAPTER_CONTENT);
+
+        //act
+        let notes = chapter
+            .note_references()
+            .iter()
+            .map(|reference| book.note(&reference.target).unwrap())
+            .collect::<Vec<Option<Note>>>();
+
+        //assert
+        assert_eq!(notes[0].as_ref().unwrap().text, "Leviathan, of the deep.");
+        assert_eq!(notes[1].as_ref().unwrap().text, "The sea is salty.");
+    }
+}
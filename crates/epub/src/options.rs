@@ -0,0 +1,24 @@
+use crate::{
+    chapters::chapter::ChapterParsingMode,
+    table_of_contents::table_of_contents::TableOfContentsSource,
+};
+
+/// Options used while reading the epub file
+#[derive(Debug, Clone)]
+pub struct EBookOptions {
+    /// Table of contents to use when the book contains both the epub 3 navigation document and the epub 2 NCX
+    pub preferred_table_of_contents: TableOfContentsSource,
+    /// Number of parsed chapters kept in memory, 0 disables the cache
+    pub chapter_cache_capacity: usize,
+    pub chapter_parsing_mode: ChapterParsingMode,
+}
+
+impl Default for EBookOptions {
+    fn default() -> Self {
+        Self {
+            preferred_table_of_contents: TableOfContentsSource::default(),
+            chapter_cache_capacity: 8,
+            chapter_parsing_mode: ChapterParsingMode::default(),
+        }
+    }
+}
@@ -1,63 +1,411 @@
 use std::sync::Arc;
 
-use crate::{chapters::chapter::Chapter, epub::EBook};
+use crate::{
+    cfi::Cfi,
+    chapters::{chapter::Chapter, chapter_node::ChapterNode},
+    epub::EBook,
+    error::EpubError,
+    notes::Note,
+    rendition::FixedLayoutPage,
+    resources::LinkTarget,
+    spine::BookSpineItem,
+    styles::ChapterStyles,
+    table_of_contents::table_of_contents_item::TableOfContentsItem,
+};
 
+/// Reader following the spine of the book, which defines the reading order.
+/// Items marked with `linear="no"` are skipped by default, but still can be opened directly.
 pub struct EBookReader {
     book: EBook,
     session: ReadingSession,
+    include_non_linear: bool,
 }
 
 struct ReadingSession {
     current: Arc<Chapter>,
+    spine_position: usize,
+    /// Id of the element the view should be positioned at, e.g. when a part of the document was opened from the table of contents
+    anchor: Option<String>,
+    /// Location of the view, which unlike the chapter can be saved and used to restore the session after a restart
+    location: Cfi,
 }
 
-impl EBookReader {
-    pub fn new(mut ebook: EBook) -> Self {
-        let first_toc_item = ebook.table_of_contents.items.first().unwrap().clone();
-        let chapter = Chapter::from_item(first_toc_item, &mut ebook);
+impl ReadingSession {
+    /// Session positioned at the anchored element, or at the beginning of the chapter when there is no such element
+    fn new(
+        ebook: &EBook,
+        chapter: Arc<Chapter>,
+        spine_position: usize,
+        anchor: Option<String>,
+    ) -> ReadingSession {
+        let spine_item_id = ebook
+            .spine
+            .items
+            .get(spine_position)
+            .and_then(|item| item.itemref_id.as_deref());
+        let location_node = anchor
+            .as_deref()
+            .and_then(|anchor| chapter.find_by_id(anchor))
+            .or_else(|| chapter.get_body());
 
-        let session = ReadingSession {
-            current: Arc::new(chapter),
+        let location = match location_node {
+            Some(node) => Cfi::from_node(spine_position, spine_item_id, &node),
+            None => Cfi {
+                spine_position,
+                spine_item_id: spine_item_id.map(|id| id.to_string()),
+                steps: vec![],
+                offset: None,
+            },
         };
 
-        Self {
+        ReadingSession {
+            current: chapter,
+            spine_position,
+            anchor,
+            location,
+        }
+    }
+}
+
+impl EBookReader {
+    /// Opens the first linear item of the spine. Fails when the spine is empty or its first document can not be read.
+    pub fn new(ebook: EBook) -> Result<Self, EpubError> {
+        let first_position = ebook
+            .spine
+            .items
+            .iter()
+            .position(|item| item.linear)
+            .unwrap_or_default();
+        let session = EBookReader::open_spine_position(&ebook, first_position)?;
+
+        Ok(Self {
             book: ebook,
             session,
-        }
+            include_non_linear: false,
+        })
     }
 
     pub fn current_chapter(&self) -> &Chapter {
         &self.session.current
     }
 
-    pub fn move_to_next_chapter(&mut self) {
-        let next_toc_item_to_move_to = self
+    /// Styles of the current chapter, loaded from the archive on each call
+    pub fn current_chapter_styles(&self) -> Result<ChapterStyles, EpubError> {
+        self.book.chapter_styles(&self.session.current)
+    }
+
+    /// Page of the current spine item when it is pre-paginated, `None` when it should be reflowed
+    pub fn current_fixed_layout_page(&self) -> Result<Option<FixedLayoutPage>, EpubError> {
+        self.book.fixed_layout_page(self.session.spine_position)
+    }
+
+    pub fn current_spine_position(&self) -> usize {
+        self.session.spine_position
+    }
+
+    pub fn current_anchor(&self) -> Option<&str> {
+        self.session.anchor.as_deref()
+    }
+
+    /// Element the view should be positioned at, `None` when the chapter is shown from its beginning
+    /// or the location points to an element missing from the document
+    pub fn current_anchor_node(&self) -> Option<Arc<ChapterNode>> {
+        let body = self.session.current.get_body()?;
+        let node = self.session.location.resolve(&self.session.current)?.node;
+
+        // Locations pointing to the body itself or its text are shown from the beginning of the chapter as well
+        let is_inside_body = std::iter::successors(node.get_parent().upgrade(), |node| {
+            node.get_parent().upgrade()
+        })
+        .any(|ancestor| Arc::ptr_eq(&ancestor, &body));
+
+        is_inside_body.then_some(node)
+    }
+
+    /// Location of the view, which can be saved, e.g. with `to_string`, and restored later with `move_to_cfi`
+    pub fn current_location(&self) -> &Cfi {
+        &self.session.location
+    }
+
+    /// Restores a location saved by `current_location` or by other reading systems.
+    /// The document is found by the id asserted on the spine step when the item at the spine position does not have it,
+    /// since the spine may have changed, e.g. by an update of the book. Returns false when the document is not found.
+    /// The current session is kept when the document can not be read.
+    pub fn move_to_cfi(&mut self, cfi: &Cfi) -> Result<bool, EpubError> {
+        let spine_items = &self.book.spine.items;
+        let has_id = |item: &BookSpineItem, id: &str| {
+            item.itemref_id.as_deref() == Some(id) || item.id == id
+        };
+
+        let spine_position = match cfi.spine_item_id.as_deref() {
+            Some(id)
+                if !spine_items
+                    .get(cfi.spine_position)
+                    .is_some_and(|item| has_id(item, id)) =>
+            {
+                spine_items.iter().position(|item| has_id(item, id))
+            }
+            _ => Some(cfi.spine_position).filter(|position| *position < spine_items.len()),
+        };
+
+        let spine_position = match spine_position {
+            Some(spine_position) => spine_position,
+            None => return Ok(false),
+        };
+
+        // Assertions of other reading systems are kept, so that the location is saved the same way it was read
+        let spine_item = &spine_items[spine_position];
+        let spine_item_id = cfi
+            .spine_item_id
+            .clone()
+            .filter(|id| has_id(spine_item, id))
+            .or_else(|| spine_item.itemref_id.clone());
+
+        let mut session = EBookReader::open_spine_position(&self.book, spine_position)?;
+        session.location = Cfi {
+            spine_position,
+            spine_item_id,
+            ..cfi.clone()
+        };
+        self.session = session;
+
+        Ok(true)
+    }
+
+    /// Table of contents item of the current location, keyed on both the document and the anchor,
+    /// since several items may point into one document
+    pub fn current_toc_item(&self) -> Option<&TableOfContentsItem> {
+        self.current_toc_position()
+            .map(|position| &self.book.table_of_contents.items[position])
+    }
+
+    /// Opens the document the table of contents item points to, positioned at the anchored element.
+    /// Items pointing to documents which are not in the spine are ignored.
+    pub fn move_to_toc_item(&mut self, toc_item: &TableOfContentsItem) -> Result<(), EpubError> {
+        self.move_to_location(&toc_item.path, toc_item.anchor.as_deref())
+            .map(|_| ())
+    }
+
+    /// Resolves `href` of a link found in the current chapter, relative to the path of the chapter
+    pub fn resolve_link(&self, href: &str) -> LinkTarget {
+        LinkTarget::resolve(&self.session.current.path, href)
+    }
+
+    /// Moves to the target of a link found in the current chapter.
+    /// External links and links to documents which are not in the spine do not move the reader,
+    /// the target is returned so that the caller can e.g. open the external ones in the browser.
+    /// The current session is kept when the target document can not be read.
+    pub fn follow_link(&mut self, href: &str) -> Result<LinkTarget, EpubError> {
+        let target = self.resolve_link(href);
+
+        if let LinkTarget::Internal(resource) = &target {
+            self.move_to_location(&resource.path, resource.fragment.as_deref())?;
+        }
+
+        Ok(target)
+    }
+
+    /// Note a link found in the current chapter points to, so that it can be shown without leaving the chapter.
+    /// `None` when the link is external or its target element does not exist.
+    pub fn note_of_link(&self, href: &str) -> Result<Option<Note>, EpubError> {
+        match self.resolve_link(href) {
+            LinkTarget::Internal(target) if target.path == self.session.current.path => Ok(target
+                .fragment
+                .and_then(|id| self.session.current.note(&id))),
+            LinkTarget::Internal(target) => self.book.note(&target),
+            LinkTarget::External(_) => Ok(None),
+        }
+    }
+
+    pub fn move_to_next_toc_item(&mut self) -> Result<(), EpubError> {
+        let next_item = self
+            .current_toc_position()
+            .and_then(|position| self.book.table_of_contents.items.get(position + 1))
+            .cloned();
+
+        match next_item {
+            Some(toc_item) => self.move_to_toc_item(&toc_item),
+            None => Ok(()),
+        }
+    }
+
+    pub fn move_to_previous_toc_item(&mut self) -> Result<(), EpubError> {
+        let previous_item = self
+            .current_toc_position()
+            .and_then(|position| position.checked_sub(1))
+            .and_then(|position| self.book.table_of_contents.items.get(position))
+            .cloned();
+
+        match previous_item {
+            Some(toc_item) => self.move_to_toc_item(&toc_item),
+            None => Ok(()),
+        }
+    }
+
+    /// Opens the document placed at `path`, positioned at the element with `anchor` id.
+    /// Returns false when the document is not in the spine, the current session is kept when it can not be read.
+    fn move_to_location(&mut self, path: &str, anchor: Option<&str>) -> Result<bool, EpubError> {
+        let spine_position = self.book.spine.items.iter().position(|spine_item| {
+            self.book
+                .resolve_href(self.book.opf_path(), &spine_item.value.href)
+                .path
+                == path
+        });
+
+        let spine_position = match spine_position {
+            Some(spine_position) => spine_position,
+            None => return Ok(false),
+        };
+
+        let table_of_contents = &self.book.table_of_contents;
+        let label = match table_of_contents.position_of(path, anchor) {
+            Some(position) => table_of_contents.items[position].label.clone(),
+            None => EBookReader::label_for_spine_position(&self.book, spine_position),
+        };
+
+        let chapter =
+            self.book
+                .chapter(&TableOfContentsItem::new(path.to_string(), label, None))?;
+        self.session = ReadingSession::new(
+            &self.book,
+            chapter,
+            spine_position,
+            anchor.map(|anchor| anchor.to_string()),
+        );
+
+        Ok(true)
+    }
+
+    fn current_toc_position(&self) -> Option<usize> {
+        let table_of_contents = &self.book.table_of_contents;
+        let path = &self.session.current.path;
+
+        let exact_position = match self.current_anchor() {
+            Some(anchor) => table_of_contents.position_of(path, Some(anchor)),
+            // Restored locations do not keep an anchor, even when they point inside of the document
+            None if self.current_anchor_node().is_none() => {
+                table_of_contents.position_of(path, None)
+            }
+            None => None,
+        };
+        if exact_position.is_some() {
+            return exact_position;
+        }
+
+        // Other locations, e.g. targets of links or restored locations, belong to the last item of the document
+        // placed before them, or to its first item when all of them are placed after the location
+        let location = &self.session.location;
+        let preceding_position = table_of_contents
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.path == *path)
+            .filter(|(_, item)| match item.anchor.as_deref() {
+                Some(anchor) => self.session.current.find_by_id(anchor).is_some_and(|node| {
+                    let toc_location = Cfi::from_node(
+                        location.spine_position,
+                        location.spine_item_id.as_deref(),
+                        &node,
+                    );
+                    toc_location <= *location
+                }),
+                None => true,
+            })
+            .map(|(position, _)| position)
+            .last();
+
+        preceding_position.or_else(|| table_of_contents.position_of(path, None))
+    }
+
+    /// Whether `linear="no"` items should be visited when moving to the next or previous chapter
+    pub fn set_include_non_linear(&mut self, include_non_linear: bool) {
+        self.include_non_linear = include_non_linear;
+    }
+
+    /// Items that are not a part of the default reading order, together with their spine positions
+    pub fn non_linear_items(&self) -> Vec<(usize, &BookSpineItem)> {
+        self.book
+            .spine
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| !item.linear)
+            .collect()
+    }
+
+    pub fn move_to_next_chapter(&mut self) -> Result<(), EpubError> {
+        let next_position = self
             .book
-            .table_of_contents
-            .next_relative(&self.session.current.path);
+            .spine
+            .next_position(self.session.spine_position, self.include_non_linear);
 
-        if let Some(next_toc_item) = next_toc_item_to_move_to {
-            let current_chapter = Chapter::from_item(next_toc_item.clone(), &mut self.book);
-            let session = ReadingSession {
-                current: Arc::new(current_chapter),
-            };
-            self.session = session;
+        match next_position {
+            Some(position) => self.move_to_spine_position(position),
+            None => Ok(()),
         }
     }
 
-    pub fn move_to_previous_chapter(&mut self) {
-        let previous_toc_item_to_move_to = self
+    pub fn move_to_previous_chapter(&mut self) -> Result<(), EpubError> {
+        let previous_position = self
             .book
-            .table_of_contents
-            .previous_relative(&self.session.current.path);
+            .spine
+            .previous_position(self.session.spine_position, self.include_non_linear);
+
+        match previous_position {
+            Some(position) => self.move_to_spine_position(position),
+            None => Ok(()),
+        }
+    }
+
+    /// Opens the spine item at given position, regardless of it being linear or not.
+    /// Positions outside of the spine are ignored, the current session is kept when the document can not be read.
+    pub fn move_to_spine_position(&mut self, position: usize) -> Result<(), EpubError> {
+        if position < self.book.spine.items.len() {
+            self.session = EBookReader::open_spine_position(&self.book, position)?;
+        }
+
+        Ok(())
+    }
+
+    fn open_spine_position(ebook: &EBook, position: usize) -> Result<ReadingSession, EpubError> {
+        let spine_item = ebook
+            .spine
+            .items
+            .get(position)
+            .ok_or(EpubError::MissingSpineItem(position))?;
+        let path = ebook
+            .resolve_href(ebook.opf_path(), &spine_item.value.href)
+            .path;
+        let label = EBookReader::label_for_spine_position(ebook, position);
+
+        let chapter = ebook.chapter(&TableOfContentsItem::new(path, label, None))?;
 
-        if let Some(previous_toc_item) = previous_toc_item_to_move_to {
-            let current_chapter = Chapter::from_item(previous_toc_item.clone(), &mut self.book);
-            let session = ReadingSession {
-                current: Arc::new(current_chapter),
+        Ok(ReadingSession::new(ebook, chapter, position, None))
+    }
+
+    /// Label of the table of contents entry pointing to the spine item at given position.
+    /// Documents missing from the table of contents get the label of the nearest preceding entry,
+    /// since they are usually a continuation of it, e.g. a chapter split into multiple files.
+    fn label_for_spine_position(ebook: &EBook, position: usize) -> String {
+        for (index, spine_item) in ebook.spine.items[..=position].iter().enumerate().rev() {
+            let path = ebook
+                .resolve_href(ebook.opf_path(), &spine_item.value.href)
+                .path;
+            let mut toc_items = ebook.table_of_contents.items.iter();
+
+            // The document itself is labeled by its first entry, preceding documents by their last one
+            let toc_item = if index == position {
+                toc_items.find(|item| item.path == path)
+            } else {
+                toc_items.rfind(|item| item.path == path)
             };
-            self.session = session;
+
+            if let Some(toc_item) = toc_item {
+                return toc_item.label.clone();
+            }
         }
+
+        ebook.metadata.title.clone().unwrap_or_default()
     }
 }
 
@@ -66,9 +414,13 @@ mod reader_tests {
     use std::{rc::Rc, sync::Arc};
 
     use crate::{
+        cfi::Cfi,
         chapters::{chapter::Chapter, chapter_node::ChapterNode},
         epub::EBook,
+        error::EpubError,
         reader::EBookReader,
+        resources::{LinkTarget, ResourcePath},
+        test_utils::create_epub,
     };
 
     const MOBY_DICK_PATH: &str = "./test_data/epub/moby-dick.epub";
@@ -83,6 +435,7 @@ mod reader_tests {
                     vec![],
                     "content".to_string(),
                 )),
+                warnings: vec![],
                 _raw_content: "raw".to_string(),
             }
         }
@@ -92,7 +445,7 @@ mod reader_tests {
     fn should_create_reader() {
         //arrange
         let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
-        let reader = EBookReader::new(book);
+        let reader = EBookReader::new(book).unwrap();
         let expected_current_chapter = Chapter::with_path_and_label(
             "OPS/titlepage.xhtml".to_string(),
             "Moby-Dick".to_string(),
@@ -114,27 +467,27 @@ mod reader_tests {
             "Moby-Dick".to_string(),
         );
 
-        let expected_2nd_chapter_5th_in_order = Chapter::with_path_and_label(
+        let expected_2nd_chapter_6th_in_order = Chapter::with_path_and_label(
             "OPS/chapter_001.xhtml".to_string(),
             "Chapter 1. Loomings.".to_string(),
         );
 
-        let expected_3rd_chapter_7th_in_order = Chapter::with_path_and_label(
+        let expected_3rd_chapter_8th_in_order = Chapter::with_path_and_label(
             "OPS/chapter_003.xhtml".to_string(),
             "Chapter 3. The Spouter-Inn.".to_string(),
         );
 
-        let expected_4th_chapter_6th_in_order = Chapter::with_path_and_label(
+        let expected_4th_chapter_7th_in_order = Chapter::with_path_and_label(
             "OPS/chapter_002.xhtml".to_string(),
             "Chapter 2. The Carpet-Bag.".to_string(),
         );
 
-        let expected_5th_chapter_138th_in_order = Chapter::with_path_and_label(
+        let expected_5th_chapter_142nd_in_order = Chapter::with_path_and_label(
             "OPS/copyright.xhtml".to_string(),
             "Copyright Page".to_string(),
         );
 
-        let mut reader = EBookReader::new(book);
+        let mut reader = EBookReader::new(book).unwrap();
 
         //act & assert
         //Comparing initial chapter after reader creation
@@ -142,42 +495,440 @@ mod reader_tests {
         assert_eq!(*current_chapter, expected_1st_chapter_1st_in_order);
 
         //Trying to move back to previous chapter, should not change the current chapter since reader is on the first one
-        reader.move_to_previous_chapter();
-        reader.move_to_previous_chapter();
-        reader.move_to_previous_chapter();
+        reader.move_to_previous_chapter().unwrap();
+        reader.move_to_previous_chapter().unwrap();
+        reader.move_to_previous_chapter().unwrap();
         let current_chapter = reader.current_chapter();
         assert_eq!(*current_chapter, expected_1st_chapter_1st_in_order);
 
-        //Moving a couple of chapters in order to the 5th one
-        reader.move_to_next_chapter();
-        reader.move_to_next_chapter();
-        reader.move_to_next_chapter();
-        reader.move_to_next_chapter();
+        //Moving a couple of chapters in order to the 6th one
+        reader.move_to_next_chapter().unwrap();
+        reader.move_to_next_chapter().unwrap();
+        reader.move_to_next_chapter().unwrap();
+        reader.move_to_next_chapter().unwrap();
+        reader.move_to_next_chapter().unwrap();
         let current_chapter = reader.current_chapter();
-        assert_eq!(*current_chapter, expected_2nd_chapter_5th_in_order);
+        assert_eq!(*current_chapter, expected_2nd_chapter_6th_in_order);
 
-        //Moving a couple of chapters in order to the 7th one
-        reader.move_to_next_chapter();
-        reader.move_to_next_chapter();
+        //Moving a couple of chapters in order to the 8th one
+        reader.move_to_next_chapter().unwrap();
+        reader.move_to_next_chapter().unwrap();
         let current_chapter = reader.current_chapter();
-        assert_eq!(*current_chapter, expected_3rd_chapter_7th_in_order);
+        assert_eq!(*current_chapter, expected_3rd_chapter_8th_in_order);
 
-        //Moving to a previous chapter, 6th in order
-        reader.move_to_previous_chapter();
+        //Moving to a previous chapter, 7th in order
+        reader.move_to_previous_chapter().unwrap();
         let current_chapter = reader.current_chapter();
-        assert_eq!(*current_chapter, expected_4th_chapter_6th_in_order);
+        assert_eq!(*current_chapter, expected_4th_chapter_7th_in_order);
 
-        //Moving to a previous chapter, then to the next in order, landing on 6th
-        reader.move_to_previous_chapter();
-        reader.move_to_next_chapter();
+        //Moving to a previous chapter, then to the next in order, landing on 7th
+        reader.move_to_previous_chapter().unwrap();
+        reader.move_to_next_chapter().unwrap();
         let current_chapter = reader.current_chapter();
-        assert_eq!(*current_chapter, expected_4th_chapter_6th_in_order);
+        assert_eq!(*current_chapter, expected_4th_chapter_7th_in_order);
 
-        //Moving to last chapter. There are only 138 chapters in the book, so moving 150 times should land on the last one
+        //Moving to last chapter. There are only 142 linear chapters in the book, so moving 150 times should land on the last one
         for _ in 0..150 {
-            reader.move_to_next_chapter();
+            reader.move_to_next_chapter().unwrap();
         }
         let current_chapter = reader.current_chapter();
-        assert_eq!(*current_chapter, expected_5th_chapter_138th_in_order);
+        assert_eq!(*current_chapter, expected_5th_chapter_142nd_in_order);
+    }
+
+    #[test]
+    fn should_label_spine_items_missing_from_toc_with_preceding_toc_entry() {
+        //arrange
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let mut reader = EBookReader::new(book).unwrap();
+        let expected_chapter = Chapter::with_path_and_label(
+            "OPS/toc-short.xhtml".to_string(),
+            "Moby-Dick".to_string(),
+        );
+
+        //act
+        reader.move_to_next_chapter().unwrap();
+
+        //assert
+        assert_eq!(*reader.current_chapter(), expected_chapter);
+        assert_eq!(reader.current_spine_position(), 2);
+    }
+
+    #[test]
+    fn should_expose_non_linear_items_and_open_them_on_demand() {
+        //arrange
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let mut reader = EBookReader::new(book).unwrap();
+
+        //act
+        let non_linear_ids: Vec<(usize, String)> = reader
+            .non_linear_items()
+            .iter()
+            .map(|(position, item)| (*position, item.id.clone()))
+            .collect();
+        reader.move_to_spine_position(0).unwrap();
+
+        //assert
+        assert_eq!(
+            non_linear_ids,
+            vec![(0, "cover".to_string()), (143, "toc".to_string())]
+        );
+        assert_eq!(reader.current_chapter().path, "OPS/cover.xhtml");
+        //Cover is not in the table of contents and nothing precedes it, so the title of the book is used
+        assert_eq!(reader.current_chapter().label, "Moby-Dick");
+    }
+
+    #[test]
+    fn should_visit_non_linear_items_when_included() {
+        //arrange
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let mut reader = EBookReader::new(book).unwrap();
+
+        //act
+        reader.move_to_previous_chapter().unwrap();
+        let position_with_non_linear_skipped = reader.current_spine_position();
+        reader.set_include_non_linear(true);
+        reader.move_to_previous_chapter().unwrap();
+
+        //assert
+        assert_eq!(position_with_non_linear_skipped, 1);
+        assert_eq!(reader.current_spine_position(), 0);
+    }
+
+    #[test]
+    fn should_reuse_parsed_chapter_when_moving_back_and_forth() {
+        //arrange
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let mut reader = EBookReader::new(book).unwrap();
+        let first_structure = Arc::clone(&reader.current_chapter().recreated_structure);
+
+        //act
+        reader.move_to_next_chapter().unwrap();
+        reader.move_to_previous_chapter().unwrap();
+
+        //assert
+        assert!(Arc::ptr_eq(
+            &first_structure,
+            &reader.current_chapter().recreated_structure
+        ));
+    }
+
+    const PARTS_NAV_CONTENT: &str = r#"<html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+        <nav epub:type="toc"><ol>
+            <li><a href="parts.xhtml#part_1">Part 1</a></li>
+            <li><a href="parts.xhtml#part_2">Part 2</a></li>
+            <li><a href="epilogue.xhtml">Epilogue</a></li>
+        </ol></nav>
+    </body></html>"#;
+
+    const PARTS_CONTENT: &str = r#"<html><body>
+        <section id="part_1"><h1>Part 1</h1><p id="part_1_text">Text</p></section>
+        <section id="part_2"><h1>Part 2</h1></section>
+    </body></html>"#;
+
+    fn create_book_with_parts_in_one_file(name: &str) -> EBook {
+        let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Parts</dc:title></metadata>
+                <manifest>
+                    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+                    <item id="parts" href="parts.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="epilogue" href="epilogue.xhtml" media-type="application/xhtml+xml"/>
+                </manifest>
+                <spine>
+                    <itemref idref="parts"/>
+                    <itemref idref="epilogue"/>
+                </spine>
+            </package>"#;
+
+        let path = create_epub(
+            name,
+            opf,
+            &[
+                ("nav.xhtml", PARTS_NAV_CONTENT.as_bytes()),
+                ("parts.xhtml", PARTS_CONTENT.as_bytes()),
+                (
+                    "epilogue.xhtml",
+                    b"<html><body><p>The end</p></body></html>",
+                ),
+            ],
+        );
+
+        EBook::read_epub(path).unwrap()
+    }
+
+    #[test]
+    fn should_navigate_table_of_contents_items_pointing_into_one_file() {
+        //arrange
+        let book = create_book_with_parts_in_one_file("reader_parts_navigation.epub");
+        let mut reader = EBookReader::new(book).unwrap();
+
+        //act & assert
+        assert_eq!(reader.current_toc_item().unwrap().label, "Part 1");
+        assert_eq!(reader.current_anchor(), None);
+
+        reader.move_to_next_toc_item().unwrap();
+        assert_eq!(reader.current_toc_item().unwrap().label, "Part 2");
+        assert_eq!(reader.current_anchor(), Some("part_2"));
+        assert_eq!(reader.current_spine_position(), 0);
+
+        reader.move_to_next_toc_item().unwrap();
+        assert_eq!(reader.current_toc_item().unwrap().label, "Epilogue");
+        assert_eq!(reader.current_spine_position(), 1);
+
+        reader.move_to_previous_toc_item().unwrap();
+        reader.move_to_previous_toc_item().unwrap();
+        assert_eq!(reader.current_toc_item().unwrap().label, "Part 1");
+        assert_eq!(reader.current_anchor(), Some("part_1"));
+    }
+
+    #[test]
+    fn should_position_the_view_at_the_anchored_element() {
+        //arrange
+        let book = create_book_with_parts_in_one_file("reader_parts_anchor.epub");
+        let part_2 = book.table_of_contents.items[1].clone();
+        let mut reader = EBookReader::new(book).unwrap();
+
+        //act
+        reader.move_to_toc_item(&part_2).unwrap();
+
+        //assert
+        let anchor_node = reader.current_anchor_node().unwrap();
+        assert_eq!(anchor_node.tag, "section");
+        assert_eq!(anchor_node.text_content(), "Part 2");
+        assert_eq!(reader.current_chapter().label, "Part 2");
+
+        reader.move_to_next_chapter().unwrap();
+        assert!(reader.current_anchor_node().is_none());
+    }
+
+    #[test]
+    fn should_follow_links_relative_to_the_current_chapter() {
+        //arrange
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let mut reader = EBookReader::new(book).unwrap();
+        reader.move_to_spine_position(6).unwrap();
+
+        //act
+        let target = reader.follow_link("chapter_012.xhtml#note3").unwrap();
+
+        //assert
+        assert_eq!(
+            target,
+            LinkTarget::Internal(ResourcePath {
+                path: "OPS/chapter_012.xhtml".to_string(),
+                fragment: Some("note3".to_string()),
+            })
+        );
+        assert_eq!(reader.current_chapter().path, "OPS/chapter_012.xhtml");
+        assert_eq!(reader.current_chapter().label, "Chapter 12. Biographical.");
+        assert_eq!(reader.current_anchor(), Some("note3"));
+    }
+
+    #[test]
+    fn should_not_move_when_following_external_links_or_links_outside_of_spine() {
+        //arrange
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let mut reader = EBookReader::new(book).unwrap();
+        reader.move_to_spine_position(6).unwrap();
+
+        //act
+        let external_target = reader.follow_link("https://example.com/moby-dick").unwrap();
+        let image_target = reader
+            .follow_link("images/Moby-Dick_FE_title_page.jpg")
+            .unwrap();
+
+        //assert
+        assert_eq!(
+            external_target,
+            LinkTarget::External("https://example.com/moby-dick".to_string())
+        );
+        assert!(matches!(image_target, LinkTarget::Internal(_)));
+        assert_eq!(reader.current_spine_position(), 6);
+    }
+
+    #[test]
+    fn should_follow_links_to_elements_of_the_current_chapter() {
+        //arrange
+        let book = create_book_with_parts_in_one_file("reader_parts_links.epub");
+        let mut reader = EBookReader::new(book).unwrap();
+
+        //act
+        reader.follow_link("#part_2").unwrap();
+
+        //assert
+        assert_eq!(reader.current_spine_position(), 0);
+        assert_eq!(reader.current_toc_item().unwrap().label, "Part 2");
+        assert_eq!(
+            reader.current_anchor_node().unwrap().text_content(),
+            "Part 2"
+        );
+    }
+
+    #[test]
+    fn should_navigate_table_of_contents_from_anchors_missing_from_it() {
+        //arrange
+        let book = create_book_with_parts_in_one_file("reader_parts_non_toc_anchor.epub");
+        let mut reader = EBookReader::new(book).unwrap();
+        let part_2_heading: Cfi = "epubcfi(/6/2!/2/4[part_2]/2)".parse().unwrap();
+
+        //act & assert
+        reader.follow_link("#part_1_text").unwrap();
+        assert_eq!(reader.current_toc_item().unwrap().label, "Part 1");
+
+        reader.move_to_next_toc_item().unwrap();
+        assert_eq!(reader.current_toc_item().unwrap().label, "Part 2");
+
+        reader.move_to_cfi(&part_2_heading).unwrap();
+        assert_eq!(reader.current_toc_item().unwrap().label, "Part 2");
+
+        reader.move_to_previous_toc_item().unwrap();
+        assert_eq!(reader.current_toc_item().unwrap().label, "Part 1");
+        assert_eq!(reader.current_anchor(), Some("part_1"));
+    }
+
+    #[test]
+    fn should_read_notes_of_links_without_moving() {
+        //arrange
+        let book = create_book_with_parts_in_one_file("reader_parts_notes.epub");
+        let reader = EBookReader::new(book).unwrap();
+
+        //act
+        let same_document_note = reader.note_of_link("#part_2").unwrap();
+        let other_document_note = reader.note_of_link("epilogue.xhtml#missing").unwrap();
+        let external_note = reader.note_of_link("https://example.com#note").unwrap();
+
+        //assert
+        assert_eq!(same_document_note.unwrap().text, "Part 2");
+        assert!(other_document_note.is_none());
+        assert!(external_note.is_none());
+        assert_eq!(reader.current_anchor(), None);
+    }
+
+    #[test]
+    fn should_restore_saved_location_in_new_session() {
+        //arrange
+        let book = create_book_with_parts_in_one_file("reader_parts_location.epub");
+        let mut reader = EBookReader::new(book).unwrap();
+        reader.follow_link("#part_2").unwrap();
+        let saved_location = reader.current_location().to_string();
+
+        let book = create_book_with_parts_in_one_file("reader_parts_location_restored.epub");
+        let mut restored_reader = EBookReader::new(book).unwrap();
+        restored_reader.move_to_next_chapter().unwrap();
+
+        //act
+        let moved = restored_reader
+            .move_to_cfi(&saved_location.parse::<Cfi>().unwrap())
+            .unwrap();
+
+        //assert
+        assert!(moved);
+        assert_eq!(saved_location, "epubcfi(/6/2!/2/4[part_2])");
+        assert_eq!(restored_reader.current_spine_position(), 0);
+        assert_eq!(
+            restored_reader.current_anchor_node().unwrap().id(),
+            Some("part_2")
+        );
+        assert_eq!(
+            restored_reader.current_location().to_string(),
+            saved_location
+        );
+    }
+
+    #[test]
+    fn should_find_spine_item_of_location_by_asserted_id() {
+        //arrange
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let mut reader = EBookReader::new(book).unwrap();
+        let moved_chapter: Cfi = "epubcfi(/6/2[xchapter_001]!/4/2/1:0)".parse().unwrap();
+        let missing_chapter: Cfi = "epubcfi(/6/2[missing]!/4)".parse().unwrap();
+
+        //act & assert
+        assert!(reader.move_to_cfi(&moved_chapter).unwrap());
+        assert_eq!(reader.current_chapter().path, "OPS/chapter_001.xhtml");
+        assert_eq!(reader.current_location().spine_position, 6);
+        assert_eq!(
+            reader.current_location().to_string(),
+            "epubcfi(/6/14[xchapter_001]!/4/2/1:0)"
+        );
+
+        assert!(!reader.move_to_cfi(&missing_chapter).unwrap());
+        assert_eq!(reader.current_chapter().path, "OPS/chapter_001.xhtml");
+    }
+
+    #[test]
+    fn should_fail_to_open_book_with_empty_spine() {
+        //arrange
+        let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Empty</dc:title></metadata>
+                <manifest/>
+                <spine/>
+            </package>"#;
+        let path = create_epub("reader_empty_spine.epub", opf, &[]);
+        let book = EBook::read_epub(path).unwrap();
+
+        //act
+        let result = EBookReader::new(book);
+
+        //assert
+        assert!(matches!(result, Err(EpubError::MissingSpineItem(0))));
+    }
+
+    fn create_book_with_missing_document(name: &str) -> EBook {
+        let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Missing</dc:title></metadata>
+                <manifest>
+                    <item id="chapter_1" href="chapter_1.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="chapter_2" href="chapter_2.xhtml" media-type="application/xhtml+xml"/>
+                </manifest>
+                <spine>
+                    <itemref idref="chapter_1"/>
+                    <itemref idref="chapter_2"/>
+                </spine>
+            </package>"#;
+        let path = create_epub(
+            name,
+            opf,
+            &[(
+                "chapter_1.xhtml",
+                b"<html><body><p><a href=\"chapter_2.xhtml\">Chapter 2</a></p></body></html>",
+            )],
+        );
+
+        EBook::read_epub(path).unwrap()
+    }
+
+    #[test]
+    fn should_keep_current_chapter_when_spine_document_is_missing() {
+        //arrange
+        let book = create_book_with_missing_document("reader_missing_spine_document.epub");
+        let mut reader = EBookReader::new(book).unwrap();
+
+        //act
+        let next_chapter = reader.move_to_next_chapter();
+        let spine_position = reader.move_to_spine_position(1);
+
+        //assert
+        assert!(next_chapter.is_err());
+        assert!(spine_position.is_err());
+        assert_eq!(reader.current_spine_position(), 0);
+        assert_eq!(reader.current_chapter().path, "OEBPS/chapter_1.xhtml");
+    }
+
+    #[test]
+    fn should_keep_current_chapter_when_link_target_is_missing() {
+        //arrange
+        let book = create_book_with_missing_document("reader_missing_link_target.epub");
+        let mut reader = EBookReader::new(book).unwrap();
+
+        //act
+        let target = reader.follow_link("chapter_2.xhtml");
+
+        //assert
+        assert!(target.is_err());
+        assert_eq!(reader.current_spine_position(), 0);
+        assert_eq!(reader.current_chapter().path, "OEBPS/chapter_1.xhtml");
     }
 }
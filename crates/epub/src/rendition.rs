@@ -0,0 +1,471 @@
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::{
+    cover::CoverImage, encoding::decode_document, epub::EBook, error::EpubError,
+    spine::BookSpineItem, xml::attribute_value,
+};
+
+const RENDITION_PREFIX: &str = "rendition:";
+
+/// Whether the content is reflowed to the screen or every spine item is a page of fixed dimensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenditionLayout {
+    #[default]
+    Reflowable,
+    PrePaginated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenditionOrientation {
+    #[default]
+    Auto,
+    Landscape,
+    Portrait,
+}
+
+/// When two pages should be shown side by side as a spread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenditionSpread {
+    #[default]
+    Auto,
+    None,
+    Landscape,
+    /// Also used for the deprecated `portrait` value, which the spec treats as `both`
+    Both,
+}
+
+/// Side of the spread on which the page should be placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSpread {
+    Left,
+    Right,
+    Center,
+}
+
+/// Rendition properties of the package, see https://www.w3.org/TR/epub-33/#sec-fixed-layouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenditionProperties {
+    pub layout: RenditionLayout,
+    pub orientation: RenditionOrientation,
+    pub spread: RenditionSpread,
+}
+
+impl RenditionProperties {
+    /// Applies `<meta property="rendition:*">` of the package, unknown properties and values are ignored
+    pub(crate) fn apply_meta(&mut self, property: &str, value: &str) {
+        let value = value.trim();
+
+        match property.strip_prefix(RENDITION_PREFIX) {
+            Some("layout") => match value {
+                "reflowable" => self.layout = RenditionLayout::Reflowable,
+                "pre-paginated" => self.layout = RenditionLayout::PrePaginated,
+                _ => {}
+            },
+            Some("orientation") => match value {
+                "auto" => self.orientation = RenditionOrientation::Auto,
+                "landscape" => self.orientation = RenditionOrientation::Landscape,
+                "portrait" => self.orientation = RenditionOrientation::Portrait,
+                _ => {}
+            },
+            Some("spread") => match value {
+                "auto" => self.spread = RenditionSpread::Auto,
+                "none" => self.spread = RenditionSpread::None,
+                "landscape" => self.spread = RenditionSpread::Landscape,
+                "both" | "portrait" => self.spread = RenditionSpread::Both,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Properties of given spine item, the global ones overridden by its `rendition:*` properties,
+    /// e.g. `rendition:layout-reflowable`
+    pub fn for_spine_item(&self, spine_item: &BookSpineItem) -> RenditionProperties {
+        let mut properties = *self;
+
+        for property in &spine_item.properties {
+            let override_value = property
+                .strip_prefix(RENDITION_PREFIX)
+                .and_then(|property| property.split_once('-'));
+
+            if let Some((name, value)) = override_value {
+                properties.apply_meta(&format!("{}{}", RENDITION_PREFIX, name), value);
+            }
+        }
+
+        properties
+    }
+}
+
+impl BookSpineItem {
+    /// Side of the spread from `page-spread-*` or `rendition:page-spread-*` properties
+    pub fn page_spread(&self) -> Option<PageSpread> {
+        self.properties.iter().find_map(|property| {
+            match property.trim_start_matches(RENDITION_PREFIX) {
+                "page-spread-left" => Some(PageSpread::Left),
+                "page-spread-right" => Some(PageSpread::Right),
+                "page-spread-center" => Some(PageSpread::Center),
+                _ => None,
+            }
+        })
+    }
+}
+
+/// Dimensions of a fixed-layout page in CSS pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    /// Parses the content of `<meta name="viewport">`, e.g. `width=1200, height=1800`.
+    /// Returns `None` unless both dimensions are given in pixels.
+    pub fn from_meta_content(content: &str) -> Option<Viewport> {
+        let mut width = None;
+        let mut height = None;
+
+        for declaration in content.split([',', ';']) {
+            if let Some((name, value)) = declaration.split_once('=') {
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "width" => width = parse_dimension(value),
+                    "height" => height = parse_dimension(value),
+                    _ => {}
+                }
+            }
+        }
+
+        Some(Viewport {
+            width: width?,
+            height: height?,
+        })
+    }
+
+    /// Reads the viewport of an XHTML page from `<meta name="viewport">`.
+    /// SVG pages, and XHTML pages without the meta, are sized by the `viewBox`
+    /// or the `width` and `height` of their first `<svg>` element.
+    pub fn from_document(document: &str) -> Result<Option<Viewport>, EpubError> {
+        let mut reader = Reader::from_str(document);
+        reader.check_end_names(false);
+
+        let mut buf = Vec::new();
+        let mut svg_viewport: Option<Option<Viewport>> = None;
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(ref e) | Event::Empty(ref e) => match e.local_name().as_ref() {
+                    b"meta" if attribute_value(e, b"name")?.as_deref() == Some("viewport") => {
+                        let viewport = attribute_value(e, b"content")?
+                            .and_then(|content| Viewport::from_meta_content(&content));
+
+                        if viewport.is_some() {
+                            return Ok(viewport);
+                        }
+                    }
+                    b"svg" if svg_viewport.is_none() => {
+                        svg_viewport = Some(Viewport::from_svg_element(e)?);
+                    }
+                    _ => {}
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(svg_viewport.flatten())
+    }
+
+    fn from_svg_element(e: &BytesStart) -> Result<Option<Viewport>, EpubError> {
+        let view_box = attribute_value(e, b"viewBox")?.and_then(|view_box| {
+            let values: Vec<f32> = view_box
+                .split([' ', ','])
+                .filter(|value| !value.is_empty())
+                .map(|value| value.parse::<f32>().ok())
+                .collect::<Option<_>>()?;
+
+            match values.as_slice() {
+                [_, _, width, height] if *width > 0.0 && *height > 0.0 => Some(Viewport {
+                    width: *width,
+                    height: *height,
+                }),
+                _ => None,
+            }
+        });
+
+        if view_box.is_some() {
+            return Ok(view_box);
+        }
+
+        let width = attribute_value(e, b"width")?.and_then(|value| parse_dimension(&value));
+        let height = attribute_value(e, b"height")?.and_then(|value| parse_dimension(&value));
+
+        Ok(width
+            .zip(height)
+            .map(|(width, height)| Viewport { width, height }))
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width / self.height
+    }
+
+    /// Largest size with the aspect ratio of the viewport that fits in given area
+    pub fn scaled_to_fit(&self, width: f32, height: f32) -> (f32, f32) {
+        let scale = (width / self.width).min(height / self.height);
+
+        (self.width * scale, self.height * scale)
+    }
+}
+
+/// Page of a pre-paginated spine item, which should be shown whole instead of being reflowed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedLayoutPage {
+    /// Full path of the page document inside of the archive
+    pub path: String,
+    /// `None` when the page does not declare its dimensions
+    pub viewport: Option<Viewport>,
+    pub page_spread: Option<PageSpread>,
+    /// The image of the page, or the first one when the page has more of them.
+    /// Pages of comics and illustrated books usually consist of a single image.
+    pub image: Option<CoverImage>,
+}
+
+impl EBook {
+    /// Rendition properties of the spine item at given position, `None` when there is no such item
+    pub fn rendition_of_spine_item(&self, position: usize) -> Option<RenditionProperties> {
+        self.spine
+            .items
+            .get(position)
+            .map(|spine_item| self.metadata.rendition.for_spine_item(spine_item))
+    }
+
+    /// Whether every spine item of the book is a fixed-layout page
+    pub fn is_fixed_layout(&self) -> bool {
+        (0..self.spine.items.len()).all(|position| self.is_pre_paginated(position))
+            && !self.spine.items.is_empty()
+    }
+
+    pub fn is_pre_paginated(&self, position: usize) -> bool {
+        self.rendition_of_spine_item(position)
+            .is_some_and(|rendition| rendition.layout == RenditionLayout::PrePaginated)
+    }
+
+    /// Viewport declared by the document of the spine item at given position
+    pub fn viewport_of_spine_item(&self, position: usize) -> Result<Option<Viewport>, EpubError> {
+        let spine_item = match self.spine.items.get(position) {
+            Some(spine_item) => spine_item,
+            None => return Ok(None),
+        };
+
+        let path = self
+            .resolve_href(self.opf_path(), &spine_item.value.href)
+            .path;
+        let document = decode_document(&self.read_resource_at(&path)?)?;
+
+        Viewport::from_document(&document)
+    }
+
+    /// Page of the spine item at given position, `None` when the item is reflowable
+    pub fn fixed_layout_page(&self, position: usize) -> Result<Option<FixedLayoutPage>, EpubError> {
+        if !self.is_pre_paginated(position) {
+            return Ok(None);
+        }
+
+        let spine_item = &self.spine.items[position];
+        let path = self
+            .resolve_href(self.opf_path(), &spine_item.value.href)
+            .path;
+
+        Ok(Some(FixedLayoutPage {
+            viewport: self.viewport_of_spine_item(position)?,
+            page_spread: spine_item.page_spread(),
+            image: self.read_image_from_path(&path)?,
+            path,
+        }))
+    }
+}
+
+/// Parses a dimension in pixels, e.g. `1200` or `1200px`
+fn parse_dimension(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let dimension = value
+        .strip_suffix("px")
+        .unwrap_or(value)
+        .parse::<f32>()
+        .ok()?;
+
+    (dimension > 0.0).then_some(dimension)
+}
+
+#[cfg(test)]
+mod rendition_tests {
+    use super::*;
+    use crate::test_utils::create_epub;
+
+    const PAGE_CONTENT: &str = r#"<html xmlns="http://www.w3.org/1999/xhtml">
+        <head>
+            <meta name="viewport" content="width=1200, height=1800"/>
+        </head>
+        <body><img src="../Images/page_1.png" alt="Page 1"/></body>
+    </html>"#;
+
+    fn create_opf(layout: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+                    <dc:identifier id="uid">urn:uuid:1</dc:identifier>
+                    <dc:title>Atlas</dc:title>
+                    <meta property="rendition:layout">{}</meta>
+                    <meta property="rendition:orientation">landscape</meta>
+                    <meta property="rendition:spread">portrait</meta>
+                </metadata>
+                <manifest>
+                    <item id="page_1" href="Text/page_1.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="notes" href="Text/notes.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="page_1_image" href="Images/page_1.png" media-type="image/png"/>
+                </manifest>
+                <spine>
+                    <itemref idref="page_1" properties="page-spread-left"/>
+                    <itemref idref="notes" properties="rendition:layout-reflowable rendition:spread-none"/>
+                </spine>
+            </package>"#,
+            layout
+        )
+    }
+
+    fn create_atlas(name: &str, layout: &str) -> EBook {
+        let path = create_epub(
+            name,
+            &create_opf(layout),
+            &[
+                ("Text/page_1.xhtml", PAGE_CONTENT.as_bytes()),
+                (
+                    "Text/notes.xhtml",
+                    b"<html><body><p>Notes</p></body></html>",
+                ),
+                ("Images/page_1.png", b"\x89PNG page"),
+            ],
+        );
+
+        EBook::read_epub(path).unwrap()
+    }
+
+    #[test]
+    fn should_read_global_rendition_properties_and_spine_overrides() {
+        let ebook = create_atlas("rendition_properties.epub", "pre-paginated");
+
+        assert_eq!(
+            ebook.metadata.rendition,
+            RenditionProperties {
+                layout: RenditionLayout::PrePaginated,
+                orientation: RenditionOrientation::Landscape,
+                spread: RenditionSpread::Both,
+            }
+        );
+        assert_eq!(
+            ebook.rendition_of_spine_item(1),
+            Some(RenditionProperties {
+                layout: RenditionLayout::Reflowable,
+                orientation: RenditionOrientation::Landscape,
+                spread: RenditionSpread::None,
+            })
+        );
+        assert_eq!(ebook.rendition_of_spine_item(2), None);
+        assert!(ebook.is_pre_paginated(0));
+        assert!(!ebook.is_fixed_layout());
+    }
+
+    #[test]
+    fn should_read_page_spread_of_spine_items() {
+        let ebook = create_atlas("rendition_page_spread.epub", "pre-paginated");
+
+        assert_eq!(ebook.spine.items[0].page_spread(), Some(PageSpread::Left));
+        assert_eq!(ebook.spine.items[1].page_spread(), None);
+    }
+
+    #[test]
+    fn should_read_fixed_layout_page() {
+        let ebook = create_atlas("rendition_page.epub", "pre-paginated");
+
+        let page = ebook.fixed_layout_page(0).unwrap().unwrap();
+
+        assert_eq!(page.path, "OEBPS/Text/page_1.xhtml");
+        assert_eq!(
+            page.viewport,
+            Some(Viewport {
+                width: 1200.0,
+                height: 1800.0
+            })
+        );
+        assert_eq!(page.page_spread, Some(PageSpread::Left));
+        assert_eq!(page.image.unwrap().media_type, "image/png");
+        assert_eq!(ebook.fixed_layout_page(1).unwrap(), None);
+    }
+
+    #[test]
+    fn should_not_return_pages_of_reflowable_books() {
+        let ebook = create_atlas("rendition_reflowable.epub", "reflowable");
+
+        assert_eq!(ebook.fixed_layout_page(0).unwrap(), None);
+        assert_eq!(ebook.metadata.rendition.layout, RenditionLayout::Reflowable);
+    }
+
+    #[test]
+    fn should_parse_viewport_meta_content() {
+        assert_eq!(
+            Viewport::from_meta_content("width=600px; height = 800"),
+            Some(Viewport {
+                width: 600.0,
+                height: 800.0
+            })
+        );
+        assert_eq!(
+            Viewport::from_meta_content("width=device-width, height=800"),
+            None
+        );
+        assert_eq!(Viewport::from_meta_content("initial-scale=1"), None);
+    }
+
+    #[test]
+    fn should_read_viewport_of_svg_pages() {
+        let svg_page = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 1024 768">
+            <image width="1024" height="768" href="page.jpg"/>
+        </svg>"#;
+        let sized_svg_page =
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="512px" height="384px"/>"#;
+
+        assert_eq!(
+            Viewport::from_document(svg_page).unwrap(),
+            Some(Viewport {
+                width: 1024.0,
+                height: 768.0
+            })
+        );
+        assert_eq!(
+            Viewport::from_document(sized_svg_page).unwrap(),
+            Some(Viewport {
+                width: 512.0,
+                height: 384.0
+            })
+        );
+        assert_eq!(
+            Viewport::from_document("<html><body/></html>").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn should_scale_viewport_to_fit_the_area() {
+        let viewport = Viewport {
+            width: 1200.0,
+            height: 1800.0,
+        };
+
+        assert_eq!(viewport.scaled_to_fit(1000.0, 900.0), (600.0, 900.0));
+        assert_eq!(viewport.scaled_to_fit(300.0, 900.0), (300.0, 450.0));
+    }
+}
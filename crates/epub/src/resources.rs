@@ -0,0 +1,351 @@
+use zip::result::ZipError;
+
+use crate::{encryption::ResourceProtection, epub::EBook, error::EpubError};
+
+/// Location of a resource inside of the epub archive, resolved from an href found in the OPF, navigation or chapter documents
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResourcePath {
+    /// Full path of the archive entry, e.g. `OEBPS/Text/chapter_1.xhtml`
+    pub path: String,
+    /// Decoded fragment of the href without the `#`, e.g. `footnote_1`
+    pub fragment: Option<String>,
+}
+
+const FRAGMENT_SEPARATOR: char = '#';
+const QUERY_SEPARATOR: char = '?';
+const PATH_SEPARATOR: char = '/';
+
+impl ResourcePath {
+    /// Resolves `href` relative to the document placed at `base_document_path` in the archive.
+    /// Handles `./` and `../` segments, percent-encoding, fragments and absolute archive paths starting with `/`.
+    pub fn resolve(base_document_path: &str, href: &str) -> ResourcePath {
+        let resource =
+            ResourcePath::resolve_in_directory(parent_directory(base_document_path), href);
+
+        // Href consisting only of a fragment points to an element of the base document itself
+        if href.is_empty() || href.starts_with(FRAGMENT_SEPARATOR) {
+            return ResourcePath {
+                path: base_document_path.to_string(),
+                fragment: resource.fragment,
+            };
+        }
+
+        resource
+    }
+
+    /// Resolves `href` relative to the directory placed at `base_directory` in the archive
+    pub fn resolve_in_directory(base_directory: &str, href: &str) -> ResourcePath {
+        let (href, fragment) = match href.split_once(FRAGMENT_SEPARATOR) {
+            Some((href, fragment)) if !fragment.is_empty() => {
+                (href, Some(percent_decode(fragment)))
+            }
+            Some((href, _)) => (href, None),
+            None => (href, None),
+        };
+        let href = href.split(QUERY_SEPARATOR).next().unwrap_or_default();
+
+        let mut segments: Vec<String> = vec![];
+
+        if !href.starts_with(PATH_SEPARATOR) {
+            for segment in base_directory.split(PATH_SEPARATOR) {
+                push_segment(&mut segments, segment.to_string());
+            }
+        }
+
+        for segment in href.split(PATH_SEPARATOR) {
+            push_segment(&mut segments, percent_decode(segment));
+        }
+
+        ResourcePath {
+            path: segments.join("/"),
+            fragment,
+        }
+    }
+}
+
+/// Target of a link found in the content of the book
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// Document of the book, with the id of the target element in the fragment
+    Internal(ResourcePath),
+    /// Link pointing outside of the book, e.g. `https://example.com`, which should be opened by the browser
+    External(String),
+}
+
+impl LinkTarget {
+    /// Resolves `href` of a link found in the document placed at `base_document_path`
+    pub fn resolve(base_document_path: &str, href: &str) -> LinkTarget {
+        let href = href.trim();
+
+        if is_external_href(href) {
+            LinkTarget::External(href.to_string())
+        } else {
+            LinkTarget::Internal(ResourcePath::resolve(base_document_path, href))
+        }
+    }
+}
+
+/// Whether the href points outside of the book, e.g. `https://example.com` or `mailto:someone@example.com`
+pub fn is_external_href(href: &str) -> bool {
+    let scheme = match href.split_once(':') {
+        Some((scheme, _)) => scheme,
+        None => return false,
+    };
+
+    let mut characters = scheme.chars();
+    let starts_with_letter = characters
+        .next()
+        .map(|c| c.is_ascii_alphabetic())
+        .unwrap_or(false);
+
+    starts_with_letter && characters.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+}
+
+pub(crate) fn parent_directory(path: &str) -> &str {
+    match path.rsplit_once(PATH_SEPARATOR) {
+        Some((directory, _)) => directory,
+        None => "",
+    }
+}
+
+fn push_segment(segments: &mut Vec<String>, segment: String) {
+    match segment.as_str() {
+        "" | "." => {}
+        ".." => {
+            segments.pop();
+        }
+        _ => segments.push(segment),
+    }
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped_byte = match (bytes[index], bytes.get(index + 1..index + 3)) {
+            (b'%', Some(hex)) => std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match escaped_byte {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+impl EBook {
+    /// Path of the OPF file inside of the archive. Hrefs found in the OPF are relative to it.
+    pub fn opf_path(&self) -> &str {
+        &self.opf_path
+    }
+
+    /// Resolves `href` found in the document placed at `base_path`.
+    /// Use `opf_path` as the base for hrefs from the manifest, spine and guide.
+    pub fn resolve_href(&self, base_path: &str, href: &str) -> ResourcePath {
+        ResourcePath::resolve(base_path, href)
+    }
+
+    /// Reads bytes of the resource that `href` found in the document placed at `base_path` points to
+    pub fn read_resource(&self, base_path: &str, href: &str) -> Result<Vec<u8>, EpubError> {
+        let resource = self.resolve_href(base_path, href);
+
+        self.read_resource_at(&resource.path)
+    }
+
+    /// Reads bytes of the resource placed at the given, already resolved, path in the archive.
+    /// Obfuscated fonts are returned as they are stored, use `read_font` to restore them.
+    pub fn read_resource_at(&self, path: &str) -> Result<Vec<u8>, EpubError> {
+        if self.resource_protection(path) == ResourceProtection::DrmEncrypted {
+            return Err(EpubError::EncryptedResource(path.to_string()));
+        }
+
+        let mut archive = self.archive.lock().expect("Lock poisoned");
+
+        match EBook::get_archive_file_bytes(&mut archive, path) {
+            Err(EpubError::Zip(ZipError::FileNotFound)) => {
+                Err(EpubError::MissingResource(path.to_string()))
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod resources_tests {
+    use super::*;
+    use crate::test_utils::create_epub;
+
+    const MOBY_DICK_PATH: &str = "./test_data/epub/moby-dick.epub";
+
+    mod resolve {
+        use super::*;
+
+        #[test]
+        fn should_resolve_href_relative_to_document_directory() {
+            let resource = ResourcePath::resolve("OEBPS/content.opf", "Text/chapter_1.xhtml");
+
+            assert_eq!(resource.path, "OEBPS/Text/chapter_1.xhtml");
+            assert_eq!(resource.fragment, None);
+        }
+
+        #[test]
+        fn should_resolve_parent_and_current_directory_segments() {
+            let resource =
+                ResourcePath::resolve("OEBPS/Text/chapter_1.xhtml", "./../Styles/./main.css");
+
+            assert_eq!(resource.path, "OEBPS/Styles/main.css");
+        }
+
+        #[test]
+        fn should_not_go_above_archive_root() {
+            let resource = ResourcePath::resolve("content.opf", "../../Text/chapter_1.xhtml");
+
+            assert_eq!(resource.path, "Text/chapter_1.xhtml");
+        }
+
+        #[test]
+        fn should_split_fragment_from_path() {
+            let resource =
+                ResourcePath::resolve("OEBPS/Text/chapter_1.xhtml", "chapter_2.xhtml#note%201");
+
+            assert_eq!(resource.path, "OEBPS/Text/chapter_2.xhtml");
+            assert_eq!(resource.fragment, Some("note 1".to_string()));
+        }
+
+        #[test]
+        fn should_point_to_the_same_document_when_href_is_only_a_fragment() {
+            let resource = ResourcePath::resolve("OEBPS/Text/chapter_1.xhtml", "#note_1");
+
+            assert_eq!(resource.path, "OEBPS/Text/chapter_1.xhtml");
+            assert_eq!(resource.fragment, Some("note_1".to_string()));
+        }
+
+        #[test]
+        fn should_decode_percent_encoded_characters() {
+            let resource =
+                ResourcePath::resolve("OEBPS/content.opf", "Text/Rozdzia%C5%82%201.xhtml");
+
+            assert_eq!(resource.path, "OEBPS/Text/Rozdział 1.xhtml");
+        }
+
+        #[test]
+        fn should_leave_invalid_percent_sequences_untouched() {
+            let resource = ResourcePath::resolve("OEBPS/content.opf", "Text/100%.xhtml");
+
+            assert_eq!(resource.path, "OEBPS/Text/100%.xhtml");
+        }
+
+        #[test]
+        fn should_treat_href_starting_with_slash_as_absolute_archive_path() {
+            let resource = ResourcePath::resolve("OEBPS/Text/chapter_1.xhtml", "/Images/map.png");
+
+            assert_eq!(resource.path, "Images/map.png");
+        }
+
+        #[test]
+        fn should_detect_external_hrefs() {
+            assert!(is_external_href("https://example.com/book"));
+            assert!(is_external_href("mailto:someone@example.com"));
+            assert!(!is_external_href("chapter_1.xhtml#note:1"));
+            assert!(!is_external_href("../Text/chapter_1.xhtml"));
+            assert!(!is_external_href("#note_1"));
+        }
+
+        #[test]
+        fn should_resolve_link_targets() {
+            let base = "OEBPS/Text/chapter_1.xhtml";
+
+            assert_eq!(
+                LinkTarget::resolve(base, "chapter_12.xhtml#note3"),
+                LinkTarget::Internal(ResourcePath {
+                    path: "OEBPS/Text/chapter_12.xhtml".to_string(),
+                    fragment: Some("note3".to_string()),
+                })
+            );
+            assert_eq!(
+                LinkTarget::resolve(base, "#note3"),
+                LinkTarget::Internal(ResourcePath {
+                    path: base.to_string(),
+                    fragment: Some("note3".to_string()),
+                })
+            );
+            assert_eq!(
+                LinkTarget::resolve(base, " https://example.com/book "),
+                LinkTarget::External("https://example.com/book".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn read_resource_should_read_stylesheet_relative_to_chapter() {
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+
+        let stylesheet = book
+            .read_resource("OPS/chapter_001.xhtml", "css/stylesheet.css")
+            .unwrap();
+
+        assert!(!stylesheet.is_empty());
+    }
+
+    #[test]
+    fn read_resource_should_read_resources_from_subdirectories() {
+        let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+            <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Paths</dc:title></metadata>
+            <manifest>
+                <item id="toc" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+                <item id="chapter" href="Text/Rozdzia%C5%82%201.xhtml" media-type="application/xhtml+xml"/>
+                <item id="style" href="Styles/main.css" media-type="text/css"/>
+            </manifest>
+            <spine toc="toc"><itemref idref="chapter"/></spine>
+        </package>"#;
+        let toc = r#"<ncx><navMap>
+            <navPoint id="n1" playOrder="1"><navLabel><text>1</text></navLabel><content src="Text/Rozdzia%C5%82%201.xhtml#start"/></navPoint>
+        </navMap></ncx>"#;
+        let path = create_epub(
+            "lore_leaf_resources_subdirectories.epub",
+            opf,
+            &[
+                ("toc.ncx", toc.as_bytes()),
+                ("Text/Rozdział 1.xhtml", b"<html/>"),
+                ("Styles/main.css", b"p { margin: 0 }"),
+            ],
+        );
+        let book = EBook::read_epub(path).unwrap();
+
+        let chapter_path = &book.table_of_contents.items[0].path;
+        let stylesheet = book
+            .read_resource(chapter_path, "../Styles/main.css")
+            .unwrap();
+        let chapter = book.read_resource(book.opf_path(), &book.manifest.items[1].href);
+
+        assert_eq!(chapter_path, "OEBPS/Text/Rozdział 1.xhtml");
+        assert_eq!(stylesheet, b"p { margin: 0 }");
+        assert_eq!(chapter.unwrap(), b"<html/>");
+    }
+
+    #[test]
+    fn read_resource_should_return_missing_resource_error_when_file_is_not_in_archive() {
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+
+        let result = book.read_resource("OPS/chapter_001.xhtml", "../missing.css");
+
+        match result {
+            Err(EpubError::MissingResource(path)) => assert_eq!(path, "missing.css"),
+            _ => panic!("Expected missing resource error"),
+        }
+    }
+}
@@ -1,63 +1,226 @@
-use std::{rc::Rc, sync::Arc};
+use std::sync::Arc;
 
 use quick_xml::{events::Event, name::QName, Reader};
 
-use crate::{BookManifest, ManifestItem};
+use crate::{error::EpubError, BookManifest, ManifestItem};
 
 #[derive(Debug)]
 pub struct BookSpine {
     pub items: Vec<BookSpineItem>,
+    /// Id of the NCX manifest item from the `toc` attribute, used by epub 2 books
+    pub toc: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct BookSpineItem {
     pub id: String,
+    /// Id of the `<itemref>` itself, asserted by the spine step of EPUB CFIs
+    pub itemref_id: Option<String>,
     pub value: Arc<ManifestItem>,
+    /// False for `linear="no"` items, e.g. cover or pop-up notes, which are not part of the default reading order
+    pub linear: bool,
+    /// Values of the `properties` attribute, e.g. `page-spread-left` or `rendition:layout-pre-paginated`
+    pub properties: Vec<String>,
 }
 
 impl BookSpine {
-    pub fn from_opf_and_manifest(opf_content: &str, manifest: &BookManifest) -> BookSpine {
+    pub fn from_opf_and_manifest(
+        opf_content: &str,
+        manifest: &BookManifest,
+    ) -> Result<BookSpine, EpubError> {
         let mut reader = Reader::from_str(opf_content);
         reader.trim_text(true);
 
         let mut buf = Vec::new();
         let mut spine: Vec<BookSpineItem> = Vec::new();
+        let mut toc: Option<String> = None;
 
-        while let Ok(event) = reader.read_event_into(&mut buf) {
-            match event {
-                Event::Start(ref e) | Event::Empty(ref e) => {
-                    // current_tag = String::from_utf8(e.name().as_ref().to_vec()).unwrap();
-                    if let b"itemref" = e.name().as_ref() {
-                        BookSpine::recreate_spine_item(e, &mut spine, manifest);
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(ref e) | Event::Empty(ref e) => match e.name().as_ref() {
+                    b"itemref" => BookSpine::recreate_spine_item(e, &mut spine, manifest)?,
+                    b"spine" => {
+                        for attribute in e.attributes() {
+                            let attr = attribute?;
+                            if attr.key == QName(b"toc") {
+                                toc = Some(attr.unescape_value()?.to_string());
+                            }
+                        }
                     }
-                }
+                    _ => {}
+                },
                 Event::Eof => break,
                 _ => {}
             }
             buf.clear();
         }
 
-        BookSpine { items: spine }
+        Ok(BookSpine { items: spine, toc })
+    }
+
+    /// Position of the first item after `position` that is part of the reading order.
+    /// Non-linear items are skipped unless `include_non_linear` is set.
+    pub fn next_position(&self, position: usize, include_non_linear: bool) -> Option<usize> {
+        (position + 1..self.items.len())
+            .find(|index| include_non_linear || self.items[*index].linear)
+    }
+
+    /// Position of the last item before `position` that is part of the reading order.
+    /// Non-linear items are skipped unless `include_non_linear` is set.
+    pub fn previous_position(&self, position: usize, include_non_linear: bool) -> Option<usize> {
+        (0..position.min(self.items.len()))
+            .rev()
+            .find(|index| include_non_linear || self.items[*index].linear)
     }
 
     fn recreate_spine_item(
         e: &quick_xml::events::BytesStart<'_>,
         spine: &mut Vec<BookSpineItem>,
         manifest: &BookManifest,
-    ) {
+    ) -> Result<(), EpubError> {
+        let mut item_id: Option<String> = None;
+        let mut itemref_id: Option<String> = None;
+        let mut linear = true;
+        let mut properties: Vec<String> = vec![];
+
         for attribute in e.attributes() {
-            let attr = attribute.unwrap();
-            if attr.key == QName(b"idref") {
-                let item_id = String::from_utf8(attr.value.into_owned()).unwrap();
-                let item = manifest.items.iter().find(|i| i.id == item_id).unwrap();
-
-                let spine_item = BookSpineItem {
-                    id: item_id,
-                    value: Arc::clone(item),
-                };
-                spine.push(spine_item);
-                break;
+            let attr = attribute?;
+            match attr.key {
+                QName(b"idref") => item_id = Some(String::from_utf8(attr.value.into_owned())?),
+                QName(b"id") => itemref_id = Some(String::from_utf8(attr.value.into_owned())?),
+                QName(b"linear") => linear = attr.value.as_ref() != b"no",
+                QName(b"properties") => {
+                    properties = attr
+                        .unescape_value()?
+                        .split_whitespace()
+                        .map(|property| property.to_string())
+                        .collect()
+                }
+                _ => {}
             }
         }
+
+        let item_id = match item_id {
+            Some(item_id) => item_id,
+            None => return Ok(()),
+        };
+
+        let item = match manifest.find_by_id(&item_id) {
+            Some(item) => item,
+            None => return Err(EpubError::DanglingSpineReference(item_id)),
+        };
+
+        spine.push(BookSpineItem {
+            id: item_id,
+            itemref_id,
+            value: item,
+            linear,
+            properties,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod spine_tests {
+    use super::*;
+
+    #[test]
+    fn from_opf_and_manifest_should_return_error_when_spine_references_missing_item() {
+        //arrange
+        const OPF_CONTENT: &str = r#"
+        <package version="3.0">
+            <manifest>
+                <item id="chapter_1" href="chapter_1.xhtml" media-type="application/xhtml+xml"/>
+            </manifest>
+            <spine>
+                <itemref idref="chapter_1"/>
+                <itemref idref="chapter_2"/>
+            </spine>
+        </package>
+        "#;
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+
+        //act
+        let spine = BookSpine::from_opf_and_manifest(OPF_CONTENT, &manifest);
+
+        //assert
+        match spine {
+            Err(EpubError::DanglingSpineReference(idref)) => assert_eq!(idref, "chapter_2"),
+            _ => panic!("Expected dangling spine reference error"),
+        }
+    }
+
+    const OPF_CONTENT: &str = r#"
+    <package version="3.0">
+        <manifest>
+            <item id="cover" href="cover.xhtml" media-type="application/xhtml+xml"/>
+            <item id="chapter_1" href="chapter_1.xhtml" media-type="application/xhtml+xml"/>
+            <item id="notes" href="notes.xhtml" media-type="application/xhtml+xml"/>
+            <item id="chapter_2" href="chapter_2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine toc="ncx">
+            <itemref idref="cover" linear="no"/>
+            <itemref linear="yes" idref="chapter_1"/>
+            <itemref idref="notes" linear="no"/>
+            <itemref idref="chapter_2" properties="page-spread-right rendition:layout-pre-paginated"/>
+        </spine>
+    </package>
+    "#;
+
+    #[test]
+    fn from_opf_and_manifest_should_read_item_properties() {
+        //arrange
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+
+        //act
+        let spine = BookSpine::from_opf_and_manifest(OPF_CONTENT, &manifest).unwrap();
+
+        //assert
+        assert!(spine.items[0].properties.is_empty());
+        assert_eq!(
+            spine.items[3].properties,
+            vec!["page-spread-right", "rendition:layout-pre-paginated"]
+        );
+    }
+
+    #[test]
+    fn from_opf_and_manifest_should_read_linear_attribute() {
+        //arrange
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+
+        //act
+        let spine = BookSpine::from_opf_and_manifest(OPF_CONTENT, &manifest).unwrap();
+
+        //assert
+        let linear: Vec<bool> = spine.items.iter().map(|item| item.linear).collect();
+        assert_eq!(linear, vec![false, true, false, true]);
+        assert_eq!(spine.toc, Some("ncx".to_string()));
+        assert_eq!(spine.items[1].id, "chapter_1");
+    }
+
+    #[test]
+    fn next_and_previous_position_should_skip_non_linear_items() {
+        //arrange
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+        let spine = BookSpine::from_opf_and_manifest(OPF_CONTENT, &manifest).unwrap();
+
+        //act & assert
+        assert_eq!(spine.next_position(1, false), Some(3));
+        assert_eq!(spine.next_position(3, false), None);
+        assert_eq!(spine.previous_position(3, false), Some(1));
+        assert_eq!(spine.previous_position(1, false), None);
+    }
+
+    #[test]
+    fn next_and_previous_position_should_include_non_linear_items_when_requested() {
+        //arrange
+        let manifest = BookManifest::from_opf(OPF_CONTENT).unwrap();
+        let spine = BookSpine::from_opf_and_manifest(OPF_CONTENT, &manifest).unwrap();
+
+        //act & assert
+        assert_eq!(spine.next_position(1, true), Some(2));
+        assert_eq!(spine.previous_position(1, true), Some(0));
     }
 }
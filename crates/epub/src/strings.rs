@@ -1 +1 @@
-pub const EMTPY_STRING_SLICE: &'static str = "";
\ No newline at end of file
+pub const EMTPY_STRING_SLICE: &'static str = "";
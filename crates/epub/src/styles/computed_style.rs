@@ -0,0 +1,532 @@
+use super::stylesheet::Declaration;
+
+/// Values of the supported CSS properties resolved for a single node
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedStyle {
+    pub display: Display,
+    /// Numeric weight, `400` for `normal` and `700` for `bold`
+    pub font_weight: u16,
+    pub font_style: FontStyle,
+    /// Font families in the order of preference, including generic ones such as `serif`
+    pub font_family: Vec<String>,
+    pub small_caps: bool,
+    pub text_align: TextAlign,
+    pub text_indent: Length,
+    pub margin: Margin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Display {
+    Block,
+    Inline,
+    ListItem,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAlign {
+    Start,
+    End,
+    Left,
+    Right,
+    Center,
+    Justify,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Px(f32),
+    Em(f32),
+    Rem(f32),
+    Percent(f32),
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Margin {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+}
+
+const NORMAL_FONT_WEIGHT: u16 = 400;
+const BOLD_FONT_WEIGHT: u16 = 700;
+
+const INLINE_TAGS: [&str; 27] = [
+    "a", "abbr", "b", "bdi", "bdo", "big", "br", "cite", "code", "dfn", "em", "font", "i", "img",
+    "kbd", "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup", "time", "u", "var",
+];
+const HIDDEN_TAGS: [&str; 7] = [
+    "head", "link", "meta", "script", "style", "template", "title",
+];
+const BOLD_TAGS: [&str; 9] = ["b", "strong", "th", "h1", "h2", "h3", "h4", "h5", "h6"];
+const ITALIC_TAGS: [&str; 6] = ["address", "cite", "dfn", "em", "i", "var"];
+
+impl Default for ComputedStyle {
+    fn default() -> Self {
+        ComputedStyle {
+            display: Display::Block,
+            font_weight: NORMAL_FONT_WEIGHT,
+            font_style: FontStyle::Normal,
+            font_family: vec![],
+            small_caps: false,
+            text_align: TextAlign::Start,
+            text_indent: Length::Px(0.0),
+            margin: Margin::default(),
+        }
+    }
+}
+
+impl Default for Margin {
+    fn default() -> Self {
+        Margin {
+            top: Length::Px(0.0),
+            right: Length::Px(0.0),
+            bottom: Length::Px(0.0),
+            left: Length::Px(0.0),
+        }
+    }
+}
+
+impl ComputedStyle {
+    /// Style of the element before any stylesheet is applied, inherited properties are taken from the parent
+    /// and the defaults of the browsers are used for the rest, e.g. `strong` is bold and `span` is inline
+    pub fn for_element(tag: &str, parent: &ComputedStyle) -> ComputedStyle {
+        let tag = tag.rsplit(':').next().unwrap_or(tag).to_ascii_lowercase();
+        let tag = tag.as_str();
+
+        let display = if HIDDEN_TAGS.contains(&tag) {
+            Display::None
+        } else if INLINE_TAGS.contains(&tag) {
+            Display::Inline
+        } else if tag == "li" {
+            Display::ListItem
+        } else {
+            Display::Block
+        };
+
+        let font_weight = if BOLD_TAGS.contains(&tag) {
+            parent.font_weight.max(BOLD_FONT_WEIGHT)
+        } else {
+            parent.font_weight
+        };
+
+        let font_style = if ITALIC_TAGS.contains(&tag) {
+            FontStyle::Italic
+        } else {
+            parent.font_style
+        };
+
+        let text_align = if tag == "center" || tag == "th" {
+            TextAlign::Center
+        } else {
+            parent.text_align
+        };
+
+        ComputedStyle {
+            display,
+            font_weight,
+            font_style,
+            font_family: parent.font_family.clone(),
+            small_caps: parent.small_caps,
+            text_align,
+            text_indent: parent.text_indent,
+            margin: Margin::default(),
+        }
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.display == Display::None
+    }
+
+    pub fn is_bold(&self) -> bool {
+        self.font_weight >= 600
+    }
+
+    pub fn is_italic(&self) -> bool {
+        self.font_style != FontStyle::Normal
+    }
+
+    /// Applies a single declaration, invalid and unsupported values are ignored
+    pub fn apply(&mut self, declaration: &Declaration, parent: &ComputedStyle) {
+        let value = declaration.value.trim().to_ascii_lowercase();
+        let value = value.as_str();
+        let initial = ComputedStyle::default();
+
+        // `unset` behaves as `inherit` for the inherited properties and as `initial` for the rest
+        let inherits = !matches!(
+            declaration.property.as_str(),
+            "display" | "margin" | "margin-top" | "margin-right" | "margin-bottom" | "margin-left"
+        );
+        let source = match value {
+            "inherit" => Some(parent),
+            "unset" if inherits => Some(parent),
+            "initial" | "unset" => Some(&initial),
+            _ => None,
+        };
+
+        if let Some(source) = source {
+            self.copy_property(&declaration.property, source);
+            return;
+        }
+
+        match declaration.property.as_str() {
+            "display" => {
+                self.display = match value {
+                    "none" => Display::None,
+                    "inline" | "inline-block" | "inline-flex" | "inline-table" => Display::Inline,
+                    "list-item" => Display::ListItem,
+                    _ => Display::Block,
+                }
+            }
+            "font-weight" => {
+                if let Some(weight) = parse_font_weight(value, parent.font_weight) {
+                    self.font_weight = weight;
+                }
+            }
+            "font-style" => {
+                if let Some(font_style) = parse_font_style(value) {
+                    self.font_style = font_style;
+                }
+            }
+            "font-variant" | "font-variant-caps" => {
+                self.small_caps = value.contains("small-caps");
+            }
+            // Family names are case insensitive, so the lowercase value can be used
+            "font-family" => self.font_family = parse_font_family(value),
+            "font" => self.apply_font_shorthand(value, parent),
+            "text-align" => {
+                if let Some(text_align) = parse_text_align(value) {
+                    self.text_align = text_align;
+                }
+            }
+            "text-indent" => {
+                if let Some(length) = Length::parse(value) {
+                    self.text_indent = length;
+                }
+            }
+            "margin" => {
+                let lengths: Option<Vec<Length>> =
+                    value.split_whitespace().map(Length::parse).collect();
+
+                self.margin = match lengths.as_deref() {
+                    Some([all]) => Margin::new(*all, *all, *all, *all),
+                    Some([vertical, horizontal]) => {
+                        Margin::new(*vertical, *horizontal, *vertical, *horizontal)
+                    }
+                    Some([top, horizontal, bottom]) => {
+                        Margin::new(*top, *horizontal, *bottom, *horizontal)
+                    }
+                    Some([top, right, bottom, left]) => Margin::new(*top, *right, *bottom, *left),
+                    _ => self.margin,
+                };
+            }
+            "margin-top" | "margin-right" | "margin-bottom" | "margin-left" => {
+                if let Some(length) = Length::parse(value) {
+                    match declaration.property.as_str() {
+                        "margin-top" => self.margin.top = length,
+                        "margin-right" => self.margin.right = length,
+                        "margin-bottom" => self.margin.bottom = length,
+                        _ => self.margin.left = length,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `font: italic small-caps bold 1em/1.2 serif`, the properties not given in the shorthand are reset
+    fn apply_font_shorthand(&mut self, value: &str, parent: &ComputedStyle) {
+        // System fonts, e.g. `font: caption`, can not be resolved
+        if !value.contains(char::is_whitespace) {
+            return;
+        }
+
+        let mut font_weight = NORMAL_FONT_WEIGHT;
+        let mut font_style = FontStyle::Normal;
+        let mut small_caps = false;
+        let mut tokens = value.split_whitespace();
+
+        for token in tokens.by_ref() {
+            if token == "normal" {
+                continue;
+            } else if token == "small-caps" {
+                small_caps = true;
+            } else if let Some(style) = parse_font_style(token) {
+                font_style = style;
+            } else if let Some(weight) = parse_font_weight(token, parent.font_weight) {
+                font_weight = weight;
+            } else {
+                // The size, optionally followed by the line height, is the last token before the families
+                break;
+            }
+        }
+
+        self.font_weight = font_weight;
+        self.font_style = font_style;
+        self.small_caps = small_caps;
+        self.font_family = parse_font_family(&tokens.collect::<Vec<&str>>().join(" "));
+    }
+
+    fn copy_property(&mut self, property: &str, source: &ComputedStyle) {
+        match property {
+            "display" => self.display = source.display,
+            "font-weight" => self.font_weight = source.font_weight,
+            "font-style" => self.font_style = source.font_style,
+            "font-variant" | "font-variant-caps" => self.small_caps = source.small_caps,
+            "font-family" => self.font_family = source.font_family.clone(),
+            "font" => {
+                self.font_weight = source.font_weight;
+                self.font_style = source.font_style;
+                self.font_family = source.font_family.clone();
+                self.small_caps = source.small_caps;
+            }
+            "text-align" => self.text_align = source.text_align,
+            "text-indent" => self.text_indent = source.text_indent,
+            "margin" => self.margin = source.margin,
+            "margin-top" => self.margin.top = source.margin.top,
+            "margin-right" => self.margin.right = source.margin.right,
+            "margin-bottom" => self.margin.bottom = source.margin.bottom,
+            "margin-left" => self.margin.left = source.margin.left,
+            _ => {}
+        }
+    }
+}
+
+impl Margin {
+    pub fn new(top: Length, right: Length, bottom: Length, left: Length) -> Margin {
+        Margin {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+}
+
+impl Length {
+    /// Parses lengths such as `1.5em`, `12pt`, `10%` or `0`, absolute units are converted to pixels
+    pub fn parse(value: &str) -> Option<Length> {
+        let value = value.trim().to_ascii_lowercase();
+
+        if value == "auto" {
+            return Some(Length::Auto);
+        }
+
+        let unit_start = value
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(value.len());
+        let number: f32 = value[..unit_start].parse().ok()?;
+
+        let length = match &value[unit_start..] {
+            "" if number == 0.0 => Length::Px(0.0),
+            "px" => Length::Px(number),
+            "pt" => Length::Px(number * 96.0 / 72.0),
+            "pc" => Length::Px(number * 16.0),
+            "in" => Length::Px(number * 96.0),
+            "cm" => Length::Px(number * 96.0 / 2.54),
+            "mm" => Length::Px(number * 96.0 / 25.4),
+            "em" => Length::Em(number),
+            "ex" => Length::Em(number / 2.0),
+            "rem" => Length::Rem(number),
+            "%" => Length::Percent(number),
+            _ => return None,
+        };
+
+        Some(length)
+    }
+
+    /// Length in pixels for the given font size and width of the containing block, `auto` is treated as zero
+    pub fn to_px(&self, font_size: f32, root_font_size: f32, container_width: f32) -> f32 {
+        match self {
+            Length::Px(px) => *px,
+            Length::Em(em) => em * font_size,
+            Length::Rem(rem) => rem * root_font_size,
+            Length::Percent(percent) => percent / 100.0 * container_width,
+            Length::Auto => 0.0,
+        }
+    }
+}
+
+fn parse_font_weight(value: &str, parent_weight: u16) -> Option<u16> {
+    match value {
+        "normal" => Some(NORMAL_FONT_WEIGHT),
+        "bold" => Some(BOLD_FONT_WEIGHT),
+        "bolder" => Some(match parent_weight {
+            0..=349 => 400,
+            350..=549 => 700,
+            _ => 900,
+        }),
+        "lighter" => Some(match parent_weight {
+            0..=549 => 100,
+            550..=749 => 400,
+            _ => 700,
+        }),
+        _ => value
+            .parse::<u16>()
+            .ok()
+            .filter(|weight| (1..=1000).contains(weight)),
+    }
+}
+
+fn parse_font_family(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|family| family.trim().trim_matches(['"', '\'']).trim().to_string())
+        .filter(|family| !family.is_empty())
+        .collect()
+}
+
+fn parse_font_style(value: &str) -> Option<FontStyle> {
+    match value.split_whitespace().next()? {
+        "normal" => Some(FontStyle::Normal),
+        "italic" => Some(FontStyle::Italic),
+        "oblique" => Some(FontStyle::Oblique),
+        _ => None,
+    }
+}
+
+fn parse_text_align(value: &str) -> Option<TextAlign> {
+    match value {
+        "start" => Some(TextAlign::Start),
+        "end" => Some(TextAlign::End),
+        "left" => Some(TextAlign::Left),
+        "right" => Some(TextAlign::Right),
+        "center" => Some(TextAlign::Center),
+        "justify" => Some(TextAlign::Justify),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod computed_style_tests {
+    use super::*;
+
+    fn declaration(property: &str, value: &str) -> Declaration {
+        Declaration {
+            property: property.to_string(),
+            value: value.to_string(),
+            important: false,
+        }
+    }
+
+    #[test]
+    fn should_use_browser_defaults_for_elements() {
+        let root = ComputedStyle::default();
+
+        let strong = ComputedStyle::for_element("strong", &root);
+        let em = ComputedStyle::for_element("em", &root);
+        let style = ComputedStyle::for_element("style", &root);
+        let paragraph = ComputedStyle::for_element("p", &root);
+
+        assert!(strong.is_bold());
+        assert_eq!(strong.display, Display::Inline);
+        assert!(em.is_italic());
+        assert!(style.is_hidden());
+        assert_eq!(paragraph, ComputedStyle::default());
+    }
+
+    #[test]
+    fn should_inherit_only_inherited_properties() {
+        let mut parent = ComputedStyle::default();
+        parent.apply(
+            &declaration("text-align", "center"),
+            &ComputedStyle::default(),
+        );
+        parent.apply(&declaration("margin", "1em"), &ComputedStyle::default());
+        parent.apply(
+            &declaration("font-variant", "small-caps"),
+            &ComputedStyle::default(),
+        );
+        parent.apply(
+            &declaration("font-family", "\"Stix\", serif"),
+            &ComputedStyle::default(),
+        );
+
+        let child = ComputedStyle::for_element("span", &parent);
+
+        assert_eq!(child.text_align, TextAlign::Center);
+        assert!(child.small_caps);
+        assert_eq!(child.font_family, vec!["stix", "serif"]);
+        assert_eq!(child.margin, Margin::default());
+    }
+
+    #[test]
+    fn should_apply_margin_shorthand() {
+        let mut style = ComputedStyle::default();
+
+        style.apply(
+            &declaration("margin", "1em 5% 2px"),
+            &ComputedStyle::default(),
+        );
+
+        assert_eq!(
+            style.margin,
+            Margin::new(
+                Length::Em(1.0),
+                Length::Percent(5.0),
+                Length::Px(2.0),
+                Length::Percent(5.0)
+            )
+        );
+    }
+
+    #[test]
+    fn should_apply_font_properties() {
+        let parent = ComputedStyle::default();
+        let mut style = ComputedStyle::for_element("h1", &parent);
+
+        style.apply(
+            &declaration("font", "italic small-caps 1.2em serif"),
+            &parent,
+        );
+
+        assert_eq!(style.font_weight, 400);
+        assert_eq!(style.font_style, FontStyle::Italic);
+        assert!(style.small_caps);
+
+        style.apply(&declaration("font-weight", "bolder"), &parent);
+        style.apply(&declaration("font-style", "inherit"), &parent);
+
+        assert!(style.is_bold());
+        assert!(!style.is_italic());
+    }
+
+    #[test]
+    fn should_ignore_invalid_values() {
+        let mut style = ComputedStyle::default();
+
+        style.apply(
+            &declaration("text-indent", "1.5em"),
+            &ComputedStyle::default(),
+        );
+        style.apply(
+            &declaration("text-indent", "wide"),
+            &ComputedStyle::default(),
+        );
+        style.apply(
+            &declaration("font-weight", "heavy"),
+            &ComputedStyle::default(),
+        );
+
+        assert_eq!(style.text_indent, Length::Em(1.5));
+        assert_eq!(style.font_weight, 400);
+    }
+
+    #[test]
+    fn should_convert_lengths_to_pixels() {
+        assert_eq!(Length::parse("12pt"), Some(Length::Px(16.0)));
+        assert_eq!(Length::parse("0"), Some(Length::Px(0.0)));
+        assert_eq!(Length::parse("12"), None);
+        assert_eq!(Length::parse("2em").unwrap().to_px(16.0, 20.0, 500.0), 32.0);
+        assert_eq!(Length::parse("10%").unwrap().to_px(16.0, 20.0, 500.0), 50.0);
+    }
+}
@@ -0,0 +1,358 @@
+pub mod computed_style;
+pub mod selector;
+pub mod stylesheet;
+
+use std::sync::Arc;
+
+use crate::{
+    chapters::{chapter::Chapter, chapter_node::ChapterNode},
+    encoding::decode_document,
+    epub::EBook,
+    error::EpubError,
+    resources::{is_external_href, ResourcePath},
+};
+
+use self::{
+    computed_style::{ComputedStyle, Display},
+    selector::{Selector, Specificity},
+    stylesheet::{parse_declarations, Declaration, Stylesheet},
+};
+
+/// Style rules applying to a single chapter, collected from the linked stylesheets and `style` elements
+/// in the order they appear in the document
+#[derive(Debug, Clone, Default)]
+pub struct ChapterStyles {
+    rules: Vec<CascadedRule>,
+}
+
+#[derive(Debug, Clone)]
+struct CascadedRule {
+    selector: Selector,
+    specificity: Specificity,
+    declarations: Vec<Declaration>,
+}
+
+/// Position of the declaration in the cascade, declarations sorted by it are applied from the weakest one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct CascadeOrder {
+    important: bool,
+    inline: bool,
+    specificity: Specificity,
+    order: usize,
+}
+
+const STYLE_TAG: &str = "style";
+const STYLE_ATTRIBUTE: &str = "style";
+const LINK_TAG: &str = "link";
+const STYLESHEET_RELATION: &str = "stylesheet";
+
+impl ChapterStyles {
+    pub fn from_stylesheets(stylesheets: &[Stylesheet]) -> ChapterStyles {
+        let rules = stylesheets
+            .iter()
+            .flat_map(|stylesheet| stylesheet.rules.iter())
+            .flat_map(|rule| {
+                rule.selectors.iter().map(|selector| CascadedRule {
+                    selector: selector.clone(),
+                    specificity: selector.specificity(),
+                    declarations: rule.declarations.clone(),
+                })
+            })
+            .collect();
+
+        ChapterStyles { rules }
+    }
+
+    /// Computes the style of the node, resolving styles of all of its ancestors for the inherited properties.
+    /// Use `computed_style_with_parent` when walking down the tree to avoid resolving them again for each node.
+    pub fn computed_style(&self, node: &Arc<ChapterNode>) -> ComputedStyle {
+        let parent_style = match node.get_parent().upgrade() {
+            Some(parent) => self.computed_style(&parent),
+            None => ComputedStyle::default(),
+        };
+
+        self.computed_style_with_parent(node, &parent_style)
+    }
+
+    pub fn computed_style_with_parent(
+        &self,
+        node: &Arc<ChapterNode>,
+        parent_style: &ComputedStyle,
+    ) -> ComputedStyle {
+        let mut style = ComputedStyle::for_element(&node.tag, parent_style);
+
+        if node.attribute("hidden").is_some() {
+            style.display = Display::None;
+        }
+
+        let mut declarations: Vec<(CascadeOrder, &Declaration)> = vec![];
+
+        for (order, rule) in self.rules.iter().enumerate() {
+            if !rule.selector.matches(node) {
+                continue;
+            }
+
+            declarations.extend(rule.declarations.iter().map(|declaration| {
+                let cascade_order = CascadeOrder {
+                    important: declaration.important,
+                    inline: false,
+                    specificity: rule.specificity,
+                    order,
+                };
+                (cascade_order, declaration)
+            }));
+        }
+
+        let inline_declarations = node.attribute(STYLE_ATTRIBUTE).map(parse_declarations);
+
+        for declaration in inline_declarations.iter().flatten() {
+            let cascade_order = CascadeOrder {
+                important: declaration.important,
+                inline: true,
+                specificity: (0, 0, 0),
+                order: 0,
+            };
+            declarations.push((cascade_order, declaration));
+        }
+
+        declarations.sort_by_key(|(cascade_order, _)| *cascade_order);
+
+        for (_, declaration) in declarations {
+            style.apply(declaration, parent_style);
+        }
+
+        style
+    }
+}
+
+impl EBook {
+    /// Collects styles of the chapter from stylesheets linked in its head, including the ones imported
+    /// with `@import`, and from its `style` elements. Stylesheets missing from the archive are skipped.
+    pub fn chapter_styles(&self, chapter: &Chapter) -> Result<ChapterStyles, EpubError> {
+        let mut stylesheets: Vec<Stylesheet> = vec![];
+        let mut loaded_paths: Vec<String> = vec![];
+
+        self.collect_stylesheets(
+            &chapter.recreated_structure,
+            &chapter.path,
+            &mut stylesheets,
+            &mut loaded_paths,
+        )?;
+
+        Ok(ChapterStyles::from_stylesheets(&stylesheets))
+    }
+
+    fn collect_stylesheets(
+        &self,
+        node: &Arc<ChapterNode>,
+        chapter_path: &str,
+        stylesheets: &mut Vec<Stylesheet>,
+        loaded_paths: &mut Vec<String>,
+    ) -> Result<(), EpubError> {
+        match node.tag.as_str() {
+            STYLE_TAG => stylesheets.push(Stylesheet::parse(&node.text_content())),
+            LINK_TAG if is_stylesheet_link(node) => {
+                if let Some(href) = node.href().filter(|href| !is_external_href(href)) {
+                    let resource = ResourcePath::resolve(chapter_path, href);
+                    self.load_stylesheet(&resource.path, stylesheets, loaded_paths)?;
+                }
+            }
+            _ => {
+                for child in node.get_children() {
+                    self.collect_stylesheets(&child, chapter_path, stylesheets, loaded_paths)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads the stylesheet preceded by the ones it imports. Each stylesheet is loaded once, which also breaks import cycles.
+    fn load_stylesheet(
+        &self,
+        path: &str,
+        stylesheets: &mut Vec<Stylesheet>,
+        loaded_paths: &mut Vec<String>,
+    ) -> Result<(), EpubError> {
+        if loaded_paths.iter().any(|loaded_path| loaded_path == path) {
+            return Ok(());
+        }
+        loaded_paths.push(path.to_string());
+
+        let content = match self.read_resource_at(path) {
+            Ok(content) => decode_document(&content)?,
+            Err(EpubError::MissingResource(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let stylesheet = Stylesheet::parse(&content);
+
+        for import in stylesheet.imports.iter() {
+            if !is_external_href(import) {
+                let resource = ResourcePath::resolve(path, import);
+                self.load_stylesheet(&resource.path, stylesheets, loaded_paths)?;
+            }
+        }
+
+        stylesheets.push(stylesheet);
+
+        Ok(())
+    }
+}
+
+fn is_stylesheet_link(node: &ChapterNode) -> bool {
+    let relations = node
+        .attribute("rel")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let relations: Vec<&str> = relations.split_whitespace().collect();
+
+    relations.contains(&STYLESHEET_RELATION) && !relations.contains(&"alternate")
+}
+
+#[cfg(test)]
+mod styles_tests {
+    use super::{
+        computed_style::{FontStyle, Length, TextAlign},
+        *,
+    };
+    use crate::test_utils::{chapter_from_content, create_epub};
+
+    const MOBY_DICK_PATH: &str = "./test_data/epub/moby-dick.epub";
+
+    fn find_node(node: &Arc<ChapterNode>, tag: &str) -> Option<Arc<ChapterNode>> {
+        if node.tag == tag {
+            return Some(Arc::clone(node));
+        }
+
+        node.get_children()
+            .iter()
+            .find_map(|child| find_node(child, tag))
+    }
+
+    #[test]
+    fn should_apply_rules_by_specificity_and_order() {
+        let stylesheet = Stylesheet::parse(
+            r#"
+            p.first { text-align: right }
+            p { text-align: center; font-weight: bold !important }
+            .first { text-align: left; font-weight: normal }
+            p { text-align: justify }
+            "#,
+        );
+        let chapter = chapter_from_content(
+            "OEBPS/chapter.xhtml",
+            r#"<html><body><p class="first">Text</p></body></html>"#,
+        );
+        let paragraph = find_node(&chapter.recreated_structure, "p").unwrap();
+
+        let style = ChapterStyles::from_stylesheets(&[stylesheet]).computed_style(&paragraph);
+
+        assert_eq!(style.text_align, TextAlign::Right);
+        assert!(style.is_bold());
+    }
+
+    #[test]
+    fn should_apply_style_attribute_over_stylesheets() {
+        let stylesheet =
+            Stylesheet::parse("#note { display: none; font-style: italic !important }");
+        let chapter = chapter_from_content(
+            "OEBPS/chapter.xhtml",
+            r#"<html><body><aside id="note" style="display: block; font-style: normal">Note</aside></body></html>"#,
+        );
+        let aside = find_node(&chapter.recreated_structure, "aside").unwrap();
+
+        let style = ChapterStyles::from_stylesheets(&[stylesheet]).computed_style(&aside);
+
+        assert!(!style.is_hidden());
+        assert_eq!(style.font_style, FontStyle::Italic);
+    }
+
+    #[test]
+    fn should_inherit_styles_of_ancestors() {
+        let stylesheet = Stylesheet::parse(".centered { text-align: center; margin-left: 2em }");
+        let chapter = chapter_from_content(
+            "OEBPS/chapter.xhtml",
+            r#"<html><body><div class="centered"><p>Text <em>emphasis</em></p></div></body></html>"#,
+        );
+        let em = find_node(&chapter.recreated_structure, "em").unwrap();
+
+        let style = ChapterStyles::from_stylesheets(&[stylesheet]).computed_style(&em);
+
+        assert_eq!(style.text_align, TextAlign::Center);
+        assert!(style.is_italic());
+        assert_eq!(style.margin.left, Length::Px(0.0));
+    }
+
+    #[test]
+    fn chapter_styles_should_load_linked_imported_and_inline_stylesheets() {
+        let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+            <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Styles</dc:title></metadata>
+            <manifest>
+                <item id="chapter" href="Text/chapter.xhtml" media-type="application/xhtml+xml"/>
+                <item id="style" href="Styles/main.css" media-type="text/css"/>
+                <item id="base" href="Styles/base.css" media-type="text/css"/>
+            </manifest>
+            <spine><itemref idref="chapter"/></spine>
+        </package>"#;
+        let chapter_content = r#"<html xmlns="http://www.w3.org/1999/xhtml">
+            <head>
+                <link rel="stylesheet" type="text/css" href="../Styles/main.css"/>
+                <link rel="stylesheet" type="text/css" href="../Styles/missing.css"/>
+                <link rel="alternate stylesheet" type="text/css" href="../Styles/night.css"/>
+                <style>h1 { font-variant: small-caps }</style>
+            </head>
+            <body><h1 class="title">Title</h1><p class="TYTUL-R">Text</p></body>
+        </html>"#;
+        let path = create_epub(
+            "lore_leaf_styles_linked.epub",
+            opf,
+            &[
+                ("Text/chapter.xhtml", chapter_content.as_bytes()),
+                (
+                    "Styles/main.css",
+                    b"@import url(base.css); @import url(main.css); p.TYTUL-R { text-indent: 0 }",
+                ),
+                (
+                    "Styles/base.css",
+                    b"p { text-indent: 1em } h1 { text-align: center }",
+                ),
+                ("Styles/night.css", b"h1 { display: none }"),
+            ],
+        );
+        let book = EBook::read_epub(path).unwrap();
+        let chapter = chapter_from_content("OEBPS/Text/chapter.xhtml", chapter_content);
+        let h1 = find_node(&chapter.recreated_structure, "h1").unwrap();
+        let paragraph = find_node(&chapter.recreated_structure, "p").unwrap();
+        let head = find_node(&chapter.recreated_structure, "head").unwrap();
+
+        let styles = book.chapter_styles(&chapter).unwrap();
+
+        let h1_style = styles.computed_style(&h1);
+        assert_eq!(h1_style.text_align, TextAlign::Center);
+        assert!(h1_style.small_caps);
+        assert!(!h1_style.is_hidden());
+        assert_eq!(
+            styles.computed_style(&paragraph).text_indent,
+            Length::Px(0.0)
+        );
+        assert!(styles.computed_style(&head).is_hidden());
+    }
+
+    #[test]
+    fn chapter_styles_should_resolve_styles_of_moby_dick() {
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let item = book
+            .table_of_contents
+            .items
+            .iter()
+            .find(|item| item.path == "OPS/chapter_001.xhtml")
+            .unwrap();
+        let chapter = book.chapter(item).unwrap();
+        let h1 = find_node(&chapter.recreated_structure, "h1").unwrap();
+
+        let styles = book.chapter_styles(&chapter).unwrap();
+
+        assert_eq!(styles.computed_style(&h1).text_align, TextAlign::Center);
+    }
+}
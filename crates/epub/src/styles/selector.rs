@@ -0,0 +1,503 @@
+use std::sync::Arc;
+
+use crate::chapters::chapter_node::ChapterNode;
+
+/// CSS selector matched against the `ChapterNode` tree.
+/// Supports type, universal, class, id and attribute selectors, `:first-child`,
+/// `:last-child` and all four combinators. Other pseudo-classes and pseudo-elements are not supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    /// Compound selectors from the rightmost one, each with the combinator joining it to the next one on the left
+    compounds: Vec<(CompoundSelector, Option<Combinator>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combinator {
+    Descendant,
+    Child,
+    NextSibling,
+    SubsequentSibling,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct CompoundSelector {
+    tag: Option<String>,
+    ids: Vec<String>,
+    classes: Vec<String>,
+    attributes: Vec<AttributeSelector>,
+    pseudo_classes: Vec<PseudoClass>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AttributeSelector {
+    name: String,
+    operator: Option<(AttributeOperator, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AttributeOperator {
+    Equals,
+    Includes,
+    DashMatch,
+    Prefix,
+    Suffix,
+    Substring,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PseudoClass {
+    FirstChild,
+    LastChild,
+}
+
+/// Specificity as `(ids, classes, types)`, compared lexicographically
+pub type Specificity = (u32, u32, u32);
+
+impl Selector {
+    /// Returns `None` for invalid or unsupported selectors
+    pub fn parse(selector: &str) -> Option<Selector> {
+        let mut parser = SelectorParser {
+            chars: selector.trim().chars().collect(),
+            position: 0,
+        };
+        let mut compounds: Vec<(CompoundSelector, Option<Combinator>)> = vec![];
+        let mut combinator: Option<Combinator> = None;
+
+        loop {
+            let compound = parser.parse_compound()?;
+            compounds.push((compound, combinator));
+
+            let had_whitespace = parser.skip_whitespace();
+            if parser.is_at_end() {
+                break;
+            }
+
+            combinator = match parser.peek() {
+                Some('>') => Some(Combinator::Child),
+                Some('+') => Some(Combinator::NextSibling),
+                Some('~') => Some(Combinator::SubsequentSibling),
+                _ if had_whitespace => Some(Combinator::Descendant),
+                _ => return None,
+            };
+
+            if combinator != Some(Combinator::Descendant) {
+                parser.position += 1;
+                parser.skip_whitespace();
+            }
+        }
+
+        // Matching starts from the subject, the rightmost compound, and goes to the left
+        compounds.reverse();
+
+        Some(Selector { compounds })
+    }
+
+    pub fn specificity(&self) -> Specificity {
+        self.compounds
+            .iter()
+            .fold((0, 0, 0), |(ids, classes, types), (compound, _)| {
+                (
+                    ids + compound.ids.len() as u32,
+                    classes
+                        + (compound.classes.len()
+                            + compound.attributes.len()
+                            + compound.pseudo_classes.len()) as u32,
+                    types + compound.tag.is_some() as u32,
+                )
+            })
+    }
+
+    pub fn matches(&self, node: &Arc<ChapterNode>) -> bool {
+        Self::matches_from(&self.compounds, node)
+    }
+
+    fn matches_from(
+        compounds: &[(CompoundSelector, Option<Combinator>)],
+        node: &Arc<ChapterNode>,
+    ) -> bool {
+        let Some(((compound, combinator), rest)) = compounds.split_first() else {
+            return true;
+        };
+
+        if !compound.matches(node) {
+            return false;
+        }
+
+        match combinator {
+            None => true,
+            Some(Combinator::Child) => node
+                .get_parent()
+                .upgrade()
+                .is_some_and(|parent| Self::matches_from(rest, &parent)),
+            Some(Combinator::Descendant) => {
+                let mut ancestor = node.get_parent().upgrade();
+                while let Some(current) = ancestor {
+                    if Self::matches_from(rest, &current) {
+                        return true;
+                    }
+                    ancestor = current.get_parent().upgrade();
+                }
+                false
+            }
+            Some(Combinator::NextSibling) => previous_siblings(node)
+                .last()
+                .is_some_and(|sibling| Self::matches_from(rest, sibling)),
+            Some(Combinator::SubsequentSibling) => previous_siblings(node)
+                .iter()
+                .any(|sibling| Self::matches_from(rest, sibling)),
+        }
+    }
+}
+
+impl CompoundSelector {
+    fn matches(&self, node: &Arc<ChapterNode>) -> bool {
+        let tag_matches = self
+            .tag
+            .as_ref()
+            .is_none_or(|tag| local_name(&node.tag).eq_ignore_ascii_case(tag));
+
+        tag_matches
+            && self.ids.iter().all(|id| node.id() == Some(id.as_str()))
+            && self
+                .classes
+                .iter()
+                .all(|class| node.classes.contains(class))
+            && self
+                .attributes
+                .iter()
+                .all(|attribute| attribute.matches(node))
+            && self
+                .pseudo_classes
+                .iter()
+                .all(|pseudo_class| match pseudo_class {
+                    PseudoClass::FirstChild => previous_siblings(node).is_empty(),
+                    PseudoClass::LastChild => node.get_parent().upgrade().is_none_or(|parent| {
+                        parent
+                            .get_children()
+                            .last()
+                            .is_some_and(|last| Arc::ptr_eq(last, node))
+                    }),
+                })
+    }
+}
+
+impl AttributeSelector {
+    fn matches(&self, node: &ChapterNode) -> bool {
+        let value = match self.name.as_str() {
+            "class" if !node.classes.is_empty() => Some(node.classes.join(" ")),
+            name => node.attribute(name).map(|value| value.to_string()),
+        };
+
+        let Some(value) = value else {
+            return false;
+        };
+
+        match &self.operator {
+            None => true,
+            Some((AttributeOperator::Equals, expected)) => value == *expected,
+            Some((AttributeOperator::Includes, expected)) => {
+                value.split_whitespace().any(|part| part == expected)
+            }
+            Some((AttributeOperator::DashMatch, expected)) => {
+                value == *expected || value.starts_with(&format!("{}-", expected))
+            }
+            Some((AttributeOperator::Prefix, expected)) => {
+                !expected.is_empty() && value.starts_with(expected.as_str())
+            }
+            Some((AttributeOperator::Suffix, expected)) => {
+                !expected.is_empty() && value.ends_with(expected.as_str())
+            }
+            Some((AttributeOperator::Substring, expected)) => {
+                !expected.is_empty() && value.contains(expected.as_str())
+            }
+        }
+    }
+}
+
+/// Element siblings placed before the node, in the document order
+fn previous_siblings(node: &Arc<ChapterNode>) -> Vec<Arc<ChapterNode>> {
+    let Some(parent) = node.get_parent().upgrade() else {
+        return vec![];
+    };
+
+    parent
+        .get_children()
+        .into_iter()
+        .take_while(|sibling| !Arc::ptr_eq(sibling, node))
+        .collect()
+}
+
+/// Tag without the namespace prefix, e.g. `svg` for `svg:svg`
+fn local_name(tag: &str) -> &str {
+    tag.rsplit(':').next().unwrap_or(tag)
+}
+
+struct SelectorParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl SelectorParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.position >= self.chars.len()
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.position += 1;
+        }
+        self.position > start
+    }
+
+    fn parse_identifier(&mut self) -> Option<String> {
+        let mut identifier = String::new();
+
+        while let Some(c) = self.peek() {
+            if c == '\\' {
+                self.position += 1;
+                identifier.push(self.peek()?);
+            } else if c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii() {
+                identifier.push(c);
+            } else {
+                break;
+            }
+            self.position += 1;
+        }
+
+        if identifier.is_empty() {
+            None
+        } else {
+            Some(identifier)
+        }
+    }
+
+    fn parse_compound(&mut self) -> Option<CompoundSelector> {
+        let mut compound = CompoundSelector::default();
+        let start = self.position;
+
+        if self.peek() == Some('*') {
+            self.position += 1;
+        } else if self.peek().is_some_and(|c| c.is_alphabetic()) {
+            let tag = self.parse_identifier()?;
+            // Namespaced type selectors, e.g. `svg|svg`, are matched by the local name
+            compound.tag = Some(match self.peek() {
+                Some('|') => {
+                    self.position += 1;
+                    self.parse_identifier()?
+                }
+                _ => tag,
+            });
+        }
+
+        loop {
+            match self.peek() {
+                Some('.') => {
+                    self.position += 1;
+                    compound.classes.push(self.parse_identifier()?);
+                }
+                Some('#') => {
+                    self.position += 1;
+                    compound.ids.push(self.parse_identifier()?);
+                }
+                Some('[') => {
+                    self.position += 1;
+                    compound.attributes.push(self.parse_attribute()?);
+                }
+                Some(':') => {
+                    self.position += 1;
+                    let pseudo_class = match self.parse_identifier()?.to_ascii_lowercase().as_str()
+                    {
+                        "first-child" => PseudoClass::FirstChild,
+                        "last-child" => PseudoClass::LastChild,
+                        _ => return None,
+                    };
+                    compound.pseudo_classes.push(pseudo_class);
+                }
+                _ => break,
+            }
+        }
+
+        if self.position == start {
+            None
+        } else {
+            Some(compound)
+        }
+    }
+
+    fn parse_attribute(&mut self) -> Option<AttributeSelector> {
+        self.skip_whitespace();
+        let mut name = self.parse_identifier()?;
+        // `epub|type` in CSS stands for the `epub:type` attribute
+        if self.peek() == Some('|') && self.chars.get(self.position + 1) != Some(&'=') {
+            self.position += 1;
+            name = format!("{}:{}", name, self.parse_identifier()?);
+        }
+        self.skip_whitespace();
+
+        let operator = match self.peek()? {
+            ']' => {
+                self.position += 1;
+                return Some(AttributeSelector {
+                    name,
+                    operator: None,
+                });
+            }
+            '=' => AttributeOperator::Equals,
+            c => {
+                let operator = match c {
+                    '~' => AttributeOperator::Includes,
+                    '|' => AttributeOperator::DashMatch,
+                    '^' => AttributeOperator::Prefix,
+                    '$' => AttributeOperator::Suffix,
+                    '*' => AttributeOperator::Substring,
+                    _ => return None,
+                };
+                self.position += 1;
+                if self.peek() != Some('=') {
+                    return None;
+                }
+                operator
+            }
+        };
+        self.position += 1;
+        self.skip_whitespace();
+
+        let value = match self.peek()? {
+            quote @ ('"' | '\'') => {
+                self.position += 1;
+                let mut value = String::new();
+                while self.peek()? != quote {
+                    value.push(self.peek()?);
+                    self.position += 1;
+                }
+                self.position += 1;
+                value
+            }
+            _ => self.parse_identifier()?,
+        };
+
+        self.skip_whitespace();
+        if self.peek()? != ']' {
+            return None;
+        }
+        self.position += 1;
+
+        Some(AttributeSelector {
+            name,
+            operator: Some((operator, value)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod selector_tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn element(tag: &str, classes: &[&str], attributes: &[(&str, &str)]) -> Arc<ChapterNode> {
+        let attributes: HashMap<String, String> = attributes
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        Arc::new(
+            ChapterNode::new(
+                tag.to_string(),
+                classes.iter().map(|class| class.to_string()).collect(),
+                String::new(),
+            )
+            .with_attributes(attributes),
+        )
+    }
+
+    /// Builds `<body><div class="chapter"><h2 id="title"/><p class="first"/><p/></div></body>`
+    /// and returns all of the elements, the root has to be kept alive because parents are weak
+    fn create_tree() -> Vec<Arc<ChapterNode>> {
+        let body = element("body", &[], &[]);
+        let div = element("div", &["chapter"], &[("epub:type", "chapter bodymatter")]);
+        let h2 = element("h2", &[], &[("id", "title"), ("lang", "en-GB")]);
+        let first = element("p", &["first", "TYTUL-R"], &[]);
+        let second = element("p", &[], &[]);
+
+        ChapterNode::add_child(&body, &div);
+        ChapterNode::add_child(&div, &h2);
+        ChapterNode::add_child(&div, &first);
+        ChapterNode::add_child(&div, &second);
+
+        vec![body, div, h2, first, second]
+    }
+
+    fn matches(selector: &str, node: &Arc<ChapterNode>) -> bool {
+        Selector::parse(selector)
+            .unwrap_or_else(|| panic!("{} should be parsed", selector))
+            .matches(node)
+    }
+
+    #[test]
+    fn should_match_simple_selectors() {
+        let tree = create_tree();
+        let (h2, first) = (&tree[2], &tree[3]);
+
+        assert!(matches("p", first));
+        assert!(matches("P", first));
+        assert!(matches("*", first));
+        assert!(matches(".first", first));
+        assert!(matches("p.first.TYTUL-R", first));
+        assert!(matches("#title", h2));
+        assert!(!matches("p.second", first));
+        assert!(!matches("h2#other", h2));
+    }
+
+    #[test]
+    fn should_match_attribute_selectors() {
+        let tree = create_tree();
+        let (div, h2) = (&tree[1], &tree[2]);
+
+        assert!(matches("[id]", h2));
+        assert!(matches("[id=title]", h2));
+        assert!(matches("[lang|=en]", h2));
+        assert!(matches("[epub|type~=\"chapter\"]", div));
+        assert!(matches("[class^='chap']", div));
+        assert!(!matches("[epub|type~=\"chap\"]", div));
+    }
+
+    #[test]
+    fn should_match_combinators() {
+        let tree = create_tree();
+        let (h2, first, second) = (&tree[2], &tree[3], &tree[4]);
+
+        assert!(matches("body p", second));
+        assert!(matches("div.chapter > p", second));
+        assert!(matches("h2 + p", first));
+        assert!(!matches("h2 + p", second));
+        assert!(matches("h2 ~ p", second));
+        assert!(matches("h2:first-child", h2));
+        assert!(matches("p:last-child", second));
+        assert!(!matches("body > p", second));
+        assert!(!matches("p:first-child", first));
+    }
+
+    #[test]
+    fn should_reject_unsupported_selectors() {
+        assert_eq!(Selector::parse("a:hover"), None);
+        assert_eq!(Selector::parse("p::first-letter"), None);
+        assert_eq!(Selector::parse("p >"), None);
+        assert_eq!(Selector::parse(""), None);
+    }
+
+    #[test]
+    fn should_calculate_specificity() {
+        let specificity = |selector: &str| Selector::parse(selector).unwrap().specificity();
+
+        assert_eq!(specificity("*"), (0, 0, 0));
+        assert_eq!(specificity("div p"), (0, 0, 2));
+        assert_eq!(specificity("p.first[lang]"), (0, 2, 1));
+        assert_eq!(specificity("#title:first-child"), (1, 1, 0));
+    }
+}
@@ -0,0 +1,412 @@
+use super::{computed_style::FontStyle, selector::Selector};
+
+/// Parsed CSS stylesheet. Only the parts needed to display the chapters are kept,
+/// rules with unsupported selectors and at-rules other than `@media`, `@import` and `@font-face` are skipped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stylesheet {
+    pub rules: Vec<StyleRule>,
+    /// Urls of the stylesheets imported with `@import`, relative to this stylesheet
+    pub imports: Vec<String>,
+    pub font_faces: Vec<FontFace>,
+}
+
+/// Font declared with the `@font-face` rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    pub family: String,
+    pub font_weight: u16,
+    pub font_style: FontStyle,
+    /// Urls of the font files relative to the stylesheet, in the order of preference. Fonts installed
+    /// in the system, given with `local()`, are skipped.
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleRule {
+    pub selectors: Vec<Selector>,
+    pub declarations: Vec<Declaration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    /// Lowercase property name, e.g. `font-weight`
+    pub property: String,
+    pub value: String,
+    pub important: bool,
+}
+
+impl Stylesheet {
+    pub fn parse(css: &str) -> Stylesheet {
+        let mut stylesheet = Stylesheet::default();
+
+        stylesheet.parse_rules(&remove_comments(css));
+
+        stylesheet
+    }
+
+    fn parse_rules(&mut self, css: &str) {
+        let mut rest = css;
+
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+
+            let prelude_end = match find_outside_of_strings(rest, &['{', ';']) {
+                Some(index) => index,
+                None => break,
+            };
+            let prelude = rest[..prelude_end].trim();
+
+            // Statement at-rules, e.g. `@charset "utf-8";` or `@import url(main.css);`
+            if rest[prelude_end..].starts_with(';') {
+                if let Some(import) = prelude.strip_prefix("@import") {
+                    if let Some(url) = parse_url(import) {
+                        self.imports.push(url);
+                    }
+                }
+
+                rest = &rest[prelude_end + 1..];
+                continue;
+            }
+
+            let block_end = find_block_end(rest, prelude_end);
+            let block = &rest[prelude_end + 1..block_end];
+            rest = rest.get(block_end + 1..).unwrap_or_default();
+
+            if let Some(media_query) = prelude.strip_prefix("@media") {
+                if applies_to_screen(media_query) {
+                    self.parse_rules(block);
+                }
+                continue;
+            }
+
+            if prelude.eq_ignore_ascii_case("@font-face") {
+                if let Some(font_face) = FontFace::from_declarations(&parse_declarations(block)) {
+                    self.font_faces.push(font_face);
+                }
+                continue;
+            }
+
+            if prelude.starts_with('@') {
+                continue;
+            }
+
+            let selectors: Option<Vec<Selector>> = split_outside_of_parentheses(prelude, ',')
+                .into_iter()
+                .map(Selector::parse)
+                .collect();
+
+            // A single unsupported selector invalidates the whole rule, the same way browsers do it
+            if let Some(selectors) = selectors {
+                self.rules.push(StyleRule {
+                    selectors,
+                    declarations: parse_declarations(block),
+                });
+            }
+        }
+    }
+}
+
+impl FontFace {
+    fn from_declarations(declarations: &[Declaration]) -> Option<FontFace> {
+        let mut family: Option<String> = None;
+        let mut font_face = FontFace {
+            family: String::new(),
+            font_weight: 400,
+            font_style: FontStyle::Normal,
+            sources: vec![],
+        };
+
+        for declaration in declarations {
+            let value = declaration.value.as_str();
+
+            match declaration.property.as_str() {
+                "font-family" => family = Some(value.trim_matches(['"', '\'']).trim().to_string()),
+                // Variable fonts declare a range of weights, e.g. `100 900`, the first one is used
+                "font-weight" => {
+                    font_face.font_weight = match value.split_whitespace().next() {
+                        Some("bold") => 700,
+                        Some(weight) => weight.parse().unwrap_or(400),
+                        None => 400,
+                    }
+                }
+                "font-style" => {
+                    font_face.font_style = match value.to_ascii_lowercase().as_str() {
+                        "italic" => FontStyle::Italic,
+                        style if style.starts_with("oblique") => FontStyle::Oblique,
+                        _ => FontStyle::Normal,
+                    }
+                }
+                "src" => {
+                    font_face.sources = split_outside_of_parentheses(value, ',')
+                        .into_iter()
+                        .filter(|source| source.starts_with("url("))
+                        .filter_map(parse_url)
+                        .collect()
+                }
+                _ => {}
+            }
+        }
+
+        font_face.family = family.filter(|family| !family.is_empty())?;
+
+        Some(font_face)
+    }
+}
+
+/// Parses declarations of the rule block or of the `style` attribute, e.g. `font-weight: bold; margin: 0 !important`
+pub fn parse_declarations(block: &str) -> Vec<Declaration> {
+    split_outside_of_parentheses(block, ';')
+        .into_iter()
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let property = property.trim().to_ascii_lowercase();
+            let value = value.trim();
+
+            let (value, important) = match value.to_ascii_lowercase().rfind("!important") {
+                Some(index) => (value[..index].trim(), true),
+                None => (value, false),
+            };
+
+            if property.is_empty() || value.is_empty() {
+                return None;
+            }
+
+            Some(Declaration {
+                property,
+                value: value.to_string(),
+                important,
+            })
+        })
+        .collect()
+}
+
+fn remove_comments(css: &str) -> String {
+    let mut result = String::with_capacity(css.len());
+    let mut rest = css;
+
+    while let Some(start) = rest.find("/*") {
+        result.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Index of the `}` closing the block opened at `block_start`, or the end of the text when it is not closed
+fn find_block_end(css: &str, block_start: usize) -> usize {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+
+    for (index, c) in css[block_start..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return block_start + index;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    css.len()
+}
+
+fn find_outside_of_strings(css: &str, characters: &[char]) -> Option<usize> {
+    let mut quote: Option<char> = None;
+
+    for (index, c) in css.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, c) if characters.contains(&c) => return Some(index),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Splits on the separator, but not inside of `url(...)`, `:not(...)` or strings
+fn split_outside_of_parentheses(text: &str, separator: char) -> Vec<&str> {
+    let mut parts: Vec<&str> = vec![];
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, c) if c == separator && depth == 0 => {
+                parts.push(&text[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+
+    parts
+        .into_iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Reads the url from `url(main.css)`, `url("main.css")` or `"main.css"`
+pub(crate) fn parse_url(value: &str) -> Option<String> {
+    let value = value.trim();
+    let value = match value.strip_prefix("url(") {
+        Some(value) => &value[..value.find(')')?],
+        None => value.split_whitespace().next()?,
+    };
+    let url = value.trim().trim_matches(['"', '\'']);
+
+    if url.is_empty() {
+        None
+    } else {
+        Some(url.to_string())
+    }
+}
+
+fn applies_to_screen(media_query: &str) -> bool {
+    let media_query = media_query.to_ascii_lowercase();
+
+    media_query.split(',').any(|query| {
+        let query = query.trim();
+        !(query.starts_with("print")
+            || query.starts_with("speech")
+            || query.starts_with("not screen")
+            || query.starts_with("not all"))
+    })
+}
+
+#[cfg(test)]
+mod stylesheet_tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_rules_with_selector_lists_and_declarations() {
+        let css = r#"
+            /* Headings */
+            h1, h2.title { font-weight: bold; text-align: center !important }
+            p.TYTUL-R{margin:0 0 1em;}
+        "#;
+
+        let stylesheet = Stylesheet::parse(css);
+
+        assert_eq!(stylesheet.rules.len(), 2);
+        assert_eq!(stylesheet.rules[0].selectors.len(), 2);
+        assert_eq!(
+            stylesheet.rules[0].declarations,
+            vec![
+                Declaration {
+                    property: "font-weight".to_string(),
+                    value: "bold".to_string(),
+                    important: false,
+                },
+                Declaration {
+                    property: "text-align".to_string(),
+                    value: "center".to_string(),
+                    important: true,
+                },
+            ]
+        );
+        assert_eq!(stylesheet.rules[1].declarations[0].value, "0 0 1em");
+    }
+
+    #[test]
+    fn should_read_imports_and_screen_media_rules() {
+        let css = r#"
+            @charset "utf-8";
+            @import url("fonts.css");
+            @import 'base.css' screen;
+            @media print { p { display: none } }
+            @media screen, print { p { text-indent: 1em } }
+            em { font-style: italic }
+        "#;
+
+        let stylesheet = Stylesheet::parse(css);
+
+        assert_eq!(stylesheet.imports, vec!["fonts.css", "base.css"]);
+        assert_eq!(stylesheet.rules.len(), 2);
+        assert_eq!(stylesheet.rules[0].declarations[0].property, "text-indent");
+        assert_eq!(stylesheet.rules[1].declarations[0].property, "font-style");
+    }
+
+    #[test]
+    fn should_read_font_faces() {
+        let css = r#"
+            @font-face {
+                font-family: 'Stix';
+                font-weight: bold;
+                font-style: italic;
+                src: local("Stix Bold Italic"), url(../fonts/STIXGeneralBolIta.otf) format("opentype"),
+                    url('../fonts/STIXGeneralBolIta.woff');
+            }
+            @font-face { src: url(../fonts/unnamed.otf); }
+            @FONT-FACE { font-family: Kufi; src: url(kufi.ttf) }
+        "#;
+
+        let stylesheet = Stylesheet::parse(css);
+
+        assert_eq!(
+            stylesheet.font_faces,
+            vec![
+                FontFace {
+                    family: "Stix".to_string(),
+                    font_weight: 700,
+                    font_style: FontStyle::Italic,
+                    sources: vec![
+                        "../fonts/STIXGeneralBolIta.otf".to_string(),
+                        "../fonts/STIXGeneralBolIta.woff".to_string(),
+                    ],
+                },
+                FontFace {
+                    family: "Kufi".to_string(),
+                    font_weight: 400,
+                    font_style: FontStyle::Normal,
+                    sources: vec!["kufi.ttf".to_string()],
+                },
+            ]
+        );
+        assert!(stylesheet.rules.is_empty());
+    }
+
+    #[test]
+    fn should_skip_rules_with_unsupported_selectors() {
+        let css = "p:hover, p { color: red } p.first { margin: 0 }";
+
+        let stylesheet = Stylesheet::parse(css);
+
+        assert_eq!(stylesheet.rules.len(), 1);
+        assert_eq!(stylesheet.rules[0].declarations[0].value, "0");
+    }
+
+    #[test]
+    fn should_keep_semicolons_inside_of_urls_and_strings() {
+        let declarations = parse_declarations(
+            r#"background: url(data:image/png;base64,AAAA); content: "a;b"; margin:"#,
+        );
+
+        assert_eq!(declarations.len(), 2);
+        assert_eq!(declarations[0].value, "url(data:image/png;base64,AAAA)");
+        assert_eq!(declarations[1].value, r#""a;b""#);
+    }
+}
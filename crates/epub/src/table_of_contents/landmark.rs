@@ -0,0 +1,9 @@
+/// Entry of the epub 3 `landmarks` navigation, pointing to a structural part of the book
+#[derive(Debug, Clone, PartialEq)]
+pub struct Landmark {
+    /// Value of the `epub:type` attribute, e.g. `cover`, `toc` or `bodymatter`
+    pub landmark_type: Option<String>,
+    pub label: String,
+    pub path: String,
+    pub anchor: Option<String>,
+}
@@ -1,2 +1,5 @@
+pub mod landmark;
+pub mod page_list;
 pub mod table_of_contents;
+pub mod table_of_contents_entry;
 pub mod table_of_contents_item;
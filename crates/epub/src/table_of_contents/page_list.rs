@@ -0,0 +1,10 @@
+/// Entry of the epub 3 `page-list` navigation or the NCX `pageList`,
+/// pointing to the place where given page of the printed edition starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageTarget {
+    /// Page number as printed, e.g. `12` or `xiv`
+    pub label: String,
+    pub path: String,
+    pub anchor: Option<String>,
+    pub play_order: Option<u32>,
+}
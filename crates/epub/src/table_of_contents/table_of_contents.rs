@@ -1,40 +1,276 @@
-use std::{borrow::Borrow, fs::File, path::Path};
+use std::{borrow::Borrow, fs::File, sync::Arc};
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    name::QName,
+    Reader,
+};
+use zip::{result::ZipError, ZipArchive};
+
+use crate::{
+    epub::EBook,
+    error::EpubError,
+    manifest::{BookManifest, ManifestItem, ManifestItemProperty},
+    resources::{parent_directory, ResourcePath},
+    spine::BookSpine,
+};
+
+use super::{
+    landmark::Landmark, page_list::PageTarget, table_of_contents_entry::TableOfContentsEntry,
+    table_of_contents_item::TableOfContentsItem,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct TableOfContents {
+    /// All entries of the table of contents in reading order, with children placed right after their parent
+    pub items: Vec<TableOfContentsItem>,
+    /// Top level entries of the table of contents with nested entries kept as their children
+    pub entries: Vec<TableOfContentsEntry>,
+    pub landmarks: Vec<Landmark>,
+    pub page_list: Vec<PageTarget>,
+    /// Document the table of contents was read from, `None` when the book does not have one
+    pub source: Option<TableOfContentsSource>,
+}
 
-use quick_xml::{events::Event, name::QName, Reader};
-use zip::ZipArchive;
+/// Document holding the table of contents of the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TableOfContentsSource {
+    /// Epub 3 navigation document, the manifest item with `properties="nav"`
+    #[default]
+    NavigationDocument,
+    /// Epub 2 NCX file, pointed by the `toc` attribute of the spine
+    Ncx,
+}
 
-use crate::{epub::EBook, manifest::BookManifest};
+/// Kind of the epub 3 `<nav>` element, based on its `epub:type` attribute
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NavigationType {
+    TableOfContents,
+    Landmarks,
+    PageList,
+    Other,
+}
 
-use super::table_of_contents_item::TableOfContentsItem;
+impl NavigationType {
+    fn from_nav_element(e: &BytesStart<'_>) -> Result<NavigationType, EpubError> {
+        for attribute in e.attributes() {
+            let attr = attribute?;
 
-#[derive(Debug, Clone)]
-pub struct TableOfContents {
-    pub items: Vec<TableOfContentsItem>,
+            if attr.key == QName(b"epub:type") {
+                let value = attr.unescape_value()?;
+                let types: Vec<&str> = value.split_whitespace().collect();
+
+                if types.contains(&"toc") {
+                    return Ok(NavigationType::TableOfContents);
+                }
+                if types.contains(&"landmarks") {
+                    return Ok(NavigationType::Landmarks);
+                }
+                if types.contains(&"page-list") {
+                    return Ok(NavigationType::PageList);
+                }
+            }
+        }
+
+        Ok(NavigationType::Other)
+    }
+}
+
+/// NCX `navPoint`/`pageTarget` or navigation document `<li>` that is being read
+#[derive(Debug, Default)]
+struct NavigationPoint {
+    /// Path with an optional `#anchor`, already resolved against the content directory
+    href: Option<String>,
+    label: String,
+    play_order: Option<u32>,
+    epub_type: Option<String>,
+    has_label_element: bool,
+    children: Vec<TableOfContentsEntry>,
+}
+
+impl NavigationPoint {
+    fn from_ncx_element(e: &BytesStart<'_>) -> Result<NavigationPoint, EpubError> {
+        let mut point = NavigationPoint::default();
+
+        for attribute in e.attributes() {
+            let attr = attribute?;
+
+            if attr.key == QName(b"playOrder") {
+                point.play_order = attr.unescape_value()?.trim().parse().ok();
+            }
+        }
+
+        Ok(point)
+    }
+
+    fn is_label_element(e: &BytesStart<'_>) -> bool {
+        matches!(e.local_name().as_ref(), b"a" | b"span")
+    }
+
+    /// Reads the `<a>` or `<span>` holding the label of the point. Returns false when the label was already read,
+    /// since only the first one belongs to the point, the rest are labels of the nested points.
+    fn read_label_element(
+        &mut self,
+        e: &BytesStart<'_>,
+        content_dir: &str,
+    ) -> Result<bool, EpubError> {
+        if self.has_label_element {
+            return Ok(false);
+        }
+        self.has_label_element = true;
+
+        if e.local_name().as_ref() == b"a" {
+            self.href = Some(TableOfContentsItem::get_href_attribute_epub3(
+                e.attributes(),
+                content_dir,
+            )?);
+        }
+
+        for attribute in e.attributes() {
+            let attr = attribute?;
+
+            if attr.key == QName(b"epub:type") {
+                self.epub_type = Some(attr.unescape_value()?.to_string());
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn normalized_label(&self) -> String {
+        self.label
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Entries without a link, e.g. headings of the book parts, point to their first child
+    fn into_entry(self, depth: usize) -> TableOfContentsEntry {
+        let href = self.href.clone().or_else(|| {
+            self.children.first().map(|child| match &child.item.anchor {
+                Some(anchor) => format!("{}#{}", child.item.path, anchor),
+                None => child.item.path.clone(),
+            })
+        });
+
+        let mut item =
+            TableOfContentsItem::new(href.unwrap_or_default(), self.normalized_label(), None);
+        item.depth = depth;
+        item.play_order = self.play_order;
+
+        TableOfContentsEntry {
+            item,
+            children: self.children,
+        }
+    }
+
+    fn into_landmark(self) -> Landmark {
+        let item = TableOfContentsItem::new(
+            self.href.clone().unwrap_or_default(),
+            self.normalized_label(),
+            None,
+        );
+
+        Landmark {
+            landmark_type: self.epub_type,
+            label: item.label,
+            path: item.path,
+            anchor: item.anchor,
+        }
+    }
+
+    fn into_page_target(self) -> PageTarget {
+        let item = TableOfContentsItem::new(
+            self.href.clone().unwrap_or_default(),
+            self.normalized_label(),
+            None,
+        );
+
+        PageTarget {
+            label: item.label,
+            path: item.path,
+            anchor: item.anchor,
+            play_order: self.play_order,
+        }
+    }
 }
 
 impl TableOfContents {
     const NCX_EXTENSION: &'static str = ".ncx";
+    const NCX_MEDIA_TYPE: &'static str = "application/x-dtbncx+xml";
 
+    /// Finds the epub 3 navigation document by the `nav` manifest property and the epub 2 NCX by the spine `toc` attribute,
+    /// falling back to the manifest item with the NCX media type. When the book has both, the `preferred` one is returned.
+    pub fn locate_in_manifest(
+        manifest: &BookManifest,
+        spine: &BookSpine,
+        preferred: TableOfContentsSource,
+    ) -> Option<(TableOfContentsSource, Arc<ManifestItem>)> {
+        let navigation_document = manifest
+            .find_by_property(&ManifestItemProperty::Nav)
+            .map(|item| (TableOfContentsSource::NavigationDocument, item));
+
+        let ncx = spine
+            .toc
+            .as_ref()
+            .and_then(|toc_id| manifest.find_by_id(toc_id))
+            .or_else(|| {
+                manifest
+                    .items
+                    .iter()
+                    .find(|item| item.media_type == Self::NCX_MEDIA_TYPE)
+                    .cloned()
+            })
+            .map(|item| (TableOfContentsSource::Ncx, item));
+
+        match preferred {
+            TableOfContentsSource::NavigationDocument => navigation_document.or(ncx),
+            TableOfContentsSource::Ncx => ncx.or(navigation_document),
+        }
+    }
+
+    /// Reads the table of contents located by `locate_in_manifest`.
+    /// Books with neither the navigation document nor the NCX get an empty table of contents without a source.
     pub fn read_table_of_contents_from_manifest(
         zip: &mut ZipArchive<File>,
         manifest: &BookManifest,
-        content_dir: &Path,
-    ) -> (String, String) {
-        //TODO: Href here is not absolute, it is relative to opf file. This needs to be addressed.
-        let table_of_contents_from_manifest = manifest.search_for_item("toc").unwrap();
-
-        let toc_path = content_dir.join(table_of_contents_from_manifest.href.clone());
-        let toc_href = toc_path.to_str().unwrap();
+        spine: &BookSpine,
+        opf_path: &str,
+        preferred: TableOfContentsSource,
+    ) -> Result<TableOfContents, EpubError> {
+        let (source, item) = match TableOfContents::locate_in_manifest(manifest, spine, preferred) {
+            Some(located) => located,
+            None => return Ok(TableOfContents::default()),
+        };
+
+        let toc_path = ResourcePath::resolve(opf_path, &item.href).path;
+
+        let toc_content = match EBook::get_archive_file_content(zip, &toc_path) {
+            Err(EpubError::Zip(ZipError::FileNotFound)) => {
+                return Err(EpubError::MissingTableOfContents)
+            }
+            result => result?,
+        };
 
-        let toc_content = EBook::get_archive_file_content(zip, toc_href).unwrap_or_else(|err| {
-            eprintln!("{:?}", err);
-            "NONE".to_string()
-        });
+        let toc_dir = parent_directory(&toc_path).to_string();
+        let mut table_of_contents = match source {
+            TableOfContentsSource::NavigationDocument => {
+                TableOfContents::from_toc_content_for_epub_3(toc_content, toc_dir)?
+            }
+            TableOfContentsSource::Ncx => {
+                TableOfContents::from_toc_content_for_epub_2(toc_content, toc_dir)?
+            }
+        };
+        table_of_contents.source = Some(source);
 
-        (toc_href.to_string(), toc_content)
+        Ok(table_of_contents)
     }
 
-    pub fn from_content(href: String, content: String, content_dir: String) -> Self {
+    pub fn from_content(
+        href: String,
+        content: String,
+        content_dir: String,
+    ) -> Result<Self, EpubError> {
         let is_toc_in_ncx_format = href.contains(Self::NCX_EXTENSION);
 
         if is_toc_in_ncx_format {
@@ -47,171 +283,227 @@ impl TableOfContents {
     pub fn from_toc_content_for_epub_2(
         toc_content: String,
         content_dir: String,
-    ) -> TableOfContents {
+    ) -> Result<TableOfContents, EpubError> {
         let mut reader = Reader::from_str(toc_content.borrow());
-        reader.trim_text(true);
-
-        let navigation_selector: &[u8] = b"navMap";
+        reader.trim_text(false);
 
         let mut buf = Vec::new();
-        let mut toc_items: Vec<TableOfContentsItem> = vec![];
-
-        let mut toc_item_href: String = String::new();
-        let mut toc_item_label: String = String::new();
-        let mut toc_item_reading_started: bool = false;
-        let mut is_inside_toc_nav: bool = false;
-
-        while let Ok(event) = reader.read_event_into(&mut buf) {
-            match event {
-                Event::Start(ref e) | Event::Empty(ref e) => {
-                    if !is_inside_toc_nav {
-                        is_inside_toc_nav = e.name().as_ref() == navigation_selector;
-                    }
-
-                    if let b"content" = e.name().as_ref() {
-                        toc_item_href = TableOfContentsItem::get_src_attribute_epub2(
+        let mut entries: Vec<TableOfContentsEntry> = vec![];
+        let mut page_list: Vec<PageTarget> = vec![];
+
+        let mut open_nav_points: Vec<NavigationPoint> = vec![];
+        let mut open_page_target: Option<NavigationPoint> = None;
+        let mut is_inside_label_text: bool = false;
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(ref e) if e.local_name().as_ref() == b"text" => {
+                    // Only the first label is used when there are labels in multiple languages
+                    is_inside_label_text = open_page_target
+                        .as_ref()
+                        .or(open_nav_points.last())
+                        .map(|point| point.label.is_empty())
+                        .unwrap_or(false);
+                }
+                Event::Start(ref e) | Event::Empty(ref e) => match e.local_name().as_ref() {
+                    b"navPoint" => open_nav_points.push(NavigationPoint::from_ncx_element(e)?),
+                    b"pageTarget" => open_page_target = Some(NavigationPoint::from_ncx_element(e)?),
+                    b"content" => {
+                        let href = TableOfContentsItem::get_src_attribute_epub2(
                             e.attributes(),
                             &content_dir,
-                        );
-                        toc_item_reading_started = true;
+                        )?;
+
+                        if let Some(point) =
+                            open_page_target.as_mut().or(open_nav_points.last_mut())
+                        {
+                            point.href = Some(href);
+                        }
+                    }
+                    _ => {}
+                },
+                Event::Text(e) if is_inside_label_text => {
+                    if let Some(point) = open_page_target.as_mut().or(open_nav_points.last_mut()) {
+                        point.label.push_str(&e.unescape()?);
                     }
                 }
-                Event::Text(e) => {
-                    toc_item_label = e.unescape().unwrap().to_string();
-                }
-                Event::End(e) => {
-                    if !toc_item_reading_started {
-                        continue;
+                Event::End(ref e) => match e.local_name().as_ref() {
+                    b"text" => is_inside_label_text = false,
+                    b"navPoint" => {
+                        if let Some(point) = open_nav_points.pop() {
+                            let entry = point.into_entry(open_nav_points.len());
+
+                            match open_nav_points.last_mut() {
+                                Some(parent) => parent.children.push(entry),
+                                None => entries.push(entry),
+                            }
+                        }
                     }
-
-                    if !is_inside_toc_nav {
-                        break;
+                    b"pageTarget" => {
+                        if let Some(point) = open_page_target.take() {
+                            page_list.push(point.into_page_target());
+                        }
                     }
-
-                    let toc_item = TableOfContentsItem::new(toc_item_href, toc_item_label, None);
-
-                    toc_items.push(toc_item);
-                    toc_item_href = String::new();
-                    toc_item_label = String::new();
-                    toc_item_reading_started = false;
-                }
+                    _ => {}
+                },
                 Event::Eof => break,
                 _ => {}
             }
             buf.clear();
         }
 
-        TableOfContents { items: toc_items }
+        Ok(TableOfContents::new(entries, vec![], page_list))
     }
 
     pub fn from_toc_content_for_epub_3(
         toc_content: String,
         content_dir: String,
-    ) -> TableOfContents {
+    ) -> Result<TableOfContents, EpubError> {
         let mut reader = Reader::from_str(toc_content.borrow());
-        reader.trim_text(true);
-
-        let navigation_selector: String = "toc".to_string();
+        reader.trim_text(false);
 
         let mut buf = Vec::new();
-        let mut toc_items: Vec<TableOfContentsItem> = vec![];
-
-        let mut toc_item_href: String = String::new();
-        let mut toc_item_label: String = String::new();
-        let mut toc_item_reading_started: bool = false;
-        let mut is_inside_toc_nav: bool = false;
-
-        while let Ok(event) = reader.read_event_into(&mut buf) {
-            match event {
-                Event::Start(ref e) | Event::Empty(ref e) => {
-                    //TODO: Refactor that attribute check
-                    _ = e.attributes().any(|x| -> bool {
-                        if let Ok(attr) = x {
-                            if attr.key == QName(b"epub:type") {
-                                let ns = String::from_utf8(attr.value.to_vec()).unwrap();
-                                is_inside_toc_nav = ns == navigation_selector;
-                            }
+        let mut entries: Vec<TableOfContentsEntry> = vec![];
+        let mut landmarks: Vec<Landmark> = vec![];
+        let mut page_list: Vec<PageTarget> = vec![];
+
+        let mut current_nav: Option<NavigationType> = None;
+        let mut open_list_items: Vec<NavigationPoint> = vec![];
+        // Number of currently open elements inside of the `<a>` or `<span>` holding the label
+        let mut label_depth: usize = 0;
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(_) if label_depth > 0 => label_depth += 1,
+                Event::Start(ref e) | Event::Empty(ref e) if e.local_name().as_ref() == b"nav" => {
+                    current_nav = Some(NavigationType::from_nav_element(e)?);
+                }
+                Event::Start(ref e)
+                    if current_nav.is_some() && e.local_name().as_ref() == b"li" =>
+                {
+                    open_list_items.push(NavigationPoint::default());
+                }
+                Event::Start(ref e) if NavigationPoint::is_label_element(e) => {
+                    if let Some(item) = open_list_items.last_mut() {
+                        if item.read_label_element(e, &content_dir)? {
+                            label_depth = 1;
                         }
-
-                        false
-                    });
-
-                    if let b"a" = e.name().as_ref() {
-                        toc_item_href = TableOfContentsItem::get_href_attribute_epub3(
-                            e.attributes(),
-                            &content_dir,
-                        );
-                        toc_item_reading_started = true;
                     }
                 }
-                Event::Text(e) => {
-                    toc_item_label = e.unescape().unwrap().to_string();
-                }
-                Event::End(e) => {
-                    if !toc_item_reading_started {
-                        continue;
+                Event::Empty(ref e) if NavigationPoint::is_label_element(e) => {
+                    if let Some(item) = open_list_items.last_mut() {
+                        item.read_label_element(e, &content_dir)?;
                     }
-
-                    if !is_inside_toc_nav {
-                        break;
+                }
+                Event::Text(e) if label_depth > 0 => {
+                    if let Some(item) = open_list_items.last_mut() {
+                        item.label.push_str(&e.unescape()?);
                     }
-
-                    let toc_item = TableOfContentsItem::new(toc_item_href, toc_item_label, None);
-
-                    toc_items.push(toc_item);
-                    toc_item_href = String::new();
-                    toc_item_label = String::new();
-                    toc_item_reading_started = false;
                 }
+                Event::End(_) if label_depth > 0 => label_depth -= 1,
+                Event::End(ref e) => match e.local_name().as_ref() {
+                    b"nav" => current_nav = None,
+                    b"li" => {
+                        let item = match open_list_items.pop() {
+                            Some(item) => item,
+                            None => continue,
+                        };
+
+                        match current_nav {
+                            Some(NavigationType::TableOfContents) => {
+                                let entry = item.into_entry(open_list_items.len());
+
+                                match open_list_items.last_mut() {
+                                    Some(parent) => parent.children.push(entry),
+                                    None => entries.push(entry),
+                                }
+                            }
+                            Some(NavigationType::Landmarks) => landmarks.push(item.into_landmark()),
+                            Some(NavigationType::PageList) => {
+                                page_list.push(item.into_page_target())
+                            }
+                            Some(NavigationType::Other) | None => {}
+                        }
+                    }
+                    _ => {}
+                },
                 Event::Eof => break,
                 _ => {}
             }
             buf.clear();
         }
 
-        TableOfContents { items: toc_items }
+        Ok(TableOfContents::new(entries, landmarks, page_list))
+    }
+
+    fn new(
+        entries: Vec<TableOfContentsEntry>,
+        landmarks: Vec<Landmark>,
+        page_list: Vec<PageTarget>,
+    ) -> TableOfContents {
+        let mut items = TableOfContentsEntry::flatten(&entries);
+
+        // Reading systems should follow playOrder, which in well formed NCX files is the same as the document order
+        if items.iter().all(|item| item.play_order.is_some()) {
+            items.sort_by_key(|item| item.play_order);
+        }
+
+        TableOfContents {
+            items,
+            entries,
+            landmarks,
+            page_list,
+            source: None,
+        }
+    }
+
+    /// Returns the first landmark of given type, e.g. `bodymatter` for the beginning of the actual content
+    pub fn find_landmark(&self, landmark_type: &str) -> Option<&Landmark> {
+        self.landmarks
+            .iter()
+            .find(|landmark| landmark.landmark_type.as_deref() == Some(landmark_type))
     }
 
+    /// Finds the item pointing to `href`, which may contain an `#anchor`.
+    /// Href without an anchor matches the first item of the document when none of them points to the whole document.
     pub fn search_for_item(&self, href: &str) -> Option<&TableOfContentsItem> {
-        self.items.iter().find(|item| item.path == href)
+        self.position_of_href(href)
+            .map(|position| &self.items[position])
     }
 
-    pub fn previous_relative(&self, href: &str) -> Option<&TableOfContentsItem> {
-        let current_toc_item = self.search_for_item(href).unwrap();
+    /// Position of the item pointing to given location, see `search_for_item`
+    pub fn position_of(&self, path: &str, anchor: Option<&str>) -> Option<usize> {
+        let exact_position = self.items.iter().position(|item| item.is_at(path, anchor));
 
-        if self.items.starts_with(&[current_toc_item.clone()]) {
-            return None;
+        match anchor {
+            Some(_) => exact_position,
+            None => exact_position.or_else(|| self.items.iter().position(|item| item.path == path)),
         }
+    }
 
-        for (index, item) in self.items.iter().enumerate() {
-            if item == current_toc_item {
-                return Some(&self.items[index - 1]);
-            }
-        }
+    pub fn previous_relative(&self, href: &str) -> Option<&TableOfContentsItem> {
+        let position = self.position_of_href(href)?;
 
-        None
+        position
+            .checked_sub(1)
+            .and_then(|previous_position| self.items.get(previous_position))
     }
 
     pub fn next_relative(&self, href: &str) -> Option<&TableOfContentsItem> {
-        let current_toc_item = self.search_for_item(href).unwrap();
-
-        if self.items.ends_with(&[current_toc_item.clone()]) {
-            return None;
-        }
+        let position = self.position_of_href(href)?;
 
-        for (index, item) in self.items.iter().enumerate() {
-            if item == current_toc_item {
-                return Some(&self.items[index + 1]);
-            }
-        }
+        self.items.get(position + 1)
+    }
 
-        None
+    fn position_of_href(&self, href: &str) -> Option<usize> {
+        let (path, anchor) = TableOfContentsItem::split_href(href);
+        self.position_of(path, anchor)
     }
 }
 
 #[cfg(test)]
 mod epub2 {
-    use crate::table_of_contents::table_of_contents::TableOfContents;
+    use crate::table_of_contents::{page_list::PageTarget, table_of_contents::TableOfContents};
     use std::fs::File;
     use std::io::prelude::*;
 
@@ -229,7 +521,8 @@ mod epub2 {
         "#;
         let content_dir = "OEBPS".to_string();
         let table_of_contents =
-            TableOfContents::from_toc_content_for_epub_2(RAW_ITEM.to_string(), content_dir);
+            TableOfContents::from_toc_content_for_epub_2(RAW_ITEM.to_string(), content_dir)
+                .unwrap();
 
         assert_eq!(table_of_contents.items.len(), 1);
         assert_eq!(table_of_contents.items[0].label, "Spis treści");
@@ -252,7 +545,7 @@ mod epub2 {
         toc_file.read_to_string(&mut toc_content).unwrap();
 
         let table_of_contents =
-            TableOfContents::from_content("toc.ncx".to_string(), toc_content, content_dir);
+            TableOfContents::from_content("toc.ncx".to_string(), toc_content, content_dir).unwrap();
 
         let toc_length = table_of_contents.items.len();
 
@@ -294,11 +587,140 @@ mod epub2 {
             "Krzysztof Adamski"
         );
     }
+
+    #[test]
+    fn should_keep_nested_nav_points_as_children() {
+        const RAW_ITEM: &str = r#"
+        <ncx>
+          <navMap>
+            <navPoint id="part1" playOrder="1">
+              <navLabel><text>Part One</text></navLabel>
+              <content src="Text/part_1.xhtml"/>
+              <navPoint id="chapter1" playOrder="2">
+                <navLabel><text>Chapter 1</text></navLabel>
+                <content src="Text/chapter_1.xhtml"/>
+              </navPoint>
+              <navPoint id="chapter2" playOrder="3">
+                <navLabel><text>Chapter 2</text></navLabel>
+                <content src="Text/chapter_2.xhtml#start"/>
+              </navPoint>
+            </navPoint>
+            <navPoint id="part2" playOrder="4">
+              <navLabel><text>Part Two</text></navLabel>
+              <content src="Text/part_2.xhtml"/>
+            </navPoint>
+          </navMap>
+        </ncx>
+        "#;
+
+        let table_of_contents =
+            TableOfContents::from_toc_content_for_epub_2(RAW_ITEM.to_string(), "OEBPS".to_string())
+                .unwrap();
+
+        assert_eq!(table_of_contents.entries.len(), 2);
+        assert_eq!(table_of_contents.entries[0].item.label, "Part One");
+        assert_eq!(table_of_contents.entries[0].children.len(), 2);
+        assert_eq!(
+            table_of_contents.entries[0].children[1].item.path,
+            "OEBPS/Text/chapter_2.xhtml"
+        );
+        assert_eq!(table_of_contents.entries[0].children[1].item.depth, 1);
+        assert!(table_of_contents.entries[1].children.is_empty());
+
+        let flattened: Vec<(&str, usize, Option<u32>)> = table_of_contents
+            .items
+            .iter()
+            .map(|item| (item.label.as_str(), item.depth, item.play_order))
+            .collect();
+        assert_eq!(
+            flattened,
+            vec![
+                ("Part One", 0, Some(1)),
+                ("Chapter 1", 1, Some(2)),
+                ("Chapter 2", 1, Some(3)),
+                ("Part Two", 0, Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_order_items_by_play_order() {
+        const RAW_ITEM: &str = r#"
+        <ncx>
+          <navMap>
+            <navPoint id="second" playOrder="2">
+              <navLabel><text>Second</text></navLabel>
+              <content src="second.xhtml"/>
+            </navPoint>
+            <navPoint id="first" playOrder="1">
+              <navLabel><text>First</text></navLabel>
+              <content src="first.xhtml"/>
+            </navPoint>
+          </navMap>
+        </ncx>
+        "#;
+
+        let table_of_contents =
+            TableOfContents::from_toc_content_for_epub_2(RAW_ITEM.to_string(), "OEBPS".to_string())
+                .unwrap();
+
+        assert_eq!(table_of_contents.items[0].label, "First");
+        assert_eq!(table_of_contents.items[1].label, "Second");
+        assert_eq!(table_of_contents.entries[0].item.label, "Second");
+    }
+
+    #[test]
+    fn should_parse_page_list_separately() {
+        const RAW_ITEM: &str = r#"
+        <ncx>
+          <navMap>
+            <navPoint id="chapter1" playOrder="1">
+              <navLabel><text>Chapter 1</text></navLabel>
+              <content src="Text/chapter_1.xhtml"/>
+            </navPoint>
+          </navMap>
+          <pageList>
+            <navLabel><text>Pages</text></navLabel>
+            <pageTarget id="page1" type="normal" value="1" playOrder="2">
+              <navLabel><text>1</text></navLabel>
+              <content src="Text/chapter_1.xhtml#page_1"/>
+            </pageTarget>
+            <pageTarget id="page2" type="normal" value="2" playOrder="3">
+              <navLabel><text>2</text></navLabel>
+              <content src="Text/chapter_1.xhtml#page_2"/>
+            </pageTarget>
+          </pageList>
+        </ncx>
+        "#;
+
+        let table_of_contents =
+            TableOfContents::from_toc_content_for_epub_2(RAW_ITEM.to_string(), "OEBPS".to_string())
+                .unwrap();
+
+        assert_eq!(table_of_contents.items.len(), 1);
+        assert_eq!(
+            table_of_contents.page_list,
+            vec![
+                PageTarget {
+                    label: "1".to_string(),
+                    path: "OEBPS/Text/chapter_1.xhtml".to_string(),
+                    anchor: Some("page_1".to_string()),
+                    play_order: Some(2),
+                },
+                PageTarget {
+                    label: "2".to_string(),
+                    path: "OEBPS/Text/chapter_1.xhtml".to_string(),
+                    anchor: Some("page_2".to_string()),
+                    play_order: Some(3),
+                },
+            ]
+        );
+    }
 }
 
 #[cfg(test)]
 mod epub3 {
-    use crate::table_of_contents::table_of_contents::TableOfContents;
+    use crate::table_of_contents::{landmark::Landmark, table_of_contents::TableOfContents};
     use std::fs::File;
     use std::io::prelude::*;
 
@@ -316,7 +738,8 @@ mod epub3 {
 
         let content_dir = "OPS".to_string();
         let table_of_contents =
-            TableOfContents::from_toc_content_for_epub_3(RAW_ITEM.to_string(), content_dir);
+            TableOfContents::from_toc_content_for_epub_3(RAW_ITEM.to_string(), content_dir)
+                .unwrap();
 
         assert_eq!(table_of_contents.items.len(), 1);
         assert_eq!(table_of_contents.items[0].label, "Moby-Dick");
@@ -332,7 +755,8 @@ mod epub3 {
 
         let content_dir = "OPS".to_string();
         let table_of_contents =
-            TableOfContents::from_content("toc.xhtml".to_string(), toc_content, content_dir);
+            TableOfContents::from_content("toc.xhtml".to_string(), toc_content, content_dir)
+                .unwrap();
 
         let toc_length = table_of_contents.items.len();
 
@@ -350,6 +774,121 @@ mod epub3 {
             "Chapter 135. The Chase.—Third Day."
         );
     }
+
+    #[test]
+    fn should_keep_nested_lists_as_children() {
+        const RAW_ITEM: &str = r#"
+        <nav xmlns:epub="http://www.idpf.org/2007/ops" epub:type="toc" id="toc">
+           <h1>Contents</h1>
+           <ol>
+              <li>
+                 <span>Part <em>One</em></span>
+                 <ol>
+                    <li><a href="Text/chapter_1.xhtml#start">Chapter 1.
+                        The   Beginning</a></li>
+                    <li>
+                       <a href="Text/chapter_2.xhtml">Chapter 2</a>
+                       <ol>
+                          <li><a href="Text/chapter_2.xhtml#scene_2">Scene 2</a></li>
+                       </ol>
+                    </li>
+                 </ol>
+              </li>
+              <li><a href="Text/epilogue.xhtml">Epilogue</a></li>
+           </ol>
+        </nav>
+        "#;
+
+        let table_of_contents =
+            TableOfContents::from_toc_content_for_epub_3(RAW_ITEM.to_string(), "OPS".to_string())
+                .unwrap();
+
+        let part = &table_of_contents.entries[0];
+        assert_eq!(table_of_contents.entries.len(), 2);
+        assert_eq!(part.item.label, "Part One");
+        assert_eq!(part.item.path, "OPS/Text/chapter_1.xhtml");
+        assert_eq!(part.item.anchor, Some("start".to_string()));
+        assert_eq!(part.children[0].item.label, "Chapter 1. The Beginning");
+        assert_eq!(part.children[1].children[0].item.label, "Scene 2");
+        assert_eq!(part.children[1].children[0].item.depth, 2);
+
+        let flattened: Vec<(&str, usize)> = table_of_contents
+            .items
+            .iter()
+            .map(|item| (item.label.as_str(), item.depth))
+            .collect();
+        assert_eq!(
+            flattened,
+            vec![
+                ("Part One", 0),
+                ("Chapter 1. The Beginning", 1),
+                ("Chapter 2", 1),
+                ("Scene 2", 2),
+                ("Epilogue", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_parse_landmarks_separately() {
+        const TOC_XHTML_SAMPLE_PATH: &str = "./test_data/toc/toc.xhtml";
+        let mut toc_file = File::open(TOC_XHTML_SAMPLE_PATH).unwrap();
+        let mut toc_content = String::new();
+        toc_file.read_to_string(&mut toc_content).unwrap();
+
+        let table_of_contents =
+            TableOfContents::from_content("toc.xhtml".to_string(), toc_content, "OPS".to_string())
+                .unwrap();
+
+        assert_eq!(table_of_contents.landmarks.len(), 4);
+        assert_eq!(
+            table_of_contents.find_landmark("bodymatter"),
+            Some(&Landmark {
+                landmark_type: Some("bodymatter".to_string()),
+                label: "Begin Reading".to_string(),
+                path: "OPS/chapter_001.xhtml".to_string(),
+                anchor: None,
+            })
+        );
+        assert_eq!(
+            table_of_contents.find_landmark("toc").unwrap().anchor,
+            Some("toc".to_string())
+        );
+        assert!(table_of_contents
+            .items
+            .iter()
+            .all(|item| item.label != "Begin Reading"));
+    }
+
+    #[test]
+    fn should_parse_page_list_separately() {
+        const RAW_ITEM: &str = r#"
+        <html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+        <nav epub:type="toc">
+           <ol><li><a href="chapter_1.xhtml">Chapter 1</a></li></ol>
+        </nav>
+        <nav epub:type="page-list" hidden="">
+           <ol>
+              <li><a href="chapter_1.xhtml#page_1">1</a></li>
+              <li><a href="chapter_1.xhtml#page_2">2</a></li>
+           </ol>
+        </nav>
+        </body></html>
+        "#;
+
+        let table_of_contents =
+            TableOfContents::from_toc_content_for_epub_3(RAW_ITEM.to_string(), "OPS".to_string())
+                .unwrap();
+
+        assert_eq!(table_of_contents.items.len(), 1);
+        assert_eq!(table_of_contents.page_list.len(), 2);
+        assert_eq!(table_of_contents.page_list[1].label, "2");
+        assert_eq!(table_of_contents.page_list[1].path, "OPS/chapter_1.xhtml");
+        assert_eq!(
+            table_of_contents.page_list[1].anchor,
+            Some("page_2".to_string())
+        );
+    }
 }
 
 #[cfg(test)]
@@ -361,7 +900,7 @@ mod navigation {
 
     #[test]
     fn reader_should_get_the_content_based_on_toc_item() {
-        let mut book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
 
         let table_of_contents = book.table_of_contents.clone();
 
@@ -481,6 +1020,62 @@ mod navigation {
         assert_eq!(expected_toc_item, *next_toc_item);
     }
 
+    #[test]
+    fn relatives_should_be_keyed_on_path_and_anchor() {
+        //arrange
+        let items = vec![
+            TableOfContentsItem::new(
+                "OPS/parts.xhtml#part_1".to_string(),
+                "Part 1".to_string(),
+                None,
+            ),
+            TableOfContentsItem::new(
+                "OPS/parts.xhtml#part_2".to_string(),
+                "Part 2".to_string(),
+                None,
+            ),
+            TableOfContentsItem::new(
+                "OPS/parts.xhtml#part_3".to_string(),
+                "Part 3".to_string(),
+                None,
+            ),
+            TableOfContentsItem::new(
+                "OPS/epilogue.xhtml".to_string(),
+                "Epilogue".to_string(),
+                None,
+            ),
+        ];
+        let table_of_contents = TableOfContents {
+            items,
+            ..TableOfContents::default()
+        };
+
+        //act & assert
+        let next_toc_item = table_of_contents
+            .next_relative("OPS/parts.xhtml#part_2")
+            .unwrap();
+        assert_eq!(next_toc_item.label, "Part 3");
+        let previous_toc_item = table_of_contents
+            .previous_relative("OPS/parts.xhtml#part_2")
+            .unwrap();
+        assert_eq!(previous_toc_item.label, "Part 1");
+        let next_toc_item = table_of_contents
+            .next_relative("OPS/parts.xhtml#part_3")
+            .unwrap();
+        assert_eq!(next_toc_item.label, "Epilogue");
+        // Path alone matches the first item of the document
+        let found_toc_item = table_of_contents
+            .search_for_item("OPS/parts.xhtml")
+            .unwrap();
+        assert_eq!(found_toc_item.label, "Part 1");
+        assert!(table_of_contents
+            .search_for_item("OPS/parts.xhtml#part_4")
+            .is_none());
+        assert!(table_of_contents
+            .next_relative("OPS/missing.xhtml")
+            .is_none());
+    }
+
     #[test]
     fn get_previous_relative_should_return_none_when_there_is_no_previous_toc_item() {
         //arrange
@@ -499,3 +1094,135 @@ mod navigation {
         assert!(next_toc_item.is_none());
     }
 }
+
+#[cfg(test)]
+mod locating {
+    use crate::{epub::EBook, options::EBookOptions, test_utils::create_epub};
+
+    use super::*;
+    const MOBY_DICK_PATH: &str = "./test_data/epub/moby-dick.epub";
+
+    const NCX_CONTENT: &str = r#"<ncx><navMap>
+        <navPoint id="n1" playOrder="1"><navLabel><text>From NCX</text></navLabel><content src="chapter.xhtml"/></navPoint>
+    </navMap></ncx>"#;
+
+    const NAV_CONTENT: &str = r#"<html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+        <nav epub:type="toc"><ol><li><a href="chapter.xhtml">From navigation document</a></li></ol></nav>
+    </body></html>"#;
+
+    fn create_book(name: &str, manifest: &str, spine_attributes: &str) -> String {
+        let opf = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Navigation</dc:title></metadata>
+                <manifest>
+                    <item id="chapter" href="chapter.xhtml" media-type="application/xhtml+xml"/>
+                    {}
+                </manifest>
+                <spine {}><itemref idref="chapter"/></spine>
+            </package>"#,
+            manifest, spine_attributes
+        );
+
+        create_epub(
+            name,
+            &opf,
+            &[
+                ("navigation.xhtml", NAV_CONTENT.as_bytes()),
+                ("contents.ncx", NCX_CONTENT.as_bytes()),
+                ("chapter.xhtml", b"<html/>"),
+            ],
+        )
+    }
+
+    const NAV_ITEM: &str = r#"<item id="navigation" href="navigation.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#;
+    const NCX_ITEM: &str =
+        r#"<item id="ncx" href="contents.ncx" media-type="application/x-dtbncx+xml"/>"#;
+
+    #[test]
+    fn should_find_navigation_document_by_nav_property() {
+        let book = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+
+        assert_eq!(
+            book.table_of_contents.source,
+            Some(TableOfContentsSource::NavigationDocument)
+        );
+        assert_eq!(book.table_of_contents.items.len(), 141);
+    }
+
+    #[test]
+    fn should_find_navigation_document_with_id_other_than_toc() {
+        let path = create_book("lore_leaf_locate_nav.epub", NAV_ITEM, "");
+
+        let book = EBook::read_epub(path).unwrap();
+
+        assert_eq!(
+            book.table_of_contents.items[0].label,
+            "From navigation document"
+        );
+        assert_eq!(book.table_of_contents.items[0].path, "OEBPS/chapter.xhtml");
+    }
+
+    #[test]
+    fn should_find_ncx_by_spine_toc_attribute() {
+        let path = create_book("lore_leaf_locate_ncx.epub", NCX_ITEM, r#"toc="ncx""#);
+
+        let book = EBook::read_epub(path).unwrap();
+
+        assert_eq!(
+            book.table_of_contents.source,
+            Some(TableOfContentsSource::Ncx)
+        );
+        assert_eq!(book.table_of_contents.items[0].label, "From NCX");
+    }
+
+    #[test]
+    fn should_use_preferred_source_when_book_has_both() {
+        let manifest = format!("{}{}", NAV_ITEM, NCX_ITEM);
+        let path = create_book("lore_leaf_locate_both.epub", &manifest, r#"toc="ncx""#);
+        let ncx_options = EBookOptions {
+            preferred_table_of_contents: TableOfContentsSource::Ncx,
+            ..EBookOptions::default()
+        };
+
+        let default_book = EBook::read_epub(path.clone()).unwrap();
+        let ncx_book = EBook::read_epub_with_options(path, &ncx_options).unwrap();
+
+        assert_eq!(
+            default_book.table_of_contents.items[0].label,
+            "From navigation document"
+        );
+        assert_eq!(ncx_book.table_of_contents.items[0].label, "From NCX");
+    }
+
+    #[test]
+    fn should_return_empty_table_of_contents_when_book_has_neither() {
+        let path = create_book("lore_leaf_locate_none.epub", "", "");
+
+        let book = EBook::read_epub(path).unwrap();
+
+        assert_eq!(book.table_of_contents.source, None);
+        assert!(book.table_of_contents.items.is_empty());
+        assert_eq!(book.spine.items.len(), 1);
+    }
+
+    #[test]
+    fn should_return_error_when_declared_table_of_contents_is_missing_from_archive() {
+        let opf = r#"<package version="3.0">
+            <manifest>
+                <item id="chapter" href="chapter.xhtml" media-type="application/xhtml+xml"/>
+                <item id="navigation" href="missing.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+            </manifest>
+            <spine><itemref idref="chapter"/></spine>
+        </package>"#;
+        let path = create_epub(
+            "lore_leaf_locate_missing.epub",
+            opf,
+            &[("chapter.xhtml", b"<html/>")],
+        );
+
+        let result = EBook::read_epub(path);
+
+        assert!(matches!(result, Err(EpubError::MissingTableOfContents)));
+    }
+}
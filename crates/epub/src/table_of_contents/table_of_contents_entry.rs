@@ -0,0 +1,67 @@
+use super::table_of_contents_item::TableOfContentsItem;
+
+/// Node of the table of contents tree, e.g. a part of the book together with its chapters
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableOfContentsEntry {
+    pub item: TableOfContentsItem,
+    pub children: Vec<TableOfContentsEntry>,
+}
+
+impl TableOfContentsEntry {
+    /// Flattens given entries into a list, where every entry is followed by its children
+    pub fn flatten(entries: &[TableOfContentsEntry]) -> Vec<TableOfContentsItem> {
+        let mut items: Vec<TableOfContentsItem> = vec![];
+
+        for entry in entries {
+            items.push(entry.item.clone());
+            items.extend(TableOfContentsEntry::flatten(&entry.children));
+        }
+
+        items
+    }
+}
+
+#[cfg(test)]
+mod entry_tests {
+    use super::*;
+
+    fn entry(
+        label: &str,
+        depth: usize,
+        children: Vec<TableOfContentsEntry>,
+    ) -> TableOfContentsEntry {
+        let mut item =
+            TableOfContentsItem::new(format!("{}.xhtml", label), label.to_string(), None);
+        item.depth = depth;
+
+        TableOfContentsEntry { item, children }
+    }
+
+    #[test]
+    fn flatten_should_put_children_right_after_their_parent() {
+        let entries = vec![
+            entry(
+                "part_1",
+                0,
+                vec![entry("chapter_1", 1, vec![]), entry("chapter_2", 1, vec![])],
+            ),
+            entry("part_2", 0, vec![entry("chapter_3", 1, vec![])]),
+        ];
+
+        let labels: Vec<(String, usize)> = TableOfContentsEntry::flatten(&entries)
+            .into_iter()
+            .map(|item| (item.label, item.depth))
+            .collect();
+
+        assert_eq!(
+            labels,
+            vec![
+                ("part_1".to_string(), 0),
+                ("chapter_1".to_string(), 1),
+                ("chapter_2".to_string(), 1),
+                ("part_2".to_string(), 0),
+                ("chapter_3".to_string(), 1),
+            ]
+        );
+    }
+}
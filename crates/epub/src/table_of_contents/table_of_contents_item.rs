@@ -1,6 +1,6 @@
 use quick_xml::{events::attributes::Attributes, name::QName};
 
-use crate::strings::EMTPY_STRING_SLICE;
+use crate::{error::EpubError, resources::ResourcePath, strings::EMTPY_STRING_SLICE};
 
 #[derive(Debug, Clone)]
 pub struct TableOfContentsItem {
@@ -8,11 +8,15 @@ pub struct TableOfContentsItem {
     pub anchor: Option<String>,
     pub label: String,
     pub content: Option<String>,
+    /// Nesting level of the entry, 0 for the top level entries, e.g. parts of the book, 1 for their chapters
+    pub depth: usize,
+    /// Value of the NCX `playOrder` attribute, navigation documents of epub 3 do not have one
+    pub play_order: Option<u32>,
 }
 
 impl PartialEq for TableOfContentsItem {
     fn eq(&self, other: &Self) -> bool {
-        self.path == other.path && self.label == other.label
+        self.path == other.path && self.anchor == other.anchor && self.label == other.label
     }
 }
 
@@ -22,33 +26,36 @@ impl TableOfContentsItem {
     const EPUB3_HREF_ATTRIBUTE: &'static str = "href";
 
     pub fn new(path: String, label: String, content: Option<String>) -> Self {
-        let split_path = path.split_once(Self::EPUB2_SRC_ELEMENT_SPLITTER);
-
-        let cleaned_path = match split_path {
-            Some((path_chunk, _)) => path_chunk.to_string(),
-            None => path.to_string(),
-        };
-
-        let anchor = match split_path {
-            Some((_, anchor_chunk)) => {
-                if anchor_chunk.is_empty() {
-                    None
-                } else {
-                    Some(anchor_chunk.to_string())
-                }
-            }
-            None => None,
-        };
+        let (cleaned_path, anchor) = Self::split_href(&path);
 
         Self {
-            path: cleaned_path,
-            anchor,
+            path: cleaned_path.to_string(),
+            anchor: anchor.map(|anchor| anchor.to_string()),
             label,
             content,
+            depth: 0,
+            play_order: None,
+        }
+    }
+
+    /// Splits `path#anchor` into the path and the anchor, an empty anchor is treated as no anchor
+    pub fn split_href(href: &str) -> (&str, Option<&str>) {
+        match href.split_once(Self::EPUB2_SRC_ELEMENT_SPLITTER) {
+            Some((path, anchor)) if !anchor.is_empty() => (path, Some(anchor)),
+            Some((path, _)) => (path, None),
+            None => (href, None),
         }
     }
 
-    pub fn get_src_attribute_epub2(attributes: Attributes, content_dir: &str) -> String {
+    /// Whether the item points to given location, entries of the same document are told apart by their anchors
+    pub fn is_at(&self, path: &str, anchor: Option<&str>) -> bool {
+        self.path == path && self.anchor.as_deref() == anchor
+    }
+
+    pub fn get_src_attribute_epub2(
+        attributes: Attributes,
+        content_dir: &str,
+    ) -> Result<String, EpubError> {
         Self::create_path_from_attribute_and_content_dir(
             attributes,
             content_dir,
@@ -56,7 +63,10 @@ impl TableOfContentsItem {
         )
     }
 
-    pub fn get_href_attribute_epub3(attributes: Attributes, content_dir: &str) -> String {
+    pub fn get_href_attribute_epub3(
+        attributes: Attributes,
+        content_dir: &str,
+    ) -> Result<String, EpubError> {
         Self::create_path_from_attribute_and_content_dir(
             attributes,
             content_dir,
@@ -68,18 +78,28 @@ impl TableOfContentsItem {
         attributes: Attributes,
         content_dir: &str,
         selector: &str,
-    ) -> String {
+    ) -> Result<String, EpubError> {
         let mut href: String = EMTPY_STRING_SLICE.to_string();
 
         for attribute in attributes {
-            let attr = attribute.unwrap();
+            let attr = attribute?;
 
             if attr.key == QName(selector.as_bytes()) {
-                href = String::from_utf8(attr.value.to_vec()).unwrap();
+                href = String::from_utf8(attr.value.to_vec())?;
             }
         }
 
-        format!("{}/{}", content_dir, href).to_string()
+        let resource = ResourcePath::resolve_in_directory(content_dir, &href);
+
+        match resource.fragment {
+            Some(fragment) => Ok(format!(
+                "{}{}{}",
+                resource.path,
+                Self::EPUB2_SRC_ELEMENT_SPLITTER,
+                fragment
+            )),
+            None => Ok(resource.path),
+        }
     }
 }
 
@@ -0,0 +1,63 @@
+use std::{fs::File, io::Write};
+
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    chapters::chapter::Chapter, epub::META_INF_CONTAINER_PATH,
+    table_of_contents::table_of_contents_item::TableOfContentsItem,
+};
+
+pub(crate) const CONTAINER_CONTENT: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+    <rootfiles>
+        <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+    </rootfiles>
+</container>"#;
+
+/// Creates a zip file with given entries in the temporary directory and returns its path
+pub(crate) fn create_archive(name: &str, entries: &[(&str, &[u8])]) -> String {
+    let path = std::env::temp_dir().join(name);
+    let file = File::create(&path).unwrap();
+    let mut writer = ZipWriter::new(file);
+
+    for (entry_name, entry_content) in entries {
+        let options = if *entry_name == "mimetype" {
+            FileOptions::default().compression_method(CompressionMethod::Stored)
+        } else {
+            FileOptions::default()
+        };
+
+        writer.start_file(*entry_name, options).unwrap();
+        writer.write_all(entry_content).unwrap();
+    }
+
+    writer.finish().unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+/// Creates an epub with the OPF placed in `OEBPS/content.opf` and given resources placed relative to the OPF
+pub(crate) fn create_epub(name: &str, opf_content: &str, resources: &[(&str, &[u8])]) -> String {
+    let resource_paths: Vec<String> = resources
+        .iter()
+        .map(|(path, _)| format!("OEBPS/{}", path))
+        .collect();
+
+    let mut entries: Vec<(&str, &[u8])> = vec![
+        ("mimetype", b"application/epub+zip"),
+        (META_INF_CONTAINER_PATH, CONTAINER_CONTENT.as_bytes()),
+        ("OEBPS/content.opf", opf_content.as_bytes()),
+    ];
+
+    for (path, (_, content)) in resource_paths.iter().zip(resources) {
+        entries.push((path, content));
+    }
+
+    create_archive(name, &entries)
+}
+
+/// Parses given content as the chapter at `path`, without reading it from any book
+pub(crate) fn chapter_from_content(path: &str, content: &str) -> Chapter {
+    let item = TableOfContentsItem::new(path.to_string(), String::new(), None);
+
+    Chapter::from_item_with_content(item, content.to_string())
+}
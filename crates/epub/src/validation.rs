@@ -0,0 +1,672 @@
+use std::{collections::HashSet, fmt, fs::File};
+
+use quick_xml::{events::Event, name::QName, Reader};
+use zip::{result::ZipError, CompressionMethod, ZipArchive};
+
+use crate::{
+    encryption::{BookEncryption, DrmScheme, ResourceProtection, META_INF_ENCRYPTION_PATH},
+    epub::EBook,
+    error::EpubError,
+    manifest::BookManifest,
+    resources::{is_external_href, ResourcePath},
+    spine::BookSpine,
+    table_of_contents::table_of_contents::{TableOfContents, TableOfContentsSource},
+};
+
+const MIMETYPE_PATH: &str = "mimetype";
+const EPUB_MIMETYPE: &str = "application/epub+zip";
+const KNOWN_VERSIONS: [&str; 7] = ["2.0", "2.0.1", "3.0", "3.0.1", "3.1", "3.2", "3.3"];
+const CONTENT_DOCUMENT_MEDIA_TYPES: [&str; 2] = ["application/xhtml+xml", "text/html"];
+
+/// Problems found in the epub by `EBook::validate_epub`, in the order they were found
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub problems: Vec<ValidationProblem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationProblem {
+    /// There is no `mimetype` file in the archive
+    MissingMimetype,
+    /// The `mimetype` file is not the first entry of the archive
+    MimetypeNotFirst,
+    /// The `mimetype` file is compressed, so it can not be read at a fixed offset
+    CompressedMimetype,
+    /// The `mimetype` file contains something else than `application/epub+zip`
+    InvalidMimetype(String),
+    MissingContainer,
+    /// The OPF file is not declared in the container, or the declared path does not exist in the archive
+    MissingOpf(Option<String>),
+    /// Version of the package is missing or is not one of the published epub versions
+    UnknownVersion(Option<String>),
+    /// The book is protected with DRM, so its encrypted documents are not checked
+    DrmProtected(DrmScheme),
+    /// The document could not be parsed
+    MalformedDocument {
+        path: String,
+        message: String,
+    },
+    /// The spine references a manifest item with given id, but there is no such item in the manifest
+    DanglingSpineReference(String),
+    /// Manifest item points to a file missing from the archive
+    MissingManifestResource {
+        id: String,
+        path: String,
+    },
+    /// Neither the navigation document nor the NCX is declared, or the declared one is missing from the archive
+    MissingTableOfContents,
+    /// Table of contents entry points to a file missing from the archive
+    BrokenTableOfContentsLink {
+        label: String,
+        path: String,
+    },
+    /// The same id is used by more than one element of the document
+    DuplicateId {
+        path: String,
+        id: String,
+    },
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationProblem::MissingMimetype => write!(f, "mimetype file is missing"),
+            ValidationProblem::MimetypeNotFirst => {
+                write!(f, "mimetype file is not the first entry of the archive")
+            }
+            ValidationProblem::CompressedMimetype => write!(f, "mimetype file is compressed"),
+            ValidationProblem::InvalidMimetype(mimetype) => {
+                write!(
+                    f,
+                    "mimetype file contains {} instead of {}",
+                    mimetype, EPUB_MIMETYPE
+                )
+            }
+            ValidationProblem::MissingContainer => {
+                write!(f, "{} is missing", crate::epub::META_INF_CONTAINER_PATH)
+            }
+            ValidationProblem::MissingOpf(None) => {
+                write!(f, "container does not declare an OPF file")
+            }
+            ValidationProblem::MissingOpf(Some(path)) => write!(f, "OPF file {} is missing", path),
+            ValidationProblem::UnknownVersion(None) => {
+                write!(f, "package does not declare its version")
+            }
+            ValidationProblem::UnknownVersion(Some(version)) => {
+                write!(f, "package declares unknown version {}", version)
+            }
+            ValidationProblem::DrmProtected(scheme) => {
+                write!(
+                    f,
+                    "epub is protected with {}, its encrypted documents are not checked",
+                    scheme
+                )
+            }
+            ValidationProblem::MalformedDocument { path, message } => {
+                write!(f, "{} is malformed: {}", path, message)
+            }
+            ValidationProblem::DanglingSpineReference(idref) => {
+                write!(
+                    f,
+                    "spine references item {} missing from the manifest",
+                    idref
+                )
+            }
+            ValidationProblem::MissingManifestResource { id, path } => {
+                write!(f, "manifest item {} points to missing file {}", id, path)
+            }
+            ValidationProblem::MissingTableOfContents => {
+                write!(f, "epub does not contain a table of contents")
+            }
+            ValidationProblem::BrokenTableOfContentsLink { label, path } => {
+                write!(
+                    f,
+                    "table of contents entry {} points to missing file {}",
+                    label, path
+                )
+            }
+            ValidationProblem::DuplicateId { path, id } => {
+                write!(f, "id {} is used more than once in {}", id, path)
+            }
+        }
+    }
+}
+
+/// Parts of the OPF that are lost or rejected by the regular parsers
+#[derive(Default)]
+struct RawPackage {
+    version: Option<String>,
+    spine_toc: Option<String>,
+    spine_idrefs: Vec<String>,
+}
+
+impl EBook {
+    /// Checks the epub for problems that the reader either rejects or silently ignores.
+    /// Only failures to open the file as a zip archive are returned as errors.
+    pub fn validate_epub(epub_path: String) -> Result<ValidationReport, EpubError> {
+        let epub_file = File::open(epub_path)?;
+        let mut zip = ZipArchive::new(epub_file)?;
+        let mut problems: Vec<ValidationProblem> = vec![];
+
+        validate_mimetype(&mut zip, &mut problems)?;
+        validate_package(&mut zip, &mut problems)?;
+
+        Ok(ValidationReport { problems })
+    }
+}
+
+fn validate_mimetype(
+    zip: &mut ZipArchive<File>,
+    problems: &mut Vec<ValidationProblem>,
+) -> Result<(), EpubError> {
+    if zip.file_names().all(|name| name != MIMETYPE_PATH) {
+        problems.push(ValidationProblem::MissingMimetype);
+        return Ok(());
+    }
+
+    if zip.by_index(0)?.name() != MIMETYPE_PATH {
+        problems.push(ValidationProblem::MimetypeNotFirst);
+    }
+
+    if zip.by_name(MIMETYPE_PATH)?.compression() != CompressionMethod::Stored {
+        problems.push(ValidationProblem::CompressedMimetype);
+    }
+
+    let mimetype = EBook::get_archive_file_bytes(zip, MIMETYPE_PATH)?;
+    let mimetype = String::from_utf8_lossy(&mimetype);
+    if mimetype != EPUB_MIMETYPE {
+        problems.push(ValidationProblem::InvalidMimetype(mimetype.to_string()));
+    }
+
+    Ok(())
+}
+
+fn validate_package(
+    zip: &mut ZipArchive<File>,
+    problems: &mut Vec<ValidationProblem>,
+) -> Result<(), EpubError> {
+    let opf_path = match EBook::parse_container(zip) {
+        Ok(opf_path) => opf_path,
+        Err(EpubError::MissingContainer) => {
+            problems.push(ValidationProblem::MissingContainer);
+            return Ok(());
+        }
+        Err(EpubError::MissingOpf(path)) => {
+            problems.push(ValidationProblem::MissingOpf(path));
+            return Ok(());
+        }
+        Err(e) => {
+            problems.push(malformed(crate::epub::META_INF_CONTAINER_PATH, e));
+            return Ok(());
+        }
+    };
+
+    let encryption = match BookEncryption::from_archive(zip) {
+        Ok(encryption) => encryption,
+        Err(e) => {
+            problems.push(malformed(META_INF_ENCRYPTION_PATH, e));
+            BookEncryption::default()
+        }
+    };
+
+    // DRM is reported once, documents it encrypts can not be told apart from malformed ones
+    if let Some(scheme) = encryption.drm_scheme() {
+        problems.push(ValidationProblem::DrmProtected(scheme));
+    }
+    let is_drm_encrypted =
+        |path: &str| encryption.protection_of(path) == ResourceProtection::DrmEncrypted;
+
+    if is_drm_encrypted(&opf_path) {
+        return Ok(());
+    }
+
+    let opf_content = match EBook::get_archive_file_content(zip, &opf_path) {
+        Ok(opf_content) => opf_content,
+        Err(EpubError::Zip(ZipError::FileNotFound)) => {
+            problems.push(ValidationProblem::MissingOpf(Some(opf_path)));
+            return Ok(());
+        }
+        Err(e) => {
+            problems.push(malformed(&opf_path, e));
+            return Ok(());
+        }
+    };
+
+    let (raw_package, opf_ids) = match (read_raw_package(&opf_content), collect_ids(&opf_content)) {
+        (Ok(raw_package), Ok(opf_ids)) => (raw_package, opf_ids),
+        (Err(e), _) | (_, Err(e)) => {
+            problems.push(malformed(&opf_path, e));
+            return Ok(());
+        }
+    };
+
+    match raw_package.version.as_deref() {
+        Some(version) if KNOWN_VERSIONS.contains(&version) => {}
+        version => problems.push(ValidationProblem::UnknownVersion(
+            version.map(|version| version.to_string()),
+        )),
+    }
+    push_duplicate_ids(&opf_path, opf_ids, problems);
+
+    let manifest = match BookManifest::from_opf(&opf_content) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            problems.push(malformed(&opf_path, e));
+            return Ok(());
+        }
+    };
+
+    for idref in raw_package.spine_idrefs.iter() {
+        if manifest.find_by_id(idref).is_none() {
+            problems.push(ValidationProblem::DanglingSpineReference(idref.clone()));
+        }
+    }
+
+    let file_names: HashSet<String> = zip.file_names().map(|name| name.to_string()).collect();
+
+    for item in manifest.items.iter() {
+        if is_external_href(&item.href) {
+            continue;
+        }
+
+        let path = ResourcePath::resolve(&opf_path, &item.href).path;
+
+        if !file_names.contains(&path) {
+            problems.push(ValidationProblem::MissingManifestResource {
+                id: item.id.clone(),
+                path,
+            });
+        } else if CONTENT_DOCUMENT_MEDIA_TYPES.contains(&item.media_type.as_str())
+            && !is_drm_encrypted(&path)
+        {
+            let content = EBook::get_archive_file_content(zip, &path);
+
+            match content.and_then(|content| collect_ids(&content)) {
+                Ok(ids) => push_duplicate_ids(&path, ids, problems),
+                Err(e) => problems.push(malformed(&path, e)),
+            }
+        }
+    }
+
+    // Spine items are not needed to locate the table of contents, only the `toc` attribute is
+    let spine = BookSpine {
+        items: vec![],
+        toc: raw_package.spine_toc,
+    };
+
+    let toc_path =
+        TableOfContents::locate_in_manifest(&manifest, &spine, TableOfContentsSource::default())
+            .map(|(_, item)| ResourcePath::resolve(&opf_path, &item.href).path);
+
+    if toc_path.as_deref().is_some_and(is_drm_encrypted) {
+        return Ok(());
+    }
+
+    let table_of_contents = TableOfContents::read_table_of_contents_from_manifest(
+        zip,
+        &manifest,
+        &spine,
+        &opf_path,
+        TableOfContentsSource::default(),
+    );
+
+    match table_of_contents {
+        Ok(table_of_contents) if table_of_contents.source.is_none() => {
+            problems.push(ValidationProblem::MissingTableOfContents);
+        }
+        Ok(table_of_contents) => {
+            for item in table_of_contents.items.iter() {
+                if !file_names.contains(&item.path) {
+                    problems.push(ValidationProblem::BrokenTableOfContentsLink {
+                        label: item.label.clone(),
+                        path: item.path.clone(),
+                    });
+                }
+            }
+        }
+        Err(EpubError::MissingTableOfContents) => {
+            problems.push(ValidationProblem::MissingTableOfContents);
+        }
+        Err(e) => problems.push(malformed(&toc_path.unwrap_or_default(), e)),
+    }
+
+    Ok(())
+}
+
+fn malformed(path: &str, error: EpubError) -> ValidationProblem {
+    ValidationProblem::MalformedDocument {
+        path: path.to_string(),
+        message: error.to_string(),
+    }
+}
+
+fn read_raw_package(opf_content: &str) -> Result<RawPackage, EpubError> {
+    let mut reader = Reader::from_str(opf_content);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut raw_package = RawPackage::default();
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                for attribute in e.attributes() {
+                    let attr = attribute?;
+                    let value = attr.unescape_value()?.trim().to_string();
+
+                    match (e.local_name().as_ref(), attr.key) {
+                        (b"package", QName(b"version")) => raw_package.version = Some(value),
+                        (b"spine", QName(b"toc")) => raw_package.spine_toc = Some(value),
+                        (b"itemref", QName(b"idref")) => raw_package.spine_idrefs.push(value),
+                        _ => {}
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(raw_package)
+}
+
+/// Values of all `id` and `xml:id` attributes of the document, in the document order
+fn collect_ids(content: &str) -> Result<Vec<String>, EpubError> {
+    let mut reader = Reader::from_str(content);
+    reader.check_end_names(false);
+
+    let mut ids: Vec<String> = vec![];
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                for attribute in e.attributes().with_checks(false).flatten() {
+                    if matches!(attribute.key, QName(b"id") | QName(b"xml:id")) {
+                        ids.push(String::from_utf8_lossy(&attribute.value).trim().to_string());
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(ids)
+}
+
+fn push_duplicate_ids(path: &str, ids: Vec<String>, problems: &mut Vec<ValidationProblem>) {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut reported: HashSet<String> = HashSet::new();
+
+    for id in ids {
+        if !seen.insert(id.clone()) && reported.insert(id.clone()) {
+            problems.push(ValidationProblem::DuplicateId {
+                path: path.to_string(),
+                id,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use super::*;
+    use crate::{
+        epub::META_INF_CONTAINER_PATH,
+        test_utils::{create_archive, create_epub, CONTAINER_CONTENT},
+    };
+
+    const MOBY_DICK_PATH: &str = "./test_data/epub/moby-dick.epub";
+
+    const NAV_CONTENT: &str = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body><nav epub:type="toc"><ol>
+            <li><a href="chapter_1.xhtml">Chapter 1</a></li>
+            <li><a href="chapter_3.xhtml#start">Chapter 3</a></li>
+        </ol></nav></body>
+    </html>"#;
+
+    fn create_opf(version: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" {} unique-identifier="uid">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+                    <dc:identifier id="uid">urn:uuid:1</dc:identifier>
+                    <dc:title id="uid">Broken</dc:title>
+                </metadata>
+                <manifest>
+                    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+                    <item id="chapter_1" href="chapter_1.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="chapter_2" href="chapter_2.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="remote" href="https://example.com/font.otf" media-type="font/otf"/>
+                </manifest>
+                <spine>
+                    <itemref idref="chapter_1"/>
+                    <itemref idref="chapter_2"/>
+                    <itemref idref="chapter_9"/>
+                </spine>
+            </package>"#,
+            version
+        )
+    }
+
+    #[test]
+    fn validate_epub_should_report_problems_of_broken_book() {
+        let chapter =
+            r#"<html><body><p id="note">1</p><p id="note">2</p><p id="note">3</p></body></html>"#;
+        let path = create_epub(
+            "lore_leaf_validation_broken.epub",
+            &create_opf(r#"version="4.0""#),
+            &[
+                ("nav.xhtml", NAV_CONTENT.as_bytes()),
+                ("chapter_1.xhtml", chapter.as_bytes()),
+            ],
+        );
+
+        let report = EBook::validate_epub(path).unwrap();
+
+        assert_eq!(
+            report.problems,
+            vec![
+                ValidationProblem::UnknownVersion(Some("4.0".to_string())),
+                ValidationProblem::DuplicateId {
+                    path: "OEBPS/content.opf".to_string(),
+                    id: "uid".to_string(),
+                },
+                ValidationProblem::DanglingSpineReference("chapter_9".to_string()),
+                ValidationProblem::DuplicateId {
+                    path: "OEBPS/chapter_1.xhtml".to_string(),
+                    id: "note".to_string(),
+                },
+                ValidationProblem::MissingManifestResource {
+                    id: "chapter_2".to_string(),
+                    path: "OEBPS/chapter_2.xhtml".to_string(),
+                },
+                ValidationProblem::BrokenTableOfContentsLink {
+                    label: "Chapter 3".to_string(),
+                    path: "OEBPS/chapter_3.xhtml".to_string(),
+                },
+            ]
+        );
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn validate_epub_should_report_missing_version() {
+        let path = create_epub(
+            "lore_leaf_validation_version.epub",
+            &create_opf(""),
+            &[
+                ("nav.xhtml", NAV_CONTENT.as_bytes()),
+                ("chapter_1.xhtml", b"<html/>"),
+            ],
+        );
+
+        let report = EBook::validate_epub(path).unwrap();
+
+        assert_eq!(report.problems[0], ValidationProblem::UnknownVersion(None));
+    }
+
+    #[test]
+    fn validate_epub_should_report_drm_once_and_skip_encrypted_documents() {
+        let encryption = r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+            xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+            <enc:EncryptedData>
+                <enc:EncryptionMethod Algorithm="http://www.w3.org/2001/04/xmlenc#aes128-cbc"/>
+                <enc:CipherData><enc:CipherReference URI="OEBPS/chapter_1.xhtml"/></enc:CipherData>
+            </enc:EncryptedData>
+            <enc:EncryptedData>
+                <enc:EncryptionMethod Algorithm="http://www.w3.org/2001/04/xmlenc#aes128-cbc"/>
+                <enc:CipherData><enc:CipherReference URI="OEBPS/chapter_2.xhtml"/></enc:CipherData>
+            </enc:EncryptedData>
+        </encryption>"#;
+        let opf = create_opf(r#"version="3.0""#).replace(r#"<itemref idref="chapter_9"/>"#, "");
+        let path = create_archive(
+            "lore_leaf_validation_drm.epub",
+            &[
+                (MIMETYPE_PATH, EPUB_MIMETYPE.as_bytes()),
+                (META_INF_CONTAINER_PATH, CONTAINER_CONTENT.as_bytes()),
+                (META_INF_ENCRYPTION_PATH, encryption.as_bytes()),
+                ("OEBPS/content.opf", opf.as_bytes()),
+                (
+                    "OEBPS/nav.xhtml",
+                    NAV_CONTENT
+                        .replace("chapter_3.xhtml#start", "chapter_2.xhtml")
+                        .as_bytes(),
+                ),
+                ("OEBPS/chapter_1.xhtml", b"\x8f<\x01&\xfe"),
+                ("OEBPS/chapter_2.xhtml", b"<\x02\xa0>&<"),
+            ],
+        );
+
+        let report = EBook::validate_epub(path).unwrap();
+
+        assert_eq!(
+            report.problems,
+            vec![
+                ValidationProblem::DrmProtected(DrmScheme::Unknown),
+                ValidationProblem::DuplicateId {
+                    path: "OEBPS/content.opf".to_string(),
+                    id: "uid".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_epub_should_report_missing_table_of_contents() {
+        let opf = create_opf(r#"version="3.0""#).replace(r#"properties="nav""#, "");
+        let undeclared = create_epub(
+            "lore_leaf_validation_undeclared_toc.epub",
+            &opf,
+            &[
+                ("nav.xhtml", NAV_CONTENT.as_bytes()),
+                ("chapter_1.xhtml", b"<html/>"),
+                ("chapter_2.xhtml", b"<html/>"),
+            ],
+        );
+        let missing = create_epub(
+            "lore_leaf_validation_missing_toc.epub",
+            &create_opf(r#"version="3.0""#),
+            &[
+                ("chapter_1.xhtml", b"<html/>"),
+                ("chapter_2.xhtml", b"<html/>"),
+            ],
+        );
+
+        let undeclared_report = EBook::validate_epub(undeclared).unwrap();
+        let missing_report = EBook::validate_epub(missing).unwrap();
+
+        assert_eq!(
+            undeclared_report.problems.last(),
+            Some(&ValidationProblem::MissingTableOfContents)
+        );
+        assert_eq!(
+            missing_report.problems[missing_report.problems.len() - 2..],
+            [
+                ValidationProblem::MissingManifestResource {
+                    id: "nav".to_string(),
+                    path: "OEBPS/nav.xhtml".to_string(),
+                },
+                ValidationProblem::MissingTableOfContents,
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_epub_should_report_missing_and_misplaced_mimetype() {
+        let missing = create_archive(
+            "lore_leaf_validation_no_mimetype.epub",
+            &[(META_INF_CONTAINER_PATH, CONTAINER_CONTENT.as_bytes())],
+        );
+        let misplaced = create_archive(
+            "lore_leaf_validation_misplaced_mimetype.epub",
+            &[
+                (META_INF_CONTAINER_PATH, CONTAINER_CONTENT.as_bytes()),
+                ("mimetype", b"application/zip"),
+            ],
+        );
+
+        let missing_report = EBook::validate_epub(missing).unwrap();
+        let misplaced_report = EBook::validate_epub(misplaced).unwrap();
+
+        assert_eq!(
+            missing_report.problems,
+            vec![
+                ValidationProblem::MissingMimetype,
+                ValidationProblem::MissingOpf(Some("OEBPS/content.opf".to_string())),
+            ]
+        );
+        assert_eq!(
+            misplaced_report.problems[..2],
+            [
+                ValidationProblem::MimetypeNotFirst,
+                ValidationProblem::InvalidMimetype("application/zip".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_epub_should_report_compressed_mimetype() {
+        let path = std::env::temp_dir().join("lore_leaf_validation_compressed_mimetype.epub");
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        writer
+            .start_file(
+                MIMETYPE_PATH,
+                zip::write::FileOptions::default().compression_method(CompressionMethod::Deflated),
+            )
+            .unwrap();
+        std::io::Write::write_all(&mut writer, EPUB_MIMETYPE.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let report = EBook::validate_epub(path.to_str().unwrap().to_string()).unwrap();
+
+        assert_eq!(
+            report.problems,
+            vec![
+                ValidationProblem::CompressedMimetype,
+                ValidationProblem::MissingContainer,
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_epub_should_return_error_when_file_is_not_an_archive() {
+        let result = EBook::validate_epub("./test_data/toc/toc.xhtml".to_string());
+
+        assert!(matches!(result, Err(EpubError::Zip(_))));
+    }
+
+    #[test]
+    fn validate_epub_should_not_report_problems_of_moby_dick() {
+        let report = EBook::validate_epub(MOBY_DICK_PATH.to_string()).unwrap();
+
+        assert_eq!(report.problems, vec![]);
+    }
+}
@@ -0,0 +1,933 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use quick_xml::{
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    cover::CoverImage, encoding::decode_document, epub::EBook, error::EpubError,
+    manifest::ManifestItemProperty, resources::ResourcePath, xml::attribute_value,
+};
+
+const MIMETYPE_PATH: &str = "mimetype";
+const EPUB_MIMETYPE: &str = "application/epub+zip";
+const IMAGE_MEDIA_TYPE_PREFIX: &str = "image/";
+const NEW_COVER_ID: &str = "cover-image";
+const MODIFIED_PROPERTY: &str = "dcterms:modified";
+const SECONDS_IN_DAY: u64 = 86_400;
+
+/// Changes written by `EBook::write_epub`. Fields left as `None` are kept as they are in the book.
+#[derive(Debug, Clone, Default)]
+pub struct BookEdits {
+    pub title: Option<String>,
+    /// Replaces all of the creators, in given order
+    pub creators: Option<Vec<String>>,
+    pub language: Option<String>,
+    pub publisher: Option<String>,
+    pub description: Option<String>,
+    pub cover: Option<CoverImage>,
+}
+
+/// Manifest item holding the cover after the edits are written
+#[derive(Debug)]
+struct CoverTarget {
+    id: String,
+    href: String,
+    path: String,
+    /// Media type to write to the manifest, `None` when the declared one is kept
+    media_type: Option<String>,
+    /// The book had no cover image item, so the item and the epub 2 cover meta have to be added
+    is_new: bool,
+}
+
+impl EBook {
+    /// Writes the book with given edits applied to `path`, which may be the path the book was opened from.
+    ///
+    /// Only the OPF file and the cover image are rewritten, every other entry of the archive is copied
+    /// byte-for-byte, with `mimetype` stored uncompressed as the first entry.
+    /// The book keeps reading from the file it was opened from, open it again to see the changes.
+    pub fn write_epub(&self, path: &str, edits: &BookEdits) -> Result<(), EpubError> {
+        if let Some(scheme) = self.drm_scheme() {
+            return Err(EpubError::DrmProtected(scheme));
+        }
+
+        let cover_target = edits
+            .cover
+            .as_ref()
+            .map(|cover| self.cover_target(cover))
+            .transpose()?;
+
+        let opf_content = decode_document(&self.read_resource_at(self.opf_path())?)?;
+        let opf_content = rewrite_opf(&opf_content, edits, cover_target.as_ref())?;
+
+        // The archive is written next to the target first, so the book can be saved over the file it was read from
+        let temporary_path = format!("{}.tmp", path);
+        let result =
+            self.write_archive(&temporary_path, &opf_content, edits, cover_target.as_ref());
+
+        match result {
+            Ok(()) => Ok(fs::rename(&temporary_path, path)?),
+            Err(e) => {
+                let _ = fs::remove_file(&temporary_path);
+                Err(e)
+            }
+        }
+    }
+
+    fn write_archive(
+        &self,
+        path: &str,
+        opf_content: &str,
+        edits: &BookEdits,
+        cover_target: Option<&CoverTarget>,
+    ) -> Result<(), EpubError> {
+        let mut archive = self.archive.lock().expect("Lock poisoned");
+        let mut writer = ZipWriter::new(File::create(path)?);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        writer.start_file(
+            MIMETYPE_PATH,
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        writer.write_all(EPUB_MIMETYPE.as_bytes())?;
+
+        let cover = edits.cover.as_ref().zip(cover_target);
+
+        for index in 0..archive.len() {
+            let file = archive.by_index_raw(index)?;
+
+            match file.name() {
+                MIMETYPE_PATH => {}
+                name if name == self.opf_path() => {
+                    writer.start_file(name, deflated)?;
+                    writer.write_all(opf_content.as_bytes())?;
+                }
+                name => match cover {
+                    Some((cover, target)) if target.path == name => {
+                        writer.start_file(name, deflated)?;
+                        writer.write_all(&cover.data)?;
+                    }
+                    _ => writer.raw_copy_file(file)?,
+                },
+            }
+        }
+
+        if let Some((cover, target)) = cover.filter(|(_, target)| target.is_new) {
+            writer.start_file(target.path.as_str(), deflated)?;
+            writer.write_all(&cover.data)?;
+        }
+
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    /// Finds the manifest image item declared as the cover, or picks a free id and path for a new one
+    fn cover_target(&self, cover: &CoverImage) -> Result<CoverTarget, EpubError> {
+        let declared_cover = self
+            .manifest
+            .find_by_property(&ManifestItemProperty::CoverImage)
+            .into_iter()
+            .chain(
+                self.metadata
+                    .cover
+                    .as_ref()
+                    .and_then(|cover_id| self.manifest.find_by_id(cover_id)),
+            )
+            .find(|item| item.media_type.starts_with(IMAGE_MEDIA_TYPE_PREFIX));
+
+        if let Some(item) = declared_cover {
+            return Ok(CoverTarget {
+                id: item.id.clone(),
+                href: item.href.clone(),
+                path: self.resolve_href(self.opf_path(), &item.href).path,
+                media_type: (item.media_type != cover.media_type).then(|| cover.media_type.clone()),
+                is_new: false,
+            });
+        }
+
+        let archive_paths: HashSet<String> = {
+            let archive = self.archive.lock().expect("Lock poisoned");
+            archive.file_names().map(str::to_string).collect()
+        };
+
+        let extension = extension_of_media_type(&cover.media_type);
+        let (href, path) = (0..)
+            .map(|n| match n {
+                0 => format!("cover.{}", extension),
+                n => format!("cover-{}.{}", n, extension),
+            })
+            .map(|href| {
+                let path = ResourcePath::resolve(self.opf_path(), &href).path;
+                (href, path)
+            })
+            .find(|(_, path)| !archive_paths.contains(path))
+            .unwrap();
+
+        let id = (0..)
+            .map(|n| match n {
+                0 => NEW_COVER_ID.to_string(),
+                n => format!("{}-{}", NEW_COVER_ID, n),
+            })
+            .find(|id| self.manifest.find_by_id(id).is_none())
+            .unwrap();
+
+        Ok(CoverTarget {
+            id,
+            href,
+            path,
+            media_type: Some(cover.media_type.clone()),
+            is_new: true,
+        })
+    }
+}
+
+/// Elements of the metadata which are replaced by the edits, each of them is written at most once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum EditedElement {
+    Title,
+    Language,
+    Publisher,
+    Description,
+}
+
+impl EditedElement {
+    const ALL: [EditedElement; 4] = [
+        EditedElement::Title,
+        EditedElement::Language,
+        EditedElement::Publisher,
+        EditedElement::Description,
+    ];
+
+    fn from_local_name(name: &[u8]) -> Option<EditedElement> {
+        match name {
+            b"title" => Some(EditedElement::Title),
+            b"language" => Some(EditedElement::Language),
+            b"publisher" => Some(EditedElement::Publisher),
+            b"description" => Some(EditedElement::Description),
+            _ => None,
+        }
+    }
+
+    fn dc_name(&self) -> &'static str {
+        match self {
+            EditedElement::Title => "dc:title",
+            EditedElement::Language => "dc:language",
+            EditedElement::Publisher => "dc:publisher",
+            EditedElement::Description => "dc:description",
+        }
+    }
+
+    fn edited_value<'a>(&self, edits: &'a BookEdits) -> Option<&'a String> {
+        match self {
+            EditedElement::Title => edits.title.as_ref(),
+            EditedElement::Language => edits.language.as_ref(),
+            EditedElement::Publisher => edits.publisher.as_ref(),
+            EditedElement::Description => edits.description.as_ref(),
+        }
+    }
+}
+
+/// Streams the OPF through, replacing the edited metadata and the cover manifest item.
+/// Everything else, including whitespace and comments, is written as it was read.
+/// Epub 3 packages also get their `dcterms:modified` set to the time of writing.
+fn rewrite_opf(
+    opf_content: &str,
+    edits: &BookEdits,
+    cover_target: Option<&CoverTarget>,
+) -> Result<String, EpubError> {
+    let creator_changes = CreatorChanges::from_opf(opf_content, edits)?;
+    let main_title_index = main_title_index(opf_content)?;
+    let modified = modified_timestamp(SystemTime::now());
+
+    let mut reader = Reader::from_str(opf_content);
+    let mut writer = Writer::new(Vec::new());
+
+    let mut in_metadata = false;
+    let mut in_manifest = false;
+    let mut is_epub3 = false;
+    let mut written_elements = HashSet::new();
+    let mut is_modified_written = false;
+    let mut creator_index = 0;
+    let mut title_index = 0;
+
+    loop {
+        match reader.read_event()? {
+            Event::Decl(decl) => writer.write_event(Event::Decl(utf8_declaration(&decl)?))?,
+            Event::Start(e) if e.local_name().as_ref() == b"package" => {
+                is_epub3 = package_version(&e)?.starts_with('3');
+                writer.write_event(Event::Start(e))?;
+            }
+            Event::Start(e) if e.local_name().as_ref() == b"metadata" => {
+                in_metadata = true;
+                writer.write_event(Event::Start(e))?;
+            }
+            Event::Start(e) if e.local_name().as_ref() == b"manifest" => {
+                in_manifest = true;
+                writer.write_event(Event::Start(e))?;
+            }
+            Event::End(e) if in_metadata && e.local_name().as_ref() == b"metadata" => {
+                in_metadata = false;
+
+                for element in EditedElement::ALL {
+                    if let Some(value) = element.edited_value(edits) {
+                        if !written_elements.contains(&element) {
+                            write_text_element(&mut writer, element.dc_name(), value)?;
+                        }
+                    }
+                }
+
+                let added_creators = edits.creators.iter().flatten().skip(creator_index);
+                for creator in added_creators {
+                    write_text_element(&mut writer, "dc:creator", creator)?;
+                }
+
+                if is_epub3 && !is_modified_written {
+                    let mut meta = BytesStart::new("meta");
+                    meta.push_attribute(("property", MODIFIED_PROPERTY));
+                    writer.write_event(Event::Start(meta))?;
+                    writer.write_event(Event::Text(BytesText::new(&modified)))?;
+                    writer.write_event(Event::End(BytesEnd::new("meta")))?;
+                    writer.write_event(Event::Text(BytesText::new("\n")))?;
+                }
+
+                if let Some(target) = cover_target.filter(|target| target.is_new) {
+                    let mut meta = BytesStart::new("meta");
+                    meta.push_attribute(("name", "cover"));
+                    meta.push_attribute(("content", target.id.as_str()));
+                    writer.write_event(Event::Empty(meta))?;
+                    writer.write_event(Event::Text(BytesText::new("\n")))?;
+                }
+
+                writer.write_event(Event::End(e))?;
+            }
+            Event::End(e) if in_manifest && e.local_name().as_ref() == b"manifest" => {
+                in_manifest = false;
+
+                if let Some(target) = cover_target.filter(|target| target.is_new) {
+                    let mut item = BytesStart::new("item");
+                    item.push_attribute(("id", target.id.as_str()));
+                    item.push_attribute(("href", target.href.as_str()));
+                    if let Some(media_type) = &target.media_type {
+                        item.push_attribute(("media-type", media_type.as_str()));
+                    }
+                    if is_epub3 {
+                        item.push_attribute(("properties", "cover-image"));
+                    }
+                    writer.write_event(Event::Empty(item))?;
+                    writer.write_event(Event::Text(BytesText::new("\n")))?;
+                }
+
+                writer.write_event(Event::End(e))?;
+            }
+            event @ (Event::Start(_) | Event::Empty(_)) if in_metadata => {
+                let (e, is_empty) = match &event {
+                    Event::Start(e) => (e, false),
+                    Event::Empty(e) => (e, true),
+                    _ => unreachable!(),
+                };
+                let local_name = e.local_name();
+                let is_meta = local_name.as_ref() == b"meta";
+
+                let replacement = if local_name.as_ref() == b"creator" {
+                    creator_index += 1;
+                    edits
+                        .creators
+                        .as_ref()
+                        .map(|creators| creators.get(creator_index - 1))
+                } else if let Some(element) = EditedElement::from_local_name(local_name.as_ref()) {
+                    // Only the main title and the first occurrence of other elements are replaced,
+                    // the following ones, e.g. subtitles or additional languages, are kept
+                    let is_replaced = match element {
+                        EditedElement::Title => {
+                            title_index += 1;
+                            title_index - 1 == main_title_index
+                        }
+                        _ => !written_elements.contains(&element),
+                    };
+
+                    element
+                        .edited_value(edits)
+                        .filter(|_| is_replaced)
+                        .map(|value| {
+                            written_elements.insert(element);
+                            Some(value)
+                        })
+                } else if is_epub3 && is_meta && !is_modified_written && is_modified_meta(e)? {
+                    is_modified_written = true;
+                    Some(Some(&modified))
+                } else {
+                    None
+                };
+
+                let is_removed_meta = is_meta
+                    && (creator_changes.is_removed_meta(e)?
+                        || cover_target.is_some_and(|target| target.is_new)
+                            && attribute_value(e, b"name")?.as_deref() == Some("cover"));
+
+                match replacement {
+                    Some(Some(value)) => {
+                        let end = e.to_end().into_owned();
+                        let start = if creator_changes.renamed_indexes.contains(&creator_index) {
+                            without_attribute(e, b"opf:file-as")?
+                        } else {
+                            e.borrow()
+                        };
+                        writer.write_event(Event::Start(start))?;
+                        writer.write_event(Event::Text(BytesText::new(value)))?;
+                        writer.write_event(Event::End(end))?;
+                        if !is_empty {
+                            reader.read_to_end(e.name())?;
+                        }
+                    }
+                    Some(None) => {
+                        if !is_empty {
+                            reader.read_to_end(e.name())?;
+                        }
+                    }
+                    None if is_removed_meta => {
+                        if !is_empty {
+                            reader.read_to_end(e.name())?;
+                        }
+                    }
+                    None => writer.write_event(event)?,
+                }
+            }
+            Event::Start(e) if in_manifest && e.local_name().as_ref() == b"item" => {
+                writer.write_event(Event::Start(with_cover_media_type(e, cover_target)?))?;
+            }
+            Event::Empty(e) if in_manifest && e.local_name().as_ref() == b"item" => {
+                writer.write_event(Event::Empty(with_cover_media_type(e, cover_target)?))?;
+            }
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+/// Creators changed by the edits. Left out creators lose all of their refining `<meta>` elements,
+/// renamed ones only lose the `file-as` sort name, which no longer matches them.
+#[derive(Debug, Default)]
+struct CreatorChanges {
+    removed_ids: HashSet<String>,
+    renamed_ids: HashSet<String>,
+    /// One-based positions of the renamed creators, matching the counter used while rewriting
+    renamed_indexes: HashSet<usize>,
+}
+
+impl CreatorChanges {
+    fn from_opf(opf_content: &str, edits: &BookEdits) -> Result<CreatorChanges, EpubError> {
+        let mut changes = CreatorChanges::default();
+        let Some(creators) = &edits.creators else {
+            return Ok(changes);
+        };
+
+        let mut reader = Reader::from_str(opf_content);
+        let mut creator_index = 0;
+
+        loop {
+            let (e, name) = match reader.read_event()? {
+                Event::Start(e) if e.local_name().as_ref() == b"creator" => {
+                    let name = reader.read_text(e.name())?;
+                    (e, unescape(&name)?)
+                }
+                Event::Empty(e) if e.local_name().as_ref() == b"creator" => (e, String::new()),
+                Event::Eof => break,
+                _ => continue,
+            };
+
+            let id = attribute_value(&e, b"id")?.map(|id| format!("#{}", id));
+            creator_index += 1;
+
+            match creators.get(creator_index - 1) {
+                None => changes.removed_ids.extend(id),
+                Some(creator) if creator.trim() != name.trim() => {
+                    changes.renamed_ids.extend(id);
+                    changes.renamed_indexes.insert(creator_index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(changes)
+    }
+
+    fn is_removed_meta(&self, e: &BytesStart) -> Result<bool, EpubError> {
+        let Some(refines) = attribute_value(e, b"refines")? else {
+            return Ok(false);
+        };
+
+        let is_file_as = attribute_value(e, b"property")?.as_deref() == Some("file-as");
+
+        Ok(
+            self.removed_ids.contains(&refines)
+                || is_file_as && self.renamed_ids.contains(&refines),
+        )
+    }
+}
+
+/// Position of the main title among the `dc:title` elements: the one refined with `title-type` of `main`,
+/// or the first one when none of them is, matching the title read by `BookMetadata`
+fn main_title_index(opf_content: &str) -> Result<usize, EpubError> {
+    let mut reader = Reader::from_str(opf_content);
+    let mut title_ids = Vec::new();
+    let mut main_title_ids = HashSet::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"title" => {
+                title_ids.push(attribute_value(&e, b"id")?);
+            }
+            Event::Start(e)
+                if e.local_name().as_ref() == b"meta"
+                    && attribute_value(&e, b"property")?.as_deref() == Some("title-type") =>
+            {
+                let title_type = reader.read_text(e.name())?;
+                if let Some(refines) = attribute_value(&e, b"refines")? {
+                    if title_type.trim() == "main" {
+                        main_title_ids.insert(refines.trim_start_matches('#').to_string());
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(title_ids
+        .iter()
+        .position(|id| id.as_ref().is_some_and(|id| main_title_ids.contains(id)))
+        .unwrap_or_default())
+}
+
+fn unescape(text: &str) -> Result<String, EpubError> {
+    Ok(BytesText::from_escaped(text).unescape()?.into_owned())
+}
+
+fn without_attribute(e: &BytesStart, name: &[u8]) -> Result<BytesStart<'static>, EpubError> {
+    let mut element = BytesStart::new(String::from_utf8(e.name().as_ref().to_vec())?);
+    for attribute in e.attributes() {
+        let attribute = attribute?;
+        if attribute.key.as_ref() != name {
+            element.push_attribute(attribute);
+        }
+    }
+
+    Ok(element)
+}
+
+/// The package-wide `dcterms:modified`, as opposed to ones refining other elements
+fn is_modified_meta(e: &BytesStart) -> Result<bool, EpubError> {
+    Ok(
+        attribute_value(e, b"property")?.as_deref() == Some(MODIFIED_PROPERTY)
+            && attribute_value(e, b"refines")?.is_none(),
+    )
+}
+
+/// `CCYY-MM-DDThh:mm:ssZ` UTC timestamp, the format epub 3 requires for `dcterms:modified`
+fn modified_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_date(seconds / SECONDS_IN_DAY);
+    let seconds_of_day = seconds % SECONDS_IN_DAY;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Gregorian calendar date of the given day since the Unix epoch, after Howard Hinnant's `civil_from_days`
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+fn package_version(e: &BytesStart) -> Result<String, EpubError> {
+    Ok(attribute_value(e, b"version")?.unwrap_or_default())
+}
+
+/// The rewritten OPF is always UTF-8, so the declaration can not keep other encodings
+fn utf8_declaration(decl: &BytesDecl) -> Result<BytesDecl<'static>, EpubError> {
+    let version = String::from_utf8(decl.version()?.into_owned())?;
+    let standalone = match decl.standalone() {
+        Some(standalone) => Some(String::from_utf8(standalone?.into_owned())?),
+        None => None,
+    };
+
+    Ok(BytesDecl::new(&version, Some("UTF-8"), standalone.as_deref()).into_owned())
+}
+
+fn with_cover_media_type<'a>(
+    e: BytesStart<'a>,
+    cover_target: Option<&CoverTarget>,
+) -> Result<BytesStart<'a>, EpubError> {
+    let media_type = match cover_target {
+        Some(CoverTarget {
+            id,
+            media_type: Some(media_type),
+            is_new: false,
+            ..
+        }) if attribute_value(&e, b"id")?.as_ref() == Some(id) => media_type,
+        _ => return Ok(e),
+    };
+
+    let mut item = BytesStart::new(String::from_utf8(e.name().as_ref().to_vec())?);
+    for attribute in e.attributes() {
+        let attribute = attribute?;
+        if attribute.key.as_ref() == b"media-type" {
+            item.push_attribute(("media-type", media_type.as_str()));
+        } else {
+            item.push_attribute(attribute);
+        }
+    }
+
+    Ok(item)
+}
+
+fn write_text_element(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    value: &str,
+) -> Result<(), EpubError> {
+    writer.write_event(Event::Start(BytesStart::new(name)))?;
+    writer.write_event(Event::Text(BytesText::new(value)))?;
+    writer.write_event(Event::End(BytesEnd::new(name)))?;
+    writer.write_event(Event::Text(BytesText::new("\n")))?;
+
+    Ok(())
+}
+
+fn extension_of_media_type(media_type: &str) -> &str {
+    match media_type {
+        "image/jpeg" => "jpg",
+        "image/svg+xml" => "svg",
+        _ => media_type
+            .strip_prefix(IMAGE_MEDIA_TYPE_PREFIX)
+            .unwrap_or("img"),
+    }
+}
+
+#[cfg(test)]
+mod writer_tests {
+    use std::io::Read;
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::test_utils::create_epub;
+
+    const MOBY_DICK_PATH: &str = "./test_data/epub/moby-dick.epub";
+
+    const PNG_COVER: &[u8] = b"\x89PNG\r\n\x1a\nnot really a png";
+
+    fn output_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(name)
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn read_entries(path: &str) -> Vec<(String, CompressionMethod, Vec<u8>)> {
+        let mut archive = ZipArchive::new(File::open(path).unwrap()).unwrap();
+
+        (0..archive.len())
+            .map(|index| {
+                let mut file = archive.by_index(index).unwrap();
+                let mut data = Vec::new();
+                file.read_to_end(&mut data).unwrap();
+                (file.name().to_string(), file.compression(), data)
+            })
+            .collect()
+    }
+
+    fn create_epub2_without_cover(name: &str) -> String {
+        let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="uid">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+                    <dc:identifier id="uid">urn:uuid:1</dc:identifier>
+                    <dc:title>Wrong Title</dc:title>
+                    <dc:creator opf:file-as="Doe, Jane" opf:role="aut">Jane Doe</dc:creator>
+                    <dc:creator opf:role="ill">John Doe</dc:creator>
+                </metadata>
+                <manifest>
+                    <item id="chapter_1" href="chapter_1.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+                </manifest>
+                <spine toc="ncx">
+                    <itemref idref="chapter_1"/>
+                </spine>
+            </package>"#;
+        let ncx = r#"<ncx><navMap>
+                <navPoint id="chapter_1"><navLabel><text>Chapter 1</text></navLabel><content src="chapter_1.xhtml"/></navPoint>
+            </navMap></ncx>"#;
+
+        create_epub(
+            name,
+            opf,
+            &[
+                ("chapter_1.xhtml", b"<html><body><p>Text</p></body></html>"),
+                ("toc.ncx", ncx.as_bytes()),
+            ],
+        )
+    }
+
+    #[test]
+    fn should_write_edited_metadata() {
+        let ebook = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let path = output_path("writer_edited_metadata.epub");
+        let edits = BookEdits {
+            title: Some("Moby-Dick; or, The Whale".to_string()),
+            creators: Some(vec![
+                "Herman Melville".to_string(),
+                "Rockwell Kent".to_string(),
+            ]),
+            description: Some("A whale & a captain".to_string()),
+            ..Default::default()
+        };
+
+        let before_writing = modified_timestamp(SystemTime::now());
+        ebook.write_epub(&path, &edits).unwrap();
+        let written = EBook::read_epub(path).unwrap();
+
+        assert_eq!(
+            written.metadata.title,
+            Some("Moby-Dick; or, The Whale".to_string())
+        );
+        let creator_names: Vec<&str> = written
+            .metadata
+            .creators
+            .iter()
+            .map(|creator| creator.name.as_str())
+            .collect();
+        assert_eq!(creator_names, vec!["Herman Melville", "Rockwell Kent"]);
+        assert_eq!(
+            written.metadata.creators[0].file_as,
+            Some("MELVILLE, HERMAN".to_string())
+        );
+        assert_eq!(
+            written.metadata.description,
+            Some("A whale & a captain".to_string())
+        );
+        assert_eq!(written.metadata.publisher, ebook.metadata.publisher);
+        assert_eq!(written.metadata.identifier, ebook.metadata.identifier);
+
+        let modified = written.metadata.modified.unwrap();
+        assert_ne!(Some(&modified), ebook.metadata.modified.as_ref());
+        assert!(modified >= before_writing, "{}", modified);
+        assert_eq!(modified.len(), "2012-01-18T12:47:00Z".len());
+    }
+
+    #[test]
+    fn should_keep_other_entries_and_store_mimetype_first() {
+        let ebook = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let path = output_path("writer_kept_entries.epub");
+        let edits = BookEdits {
+            title: Some("Moby Dick".to_string()),
+            ..Default::default()
+        };
+
+        ebook.write_epub(&path, &edits).unwrap();
+
+        let original = read_entries(MOBY_DICK_PATH);
+        let written = read_entries(&path);
+
+        assert_eq!(written[0].0, "mimetype");
+        assert_eq!(written[0].1, CompressionMethod::Stored);
+        assert_eq!(written[0].2, b"application/epub+zip");
+        assert_eq!(written.len(), original.len());
+
+        for (name, compression, data) in original
+            .iter()
+            .filter(|(name, _, _)| name != ebook.opf_path())
+        {
+            let written_entry = written
+                .iter()
+                .find(|(written_name, _, _)| written_name == name)
+                .unwrap();
+            assert_eq!(&written_entry.1, compression, "{}", name);
+            assert_eq!(&written_entry.2, data, "{}", name);
+        }
+
+        let report = EBook::validate_epub(path).unwrap();
+        assert!(report.is_valid(), "{:?}", report.problems);
+    }
+
+    #[test]
+    fn should_drop_refining_meta_of_removed_and_renamed_creators() {
+        let ebook = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let edits = BookEdits {
+            creators: Some(vec![]),
+            ..Default::default()
+        };
+        let opf = decode_document(&ebook.read_resource_at(ebook.opf_path()).unwrap()).unwrap();
+
+        let removed = rewrite_opf(&opf, &edits, None).unwrap();
+
+        assert!(!removed.contains("dc:creator"));
+        assert!(!removed.contains(r##"refines="#creator""##));
+        assert!(removed.contains(r##"refines="#contrib1""##));
+
+        let edits = BookEdits {
+            creators: Some(vec!["Melville".to_string()]),
+            ..Default::default()
+        };
+
+        let renamed = rewrite_opf(&opf, &edits, None).unwrap();
+
+        assert!(renamed.contains(r#"<dc:creator id="creator">Melville</dc:creator>"#));
+        assert!(!renamed.contains("MELVILLE, HERMAN"));
+        assert!(renamed.contains(
+            r##"<meta refines="#creator" property="role" scheme="marc:relators">aut</meta>"##
+        ));
+    }
+
+    #[test]
+    fn should_replace_main_title_and_keep_other_titles_and_languages() {
+        let opf = r##"<?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+                    <dc:identifier id="uid">urn:uuid:1</dc:identifier>
+                    <dc:title id="sub">The Whale</dc:title>
+                    <meta refines="#sub" property="title-type">subtitle</meta>
+                    <dc:title id="main">Wrong Title</dc:title>
+                    <meta refines="#main" property="title-type">main</meta>
+                    <dc:language>en</dc:language>
+                    <dc:language>fr</dc:language>
+                </metadata>
+            </package>"##;
+        let edits = BookEdits {
+            title: Some("Moby-Dick".to_string()),
+            language: Some("de".to_string()),
+            ..Default::default()
+        };
+
+        let rewritten = rewrite_opf(opf, &edits, None).unwrap();
+        let metadata = crate::metadata::BookMetadata::from_opf(&rewritten).unwrap();
+
+        assert_eq!(metadata.title, Some("Moby-Dick".to_string()));
+        assert_eq!(
+            metadata.titles,
+            vec!["The Whale".to_string(), "Moby-Dick".to_string()]
+        );
+        assert_eq!(metadata.languages, vec!["de".to_string(), "fr".to_string()]);
+        assert!(metadata.modified.is_some());
+        assert!(rewritten.contains(r#"<dc:title id="sub">The Whale</dc:title>"#));
+    }
+
+    #[test]
+    fn should_format_modified_timestamp_in_utc() {
+        let leap_day = UNIX_EPOCH + std::time::Duration::from_secs(951_827_696);
+
+        assert_eq!(modified_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(modified_timestamp(leap_day), "2000-02-29T12:34:56Z");
+    }
+
+    #[test]
+    fn should_replace_existing_cover_image() {
+        let ebook = EBook::read_epub(MOBY_DICK_PATH.to_string()).unwrap();
+        let path = output_path("writer_replaced_cover.epub");
+        let cover = CoverImage {
+            data: PNG_COVER.to_vec(),
+            media_type: "image/png".to_string(),
+        };
+        let edits = BookEdits {
+            cover: Some(cover.clone()),
+            ..Default::default()
+        };
+
+        ebook.write_epub(&path, &edits).unwrap();
+        let written = EBook::read_epub(path).unwrap();
+
+        assert_eq!(written.cover().unwrap(), Some(cover));
+        let cover_item = written.manifest.find_by_id("cover-image").unwrap();
+        assert_eq!(cover_item.href, "images/9780316000000.jpg");
+        assert_eq!(written.manifest.items.len(), ebook.manifest.items.len());
+    }
+
+    #[test]
+    fn should_add_cover_and_missing_metadata_to_epub2() {
+        let ebook = EBook::read_epub(create_epub2_without_cover("writer_epub2.epub")).unwrap();
+        let path = output_path("writer_epub2_edited.epub");
+        let cover = CoverImage {
+            data: PNG_COVER.to_vec(),
+            media_type: "image/png".to_string(),
+        };
+        let edits = BookEdits {
+            title: Some("Right Title".to_string()),
+            creators: Some(vec!["Jane Roe".to_string()]),
+            language: Some("en".to_string()),
+            publisher: Some("LoreLeaf".to_string()),
+            cover: Some(cover.clone()),
+            ..Default::default()
+        };
+
+        ebook.write_epub(&path, &edits).unwrap();
+        let written = EBook::read_epub(path.clone()).unwrap();
+
+        assert_eq!(written.metadata.title, Some("Right Title".to_string()));
+        assert_eq!(written.metadata.creators.len(), 1);
+        assert_eq!(written.metadata.creators[0].name, "Jane Roe");
+        assert_eq!(written.metadata.creators[0].file_as, None);
+        assert_eq!(written.metadata.creators[0].roles, vec!["aut".to_string()]);
+        assert_eq!(written.metadata.language, Some("en".to_string()));
+        assert_eq!(written.metadata.publisher, Some("LoreLeaf".to_string()));
+        assert_eq!(written.metadata.cover, Some("cover-image".to_string()));
+        assert_eq!(written.metadata.modified, None);
+        assert_eq!(written.cover().unwrap(), Some(cover));
+
+        let cover_item = written.manifest.find_by_id("cover-image").unwrap();
+        assert_eq!(cover_item.href, "cover.png");
+        assert!(cover_item.properties.is_empty());
+
+        let report = EBook::validate_epub(path).unwrap();
+        assert!(report.is_valid(), "{:?}", report.problems);
+    }
+
+    #[test]
+    fn should_overwrite_the_file_the_book_was_read_from() {
+        let path = create_epub2_without_cover("writer_overwritten.epub");
+        let ebook = EBook::read_epub(path.clone()).unwrap();
+        let edits = BookEdits {
+            title: Some("Right Title".to_string()),
+            ..Default::default()
+        };
+
+        ebook.write_epub(&path, &edits).unwrap();
+        let written = EBook::read_epub(path.clone()).unwrap();
+
+        assert_eq!(written.metadata.title, Some("Right Title".to_string()));
+        assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+    }
+}
@@ -0,0 +1,11 @@
+use quick_xml::events::BytesStart;
+
+use crate::error::EpubError;
+
+/// Unescaped value of the attribute with given qualified name, e.g. `opf:role`, `None` when the element does not have it
+pub(crate) fn attribute_value(e: &BytesStart, name: &[u8]) -> Result<Option<String>, EpubError> {
+    match e.try_get_attribute(name)? {
+        Some(attr) => Ok(Some(attr.unescape_value()?.to_string())),
+        None => Ok(None),
+    }
+}
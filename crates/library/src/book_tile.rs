@@ -29,7 +29,11 @@ impl Default for BookTileStyle {
 }
 
 impl BookTileBundle {
-    pub fn new(book: Book, style: Option<BookTileStyle>) -> BookTileBundle {
+    pub fn new(
+        book: Book,
+        style: Option<BookTileStyle>,
+        cover: Option<Handle<Image>>,
+    ) -> BookTileBundle {
         let style = match style {
             Some(style) => style,
             None => BookTileStyle::default(),
@@ -49,6 +53,15 @@ impl BookTileBundle {
             ..default()
         };
 
+        let button = match cover {
+            Some(cover) => ButtonBundle {
+                image: UiImage::new(cover),
+                background_color: BackgroundColor::from(Color::WHITE),
+                ..button
+            },
+            None => button,
+        };
+
         Self {
             button,
             book,
@@ -1,15 +1,27 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        texture::{CompressedImageFormats, ImageSampler, ImageType},
+    },
+};
 use common::states::NavigationState;
 use directories::UserDirs;
-use epub::{epub::EBook, reader::EBookReader};
+use epub::{
+    cover::CoverImage, epub::EBook, error::EpubError, reader::EBookReader,
+    validation::ValidationReport,
+};
 use std::{
     fs::{self, DirEntry},
     path::Path,
+    sync::Arc,
 };
 
 use crate::book_tile::BookTileBundle;
 
 const UNKNOWN: &str = "UNKNOWN";
+/// Only the first problems fit on the tile of the book
+const DISPLAYED_PROBLEMS: usize = 3;
 const BOOK_FORMATS: [&str; 1] = ["epub"];
 
 #[derive(Resource)]
@@ -78,16 +90,72 @@ pub struct Book {
     pub name: String,
     author: String,
     pub path: String,
+    cover: Option<Arc<CoverImage>>,
+    /// Why the book can not be read, e.g. DRM protection. Such books are still displayed in the library.
+    unsupported_reason: Option<String>,
 }
 
 impl Book {
     pub fn from_ebook(ebook: EBook) -> Book {
+        //TODO: Broken cover should not hide the book, but it might be worth showing the reason somewhere
+        let cover = ebook.cover().ok().flatten().map(Arc::new);
+        let unsupported_reason = ebook
+            .drm_scheme()
+            .map(|scheme| format!("Protected with {}, which is not supported", scheme));
+
         Self {
             name: ebook.metadata.title.unwrap_or(UNKNOWN.to_string()),
             author: ebook.metadata.creator.unwrap_or(UNKNOWN.to_string()),
             path: ebook.path,
+            cover,
+            unsupported_reason,
+        }
+    }
+
+    /// Book that could not be opened, it is named after its file. The error, followed by the problems
+    /// found by the validation, is kept as the reason.
+    pub fn unsupported(path: &str, error: &EpubError, report: &ValidationReport) -> Book {
+        let problems = report
+            .problems
+            .iter()
+            .take(DISPLAYED_PROBLEMS)
+            .map(|problem| format!("\n- {}", problem))
+            .collect::<String>();
+        let name = Path::new(path)
+            .file_stem()
+            .map(|file_stem| file_stem.to_string_lossy().to_string())
+            .unwrap_or(UNKNOWN.to_string());
+
+        Self {
+            name,
+            author: UNKNOWN.to_string(),
+            path: path.to_string(),
+            cover: None,
+            unsupported_reason: Some(format!("{}{}", error, problems)),
         }
     }
+
+    pub fn unsupported_reason(&self) -> Option<&str> {
+        self.unsupported_reason.as_deref()
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.unsupported_reason.is_none()
+    }
+
+    fn create_cover_image(&self) -> Option<Image> {
+        let cover = self.cover.as_ref()?;
+
+        Image::from_buffer(
+            &cover.data,
+            ImageType::MimeType(&cover.media_type),
+            CompressedImageFormats::NONE,
+            true,
+            ImageSampler::Default,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .ok()
+    }
 }
 
 impl PartialEq for Book {
@@ -116,10 +184,16 @@ pub fn detect_books_in_library(
                 .map(|dir_entry| {
                     //TODO: Fix that strange conversion to String
                     let epub_path = dir_entry.path().to_str().unwrap().to_string();
-                    EBook::read_epub(epub_path)
+
+                    match EBook::read_epub(epub_path.clone()) {
+                        Ok(ebook) => Book::from_ebook(ebook),
+                        Err(e) => {
+                            let report =
+                                EBook::validate_epub(epub_path.clone()).unwrap_or_default();
+                            Book::unsupported(&epub_path, &e, &report)
+                        }
+                    }
                 })
-                .filter_map(|x| x.ok())
-                .map(Book::from_ebook)
                 .collect();
 
             user_library.set_detected(user_books);
@@ -192,6 +266,7 @@ pub fn refresh_user_library_on_ui(
     mut commands: Commands,
     menu_data: Res<LibraryViewData>,
     mut user_library: ResMut<UserLibrary>,
+    mut images: ResMut<Assets<Image>>,
 ) {
     //TODO: Try different font since this one is not displaying Polish letters correctly
     for book_to_add in user_library.to_add.iter() {
@@ -230,8 +305,34 @@ pub fn refresh_user_library_on_ui(
             },
         ];
 
+        let unsupported_sections = book_to_add.unsupported_reason().map(|reason| {
+            vec![
+                TextSection {
+                    value: "\nUnsupported: \n".to_string(),
+                    style: TextStyle {
+                        font_size: 20.0,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                },
+                TextSection {
+                    value: reason.to_string(),
+                    style: TextStyle {
+                        font_size: 16.0,
+                        color: Color::MAROON,
+                        ..default()
+                    },
+                },
+            ]
+        });
+        let sections = [sections, unsupported_sections.unwrap_or_default()].concat();
+
+        let cover = book_to_add
+            .create_cover_image()
+            .map(|image| images.add(image));
+
         let entity = commands
-            .spawn(BookTileBundle::new(book_to_add.to_owned(), None))
+            .spawn(BookTileBundle::new(book_to_add.to_owned(), None, cover))
             .with_children(|parent| {
                 parent.spawn(TextBundle::from_sections(sections));
             })
@@ -253,6 +354,12 @@ pub fn book_interaction_system(
     for (interaction, book) in &mut interaction_query {
         if let Interaction::Pressed = *interaction {
             println!("{:?}", &book.path);
+
+            if let Some(reason) = book.unsupported_reason() {
+                warn!("Book {} can not be opened: {}", book.path, reason);
+                continue;
+            }
+
             user_library.set_selected_for_reading(book.clone());
             next_navigation.set(NavigationState::Reader);
         }
@@ -280,6 +387,66 @@ mod tests {
     }
 }
 
+#[cfg(test)]
+mod book_tests {
+    use epub::validation::ValidationProblem;
+
+    use super::*;
+
+    #[test]
+    fn unsupported_should_name_book_after_file_and_keep_reason() {
+        let error = EpubError::DrmProtected(epub::encryption::DrmScheme::AdobeAdept);
+
+        let book = Book::unsupported(
+            "/home/reader/Documents/Dragoneza.epub",
+            &error,
+            &ValidationReport::default(),
+        );
+
+        assert_eq!(book.name, "Dragoneza");
+        assert!(!book.is_supported());
+        assert_eq!(
+            book.unsupported_reason(),
+            Some("epub is protected with Adobe DRM and can not be read")
+        );
+    }
+
+    #[test]
+    fn unsupported_should_list_first_problems_of_validation_report() {
+        let error = EpubError::DanglingSpineReference("chapter_9".to_string());
+        let report = ValidationReport {
+            problems: vec![
+                ValidationProblem::MissingMimetype,
+                ValidationProblem::DanglingSpineReference("chapter_9".to_string()),
+                ValidationProblem::UnknownVersion(None),
+                ValidationProblem::CompressedMimetype,
+            ],
+        };
+
+        let book = Book::unsupported("Broken.epub", &error, &report);
+
+        assert_eq!(
+            book.unsupported_reason(),
+            Some(
+                "spine references item chapter_9 which is missing from the manifest\n\
+                - mimetype file is missing\n\
+                - spine references item chapter_9 missing from the manifest\n\
+                - package does not declare its version"
+            )
+        );
+    }
+
+    #[test]
+    fn from_ebook_should_create_supported_book_when_there_is_no_drm() {
+        let ebook = EBook::read_epub("../epub/test_data/epub/moby-dick.epub".to_string()).unwrap();
+
+        let book = Book::from_ebook(ebook);
+
+        assert!(book.is_supported());
+        assert_eq!(book.unsupported_reason(), None);
+    }
+}
+
 #[cfg(test)]
 mod check_differences_in_books_on_ui_tests {
     use super::*;
@@ -302,17 +469,23 @@ mod check_differences_in_books_on_ui_tests {
                 name: "Name 1".to_string(),
                 author: "Author 1".to_string(),
                 path: "".to_string(),
+                cover: None,
+                unsupported_reason: None,
             },
             Book {
                 name: "Name 2".to_string(),
                 author: "Author 2".to_string(),
                 path: "".to_string(),
+                cover: None,
+                unsupported_reason: None,
             },
         ];
         let displayed = vec![Book {
             name: "Name 1".to_string(),
             author: "Author 1".to_string(),
             path: "".to_string(),
+            cover: None,
+            unsupported_reason: None,
         }];
         user_library.set_detected(detected);
         user_library.set_displayed(displayed);
@@ -330,17 +503,23 @@ mod check_differences_in_books_on_ui_tests {
             name: "Name 1".to_string(),
             author: "Author 1".to_string(),
             path: "".to_string(),
+            cover: None,
+            unsupported_reason: None,
         }];
         let displayed = vec![
             Book {
                 name: "Name 1".to_string(),
                 author: "Author 1".to_string(),
                 path: "".to_string(),
+                cover: None,
+                unsupported_reason: None,
             },
             Book {
                 name: "Name 2".to_string(),
                 author: "Author 2".to_string(),
                 path: "".to_string(),
+                cover: None,
+                unsupported_reason: None,
             },
         ];
         user_library.set_detected(detected);
@@ -359,11 +538,15 @@ mod check_differences_in_books_on_ui_tests {
             name: "Name 2".to_string(),
             author: "Author 2".to_string(),
             path: "".to_string(),
+            cover: None,
+            unsupported_reason: None,
         }];
         let displayed = vec![Book {
             name: "Name 3".to_string(),
             author: "Author 3".to_string(),
             path: "".to_string(),
+            cover: None,
+            unsupported_reason: None,
         }];
         user_library.set_detected(books);
         user_library.set_displayed(displayed);
@@ -381,6 +564,8 @@ mod check_differences_in_books_on_ui_tests {
             name: "Name".to_string(),
             author: "Author".to_string(),
             path: "./123".to_string(),
+            cover: None,
+            unsupported_reason: None,
         };
 
         user_library.set_selected_for_reading(book_clicked.clone());
@@ -397,11 +582,15 @@ mod check_differences_in_books_on_ui_tests {
                 name: "Name 1".to_string(),
                 author: "Author 1".to_string(),
                 path: "./111".to_string(),
+                cover: None,
+                unsupported_reason: None,
             },
             Book {
                 name: "Name 2".to_string(),
                 author: "Author 2".to_string(),
                 path: "./222".to_string(),
+                cover: None,
+                unsupported_reason: None,
             },
         ];
 
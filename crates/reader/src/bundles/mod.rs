@@ -1,14 +1,20 @@
 use bevy::{
+    asset::Handle,
+    ecs::component::Component,
     prelude::default,
     scene::ron::de,
-    text::{Text, TextLayoutInfo, TextStyle},
+    text::{Font, JustifyText, Text, TextLayoutInfo, TextSection, TextStyle},
     ui::{
         node_bundles::{NodeBundle, TextBundle},
         widget::TextFlags,
-        Style,
+        Style, UiRect, Val,
     },
 };
-use common::text::TEXT_COLOR;
+use common::text::{LINK_COLOR, TEXT_COLOR};
+use epub::{
+    chapters::chapter_node::{ChapterNode, TextRun},
+    styles::computed_style::{ComputedStyle, Length, TextAlign},
+};
 
 #[derive(Debug)]
 pub enum ChapterNodeComponent {
@@ -25,13 +31,15 @@ pub struct HeadingComponentBundle {
 }
 
 impl HeadingComponentBundle {
+    const FONT_SIZE: f32 = 60.0;
+
     pub fn new(content: &str) -> Self {
         Self {
             node: TextBundle {
                 text: Text::from_section(
                     content,
                     TextStyle {
-                        font_size: 60.0,
+                        font_size: Self::FONT_SIZE,
                         color: TEXT_COLOR,
                         ..default()
                     },
@@ -41,25 +49,99 @@ impl HeadingComponentBundle {
             },
         }
     }
+
+    pub fn with_computed_style(mut self, computed_style: &ComputedStyle) -> Self {
+        apply_computed_style(&mut self.node, computed_style, Self::FONT_SIZE);
+        self
+    }
+
+    pub fn with_font(mut self, font: Option<Handle<Font>>) -> Self {
+        apply_font(&mut self.node, font);
+        self
+    }
+}
+
+/// Link of a paragraph, followed by the reader when the paragraph is clicked
+#[derive(Component, Debug, Clone)]
+pub struct ChapterLink {
+    pub href: String,
+    /// Links to notes open the note in a popup instead of leaving the chapter
+    pub is_note_reference: bool,
 }
 
 #[derive(Debug)]
 pub struct ParagraphComponentBundle {
     pub node: TextBundle,
+    pub link: Option<ChapterLink>,
 }
 
 impl ParagraphComponentBundle {
+    const FONT_SIZE: f32 = 20.0;
+
     pub fn new(content: &str) -> Self {
-        Self {
-            node: TextBundle {
-                text: Text::from_section(
-                    content,
+        Self::from_text(Text::from_section(
+            content,
+            TextStyle {
+                font_size: Self::FONT_SIZE,
+                color: TEXT_COLOR,
+                ..default()
+            },
+        ))
+    }
+
+    /// Creates a paragraph keeping inline elements in place, every run of text becomes a separate section.
+    /// Runs without a font of their own are displayed with the default one.
+    pub fn from_text_runs(
+        runs: &[TextRun],
+        font_of_run: impl Fn(&TextRun) -> Option<Handle<Font>>,
+    ) -> Self {
+        let sections = runs
+            .iter()
+            .map(|run| {
+                let color = if run.is_inside("a") {
+                    LINK_COLOR
+                } else {
+                    TEXT_COLOR
+                };
+
+                TextSection::new(
+                    run.text.as_str(),
                     TextStyle {
-                        font_size: 20.0,
-                        color: TEXT_COLOR,
-                        ..default()
+                        font: font_of_run(run).unwrap_or_default(),
+                        font_size: Self::FONT_SIZE,
+                        color,
                     },
-                ),
+                )
+            })
+            .collect::<Vec<TextSection>>();
+
+        Self::from_text(Text::from_sections(sections))
+    }
+
+    pub fn with_computed_style(mut self, computed_style: &ComputedStyle) -> Self {
+        apply_computed_style(&mut self.node, computed_style, Self::FONT_SIZE);
+        self
+    }
+
+    pub fn with_font(mut self, font: Option<Handle<Font>>) -> Self {
+        apply_font(&mut self.node, font);
+        self
+    }
+
+    pub fn with_link(mut self, link: Option<&ChapterNode>) -> Self {
+        self.link = link.and_then(|link| {
+            Some(ChapterLink {
+                href: link.href()?.to_string(),
+                is_note_reference: link.is_note_reference(),
+            })
+        });
+        self
+    }
+
+    fn from_text(text: Text) -> Self {
+        Self {
+            node: TextBundle {
+                text,
                 style: Style {
                     display: bevy::ui::Display::Flex,
                     align_content: bevy::ui::AlignContent::FlexStart,
@@ -68,6 +150,44 @@ impl ParagraphComponentBundle {
                 },
                 ..Default::default()
             },
+            link: None,
+        }
+    }
+}
+
+/// Applies alignment, margins and small caps of the publisher's stylesheets to the text node
+fn apply_computed_style(node: &mut TextBundle, computed_style: &ComputedStyle, font_size: f32) {
+    //TODO: Bevy has no text-indent
+    node.text.justify = match computed_style.text_align {
+        TextAlign::Center => JustifyText::Center,
+        TextAlign::Right | TextAlign::End => JustifyText::Right,
+        TextAlign::Start | TextAlign::Left | TextAlign::Justify => JustifyText::Left,
+    };
+
+    let to_val = |length: Length| match length {
+        Length::Auto => Val::Auto,
+        Length::Percent(percent) => Val::Percent(percent),
+        length => Val::Px(length.to_px(font_size, font_size, 0.0)),
+    };
+    node.style.margin = UiRect {
+        left: to_val(computed_style.margin.left),
+        right: to_val(computed_style.margin.right),
+        top: to_val(computed_style.margin.top),
+        bottom: to_val(computed_style.margin.bottom),
+    };
+
+    if computed_style.small_caps {
+        for section in node.text.sections.iter_mut() {
+            section.value = section.value.to_uppercase();
+            section.style.font_size *= 0.8;
+        }
+    }
+}
+
+fn apply_font(node: &mut TextBundle, font: Option<Handle<Font>>) {
+    if let Some(font) = font {
+        for section in node.text.sections.iter_mut() {
+            section.style.font = font.clone();
         }
     }
 }
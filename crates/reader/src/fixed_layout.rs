@@ -0,0 +1,102 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        texture::{CompressedImageFormats, ImageSampler, ImageType},
+    },
+};
+use epub::rendition::{FixedLayoutPage, Viewport};
+
+/// Image of a fixed-layout page, resized by `scale_fixed_layout_pages` to fit its frame
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FixedLayoutPageImage {
+    viewport: Viewport,
+}
+
+/// Page of a pre-paginated spine item, shown whole instead of running it through the paragraph pipeline
+pub struct FixedLayoutPageBundle {
+    frame: NodeBundle,
+    image: ImageBundle,
+    page_image: FixedLayoutPageImage,
+}
+
+impl FixedLayoutPageBundle {
+    /// Frame takes the height of the window left by the toolbar, which takes about 6% of it
+    const FRAME_HEIGHT: Val = Val::Vh(94.0);
+
+    /// Decodes the image of the page, returns `None` when the page has no image or it is in an unsupported format
+    pub fn load(page: &FixedLayoutPage, image_assets: &mut Assets<Image>) -> Option<Self> {
+        let page_image = page.image.as_ref()?;
+        let image = match Image::from_buffer(
+            &page_image.data,
+            ImageType::MimeType(&page_image.media_type),
+            CompressedImageFormats::NONE,
+            true,
+            ImageSampler::Default,
+            RenderAssetUsages::default(),
+        ) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Unsupported image of page {}: {:?}", page.path, e);
+                return None;
+            }
+        };
+
+        // Pages without a viewport are shown with the dimensions of their image
+        let viewport = page.viewport.unwrap_or(Viewport {
+            width: image.width() as f32,
+            height: image.height() as f32,
+        });
+
+        Some(Self {
+            frame: NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Self::FRAME_HEIGHT,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                ..default()
+            },
+            image: ImageBundle {
+                image: UiImage::new(image_assets.add(image)),
+                ..default()
+            },
+            page_image: FixedLayoutPageImage { viewport },
+        })
+    }
+
+    pub fn spawn(self, parent: &mut ChildBuilder) -> Entity {
+        parent
+            .spawn(self.frame)
+            .with_children(|frame| {
+                frame.spawn((self.image, self.page_image));
+            })
+            .id()
+    }
+}
+
+/// Scales page images to the size of their frames, keeping the aspect ratio of the page viewport
+pub fn scale_fixed_layout_pages(
+    mut page_images: Query<(&FixedLayoutPageImage, &Parent, &mut Style)>,
+    frames: Query<&Node>,
+) {
+    for (page_image, parent, mut style) in page_images.iter_mut() {
+        let Ok(frame) = frames.get(parent.get()) else {
+            continue;
+        };
+
+        let frame_size = frame.size();
+        let (width, height) = page_image
+            .viewport
+            .scaled_to_fit(frame_size.x, frame_size.y);
+
+        // Style is only written on change, so the layout is not recomputed every frame
+        if style.width != Val::Px(width) || style.height != Val::Px(height) {
+            style.width = Val::Px(width);
+            style.height = Val::Px(height);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+use epub::{epub::EBook, fonts::EmbeddedFont, styles::computed_style::ComputedStyle};
+
+/// Fonts embedded in the book that is currently read, registered as Bevy assets.
+/// Assets are dropped together with the resource, when the reader is closed.
+#[derive(Resource, Default, Clone)]
+pub struct BookFonts {
+    fonts: Vec<(EmbeddedFont, Handle<Font>)>,
+}
+
+impl BookFonts {
+    pub fn load(ebook: &EBook, font_assets: &mut Assets<Font>) -> Self {
+        let embedded_fonts = ebook.embedded_fonts().unwrap_or_else(|e| {
+            error!("Error reading embedded fonts: {:?}", e);
+            vec![]
+        });
+
+        let fonts = embedded_fonts
+            .into_iter()
+            .filter_map(|embedded_font| {
+                let bytes = match ebook.read_font(&embedded_font) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        error!("Error reading font {}: {:?}", embedded_font.path, e);
+                        return None;
+                    }
+                };
+
+                // Only TrueType and OpenType fonts are supported, WOFF ones are skipped
+                match Font::try_from_bytes(bytes) {
+                    Ok(font) => Some((embedded_font, font_assets.add(font))),
+                    Err(e) => {
+                        warn!("Unsupported font {}: {:?}", embedded_font.path, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self { fonts }
+    }
+
+    /// Font of the first family embedded in the book, with the closest style and weight
+    pub fn find(&self, computed_style: &ComputedStyle) -> Option<Handle<Font>> {
+        computed_style.font_family.iter().find_map(|family| {
+            self.fonts
+                .iter()
+                .filter(|(font, _)| font.family.eq_ignore_ascii_case(family))
+                .min_by_key(|(font, _)| {
+                    (
+                        font.font_style != computed_style.font_style,
+                        font.font_weight.abs_diff(computed_style.font_weight),
+                    )
+                })
+                .map(|(_, handle)| handle.clone())
+        })
+    }
+}
+
+pub fn remove_book_fonts(mut commands: Commands) {
+    commands.remove_resource::<BookFonts>();
+}
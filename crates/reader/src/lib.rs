@@ -1,4 +1,7 @@
 mod bundles;
+mod fixed_layout;
+mod fonts;
+mod note_popup;
 pub mod plugin;
 mod toolbar;
 mod toolbar_buttons;
@@ -0,0 +1,70 @@
+use bevy::{ecs::system::EntityCommands, prelude::*, ui::FocusPolicy};
+use common::text::TEXT_COLOR;
+use epub::notes::Note;
+
+/// Popup showing a note over the chapter, so that reading continues at the same place once it is closed
+#[derive(Component, Debug)]
+pub struct NotePopup;
+
+#[derive(Bundle)]
+pub struct NotePopupBundle {
+    node: NodeBundle,
+    interaction: Interaction,
+    popup: NotePopup,
+}
+
+impl NotePopupBundle {
+    const FONT_SIZE: f32 = 18.0;
+
+    fn new() -> Self {
+        Self {
+            node: NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(10.0),
+                    bottom: Val::Percent(5.0),
+                    width: Val::Percent(80.0),
+                    max_height: Val::Percent(40.0),
+                    padding: UiRect::all(Val::Px(16.0)),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                background_color: BackgroundColor::from(Color::rgba(0.1, 0.1, 0.1, 0.95)),
+                z_index: ZIndex::Global(10),
+                // Clicks on the popup should not reach the links of the chapter below it
+                focus_policy: FocusPolicy::Block,
+                ..default()
+            },
+            interaction: Interaction::default(),
+            popup: NotePopup,
+        }
+    }
+
+    /// Spawns the popup with the text of the note, clicking the popup closes it
+    pub fn spawn<'a>(commands: &'a mut Commands, note: &Note) -> EntityCommands<'a> {
+        let mut popup = commands.spawn(NotePopupBundle::new());
+        popup.with_children(|popup| {
+            popup.spawn(TextBundle::from_section(
+                note.text.as_str(),
+                TextStyle {
+                    font_size: Self::FONT_SIZE,
+                    color: TEXT_COLOR,
+                    ..default()
+                },
+            ));
+        });
+
+        popup
+    }
+}
+
+pub fn close_clicked_note_popups(
+    mut commands: Commands,
+    popups: Query<(Entity, &Interaction), (With<NotePopup>, Changed<Interaction>)>,
+) {
+    for (entity, interaction) in popups.iter() {
+        if *interaction == Interaction::Pressed {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::borrow::Borrow;
+use std::{borrow::Borrow, sync::Arc};
 
 use bevy::{ecs::system::EntityCommands, prelude::*};
 use common::{
@@ -8,27 +8,60 @@ use common::{
     text::TEXT_COLOR,
     utilities::despawn_screen,
 };
-use epub::{chapters::chapter_node::ChapterNode, epub::EBook, reader::EBookReader};
+use epub::{
+    chapters::chapter_node::{ChapterNode, TextRun},
+    epub::EBook,
+    reader::EBookReader,
+    resources::LinkTarget,
+    styles::{computed_style::ComputedStyle, ChapterStyles},
+};
 use library::library::UserLibrary;
 
 use crate::{
-    bundles::{ChapterNodeComponent, HeadingComponentBundle, ParagraphComponentBundle},
+    bundles::{
+        ChapterLink, ChapterNodeComponent, HeadingComponentBundle, ParagraphComponentBundle,
+    },
+    fixed_layout::{scale_fixed_layout_pages, FixedLayoutPageBundle},
+    fonts::{remove_book_fonts, BookFonts},
+    note_popup::{close_clicked_note_popups, NotePopup, NotePopupBundle},
     toolbar::ReaderToolbarBundle,
 };
 
 #[derive(Component)]
 pub struct OnReaderScreen;
 
+/// Container of the nodes created for the current chapter
+#[derive(Component)]
+struct ChapterContent;
+
+/// Reader of the opened book, chapter content is spawned again whenever it changes
+#[derive(Resource)]
+struct BookReader(EBookReader);
+
 pub struct ReaderPlugin;
 
 impl Plugin for ReaderPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(NavigationState::Reader), (reader_setup).chain())
-            // .add_systems(Update, ().chain().run_if(in_state(NavigationState::Reader)))
+            .add_systems(
+                Update,
+                (
+                    close_clicked_note_popups,
+                    follow_clicked_links,
+                    spawn_chapter_content.run_if(resource_exists_and_changed::<BookReader>),
+                    scale_fixed_layout_pages,
+                )
+                    .chain()
+                    .run_if(in_state(NavigationState::Reader)),
+            )
             // .add_systems(Update, ().run_if(in_state(NavigationState::Library)))
             .add_systems(
                 OnExit(NavigationState::Reader),
-                despawn_screen::<OnReaderScreen>,
+                (
+                    despawn_screen::<OnReaderScreen>,
+                    remove_book_fonts,
+                    remove_book_reader,
+                ),
             );
     }
 }
@@ -37,8 +70,33 @@ fn reader_setup(
     mut commands: Commands,
     main_screen_view_data: Res<MainScreenViewData>,
     user_library: Res<UserLibrary>,
+    mut font_assets: ResMut<Assets<Font>>,
 ) {
     let selected_book = user_library.selected_for_reading().clone();
+    let mut book_fonts = BookFonts::default();
+
+    if let Some(book) = selected_book {
+        match EBook::read_epub(book.path.to_string()) {
+            Ok(ebook) => {
+                book_fonts = BookFonts::load(&ebook, &mut font_assets);
+
+                match EBookReader::new(ebook) {
+                    Ok(mut reader) => {
+                        //TODO: Those are temporary, just to test how content is displayed
+                        for _ in 0..5 {
+                            if let Err(e) = reader.move_to_next_chapter() {
+                                error!("Error opening chapter: {:?}", e);
+                            }
+                        }
+
+                        commands.insert_resource(BookReader(reader));
+                    }
+                    Err(e) => error!("Error opening ebook: {:?}", e),
+                }
+            }
+            Err(e) => error!("Error reading ebook: {:?}", e),
+        }
+    }
 
     let reader_screen = commands
         .spawn((FlexContainer::new(None), OnReaderScreen))
@@ -61,106 +119,244 @@ fn reader_setup(
                 ])),
                 ..default()
             };
-            let mut chapter_content_entity = parent.spawn((
+            parent.spawn((
                 FlexContainer::new(Some(chapter_content_style)),
+                ChapterContent,
                 OnReaderScreen,
             ));
+        })
+        .id();
+
+    commands
+        .entity(main_screen_view_data.container_entity)
+        .push_children(&[reader_screen]);
+    commands.insert_resource(book_fonts);
+}
+
+fn remove_book_reader(mut commands: Commands) {
+    commands.remove_resource::<BookReader>();
+}
+
+/// Replaces the content with the current chapter of the reader, starting at its anchored element
+fn spawn_chapter_content(
+    mut commands: Commands,
+    book_reader: Res<BookReader>,
+    book_fonts: Res<BookFonts>,
+    mut image_assets: ResMut<Assets<Image>>,
+    chapter_content: Query<Entity, With<ChapterContent>>,
+) {
+    let Ok(chapter_content_entity) = chapter_content.get_single() else {
+        return;
+    };
+    let reader = &book_reader.0;
+
+    // Pre-paginated pages are shown whole, scaled to the window, instead of being reflowed
+    let fixed_layout_page = reader
+        .current_fixed_layout_page()
+        .unwrap_or_else(|e| {
+            error!("Error reading fixed-layout page: {:?}", e);
+            None
+        })
+        .and_then(|page| FixedLayoutPageBundle::load(&page, &mut image_assets));
+
+    let chapter = reader.current_chapter();
+    let chapter_styles = reader.current_chapter_styles().unwrap_or_else(|e| {
+        error!("Error reading chapter styles: {:?}", e);
+        ChapterStyles::default()
+    });
+    let anchor_node = reader.current_anchor_node();
+
+    let mut chapter_content_entity = commands.entity(chapter_content_entity);
+    chapter_content_entity.despawn_descendants();
+
+    if let Some(page_bundle) = fixed_layout_page {
+        chapter_content_entity.with_children(move |content_container_node| {
+            page_bundle.spawn(content_container_node);
+        });
+    } else if let Some(body_node) = chapter.get_body() {
+        chapter_content_entity.with_children(|content_container_node| {
+            let chapter_content_nodes = create_chapter_content_nodes(
+                &body_node,
+                &chapter_styles,
+                &book_fonts,
+                anchor_node.as_ref(),
+            );
+
+            content_container_node.spawn(TextBundle::from_section(
+                "ratatatattatat",
+                TextStyle {
+                    font_size: 24.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                },
+            ));
 
-            if let Some(book) = selected_book {
-                let ebook = match EBook::read_epub(book.path.to_string()) {
-                    Ok(ebook) => {
-                        // println!("SUCCESS");
-                        // println!("{:?}", ebook.table_of_contents);
-                        Some(ebook)
+            println!("{:?}", chapter_content_nodes);
+
+            for node in chapter_content_nodes.into_iter().take(20) {
+                match node {
+                    ChapterNodeComponent::Paragraph(bundle) => {
+                        let mut paragraph = content_container_node.spawn(bundle.node);
+                        if let Some(link) = bundle.link {
+                            paragraph.insert((link, Interaction::default()));
+                        }
                     }
-                    Err(e) => {
-                        error!("Error reading ebook: {:?}", e);
-                        None
+                    ChapterNodeComponent::Heading(bundle) => {
+                        content_container_node.spawn(bundle.node);
                     }
+                    _ => panic!("Unexpected enum variant"),
                 };
+            }
+        });
+    }
+}
 
-                let mut reader = EBookReader::new(ebook.unwrap());
-                //TODO: Those are temporary, just to test how content is displayed
-                reader.move_to_next_chapter();
-                reader.move_to_next_chapter();
-                reader.move_to_next_chapter();
-                reader.move_to_next_chapter();
-                reader.move_to_next_chapter();
-
-                let chapter = reader.current_chapter();
-
-                if let Some(body_node) = chapter.get_body() {
-                    chapter_content_entity.with_children(move |content_container_node| {
-                        let chapter_content_nodes =
-                            create_chapter_content_nodes(body_node.borrow());
-
-                        content_container_node.spawn(TextBundle::from_section(
-                            "ratatatattatat",
-                            TextStyle {
-                                font_size: 24.0,
-                                color: TEXT_COLOR,
-                                ..default()
-                            },
-                        ));
-
-                        println!("{:?}", chapter_content_nodes);
-
-                        for node in chapter_content_nodes.into_iter().take(20) {
-                            let node_to_spawn = match node {
-                                ChapterNodeComponent::Paragraph(bundle) => bundle.node,
-                                ChapterNodeComponent::Heading(bundle) => bundle.node,
-                                _ => panic!("Unexpected enum variant"),
-                            };
-
-                            content_container_node.spawn(node_to_spawn);
-                        }
-                    });
-                }
+/// Moves the reader to the target of the clicked link, notes are shown in a popup and external links are only logged
+fn follow_clicked_links(
+    mut commands: Commands,
+    links: Query<(&Interaction, &ChapterLink), Changed<Interaction>>,
+    note_popups: Query<Entity, With<NotePopup>>,
+    book_reader: Option<ResMut<BookReader>>,
+) {
+    let Some(mut book_reader) = book_reader else {
+        return;
+    };
+
+    for (interaction, link) in links.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
 
-                println!("HERE");
+        for note_popup in note_popups.iter() {
+            commands.entity(note_popup).despawn_recursive();
+        }
+
+        if link.is_note_reference {
+            match book_reader.0.note_of_link(&link.href) {
+                Ok(Some(note)) => {
+                    NotePopupBundle::spawn(&mut commands, &note).insert(OnReaderScreen);
+                    continue;
+                }
+                // References to missing notes are followed like any other link
+                Ok(None) => {}
+                Err(e) => error!("Error reading note {}: {:?}", link.href, e),
             }
-        })
-        .id();
+        }
 
-    commands
-        .entity(main_screen_view_data.container_entity)
-        .push_children(&[reader_screen]);
+        // Only internal links take the reader mutably, so clicking external ones does not spawn the content again
+        match book_reader.0.resolve_link(&link.href) {
+            LinkTarget::Internal(_) => {
+                if let Err(e) = book_reader.0.follow_link(&link.href) {
+                    error!("Error following link {}: {:?}", link.href, e);
+                }
+            }
+            LinkTarget::External(url) => info!("External link clicked: {}", url),
+        }
+    }
 }
 
-fn create_chapter_content_nodes(chapter_node: &ChapterNode) -> Vec<ChapterNodeComponent> {
+fn create_chapter_content_nodes(
+    chapter_node: &Arc<ChapterNode>,
+    chapter_styles: &ChapterStyles,
+    book_fonts: &BookFonts,
+    anchor_node: Option<&Arc<ChapterNode>>,
+) -> Vec<ChapterNodeComponent> {
     let mut chapter_nodes = vec![];
+    let parent_style = chapter_styles.computed_style(chapter_node);
+    let children = chapter_node.get_children();
+
+    // Content preceding the anchored element is skipped, so the view starts at the element the reader moved to
+    let first_child = anchor_node
+        .and_then(|anchor_node| {
+            children
+                .iter()
+                .position(|child| is_within(anchor_node, child))
+        })
+        .unwrap_or_default();
+
+    for child in children.iter().skip(first_child) {
+        let computed_style = chapter_styles.computed_style_with_parent(child, &parent_style);
+
+        if computed_style.is_hidden() {
+            continue;
+        }
 
-    for child in chapter_node.get_children().iter() {
-        let chapter_node = map_to_chapter_node_component(child.borrow());
+        let chapter_node =
+            map_to_chapter_node_component(child, chapter_styles, &computed_style, book_fonts);
         chapter_nodes.push(chapter_node);
     }
 
     chapter_nodes
 }
 
-fn map_to_chapter_node_component(chapter_node: &ChapterNode) -> ChapterNodeComponent {
+fn map_to_chapter_node_component(
+    chapter_node: &ChapterNode,
+    chapter_styles: &ChapterStyles,
+    computed_style: &ComputedStyle,
+    book_fonts: &BookFonts,
+) -> ChapterNodeComponent {
     let node: ChapterNodeComponent = match chapter_node.tag.as_str() {
-        "div" => ChapterNodeComponent::Paragraph(ParagraphComponentBundle::new(
-            chapter_node.content.borrow().read().unwrap().as_str(),
-        )),
-        "p" => ChapterNodeComponent::Paragraph(ParagraphComponentBundle::new(
-            chapter_node.content.borrow().read().unwrap().as_str(),
-        )),
-        "h1" => ChapterNodeComponent::Heading(HeadingComponentBundle::new(
-            chapter_node.content.borrow().read().unwrap().as_str(),
-        )),
-        _ => ChapterNodeComponent::Paragraph(ParagraphComponentBundle::new(
-            chapter_node.content.borrow().read().unwrap().as_str(),
-        )),
+        "div" | "p" => {
+            // Text of the elements hidden by the stylesheets, e.g. page numbers, is not displayed
+            let visible_runs = chapter_node
+                .text_runs()
+                .into_iter()
+                .filter(|run| {
+                    !run.elements
+                        .iter()
+                        .any(|element| chapter_styles.computed_style(element).is_hidden())
+                })
+                .collect::<Vec<_>>();
+
+            // Runs inside of `em`, `strong` and other inline elements may use a different face of the font
+            let font_of_run = |run: &TextRun| match run.elements.last() {
+                Some(element) => book_fonts.find(&chapter_styles.computed_style(element)),
+                None => book_fonts.find(computed_style),
+            };
+
+            // Bevy can not tell which section of the text was clicked, so the paragraph follows its first link
+            let link = visible_runs.iter().find_map(|run| run.link());
+
+            ChapterNodeComponent::Paragraph(
+                ParagraphComponentBundle::from_text_runs(&visible_runs, font_of_run)
+                    .with_computed_style(computed_style)
+                    .with_link(link.map(Arc::as_ref)),
+            )
+        }
+        "h1" => ChapterNodeComponent::Heading(
+            HeadingComponentBundle::new(chapter_node.text_content().as_str())
+                .with_computed_style(computed_style)
+                .with_font(book_fonts.find(computed_style)),
+        ),
+        _ => ChapterNodeComponent::Paragraph(
+            ParagraphComponentBundle::new(chapter_node.content.borrow().read().unwrap().as_str())
+                .with_computed_style(computed_style)
+                .with_font(book_fonts.find(computed_style)),
+        ),
     };
 
     node
 }
 
+/// Whether `node` is `ancestor` itself or one of its descendants
+fn is_within(node: &Arc<ChapterNode>, ancestor: &Arc<ChapterNode>) -> bool {
+    let mut current = Some(Arc::clone(node));
+
+    while let Some(node) = current {
+        if Arc::ptr_eq(&node, ancestor) {
+            return true;
+        }
+        current = node.get_parent().upgrade();
+    }
+
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use epub::{
-        chapters::chapter::Chapter, table_of_contents::table_of_contents_item::TableOfContentsItem,
+        chapters::chapter::Chapter, styles::stylesheet::Stylesheet,
+        table_of_contents::table_of_contents_item::TableOfContentsItem,
     };
 
     use super::*;
@@ -190,7 +386,12 @@ mod tests {
         //act
         let chapter_node = Chapter::from_item_with_content(toc_item, chapter_content.to_string());
         let body_node = chapter_node.get_body().expect("Body node not found");
-        let sut = create_chapter_content_nodes(body_node.borrow());
+        let sut = create_chapter_content_nodes(
+            &body_node,
+            &ChapterStyles::default(),
+            &BookFonts::default(),
+            None,
+        );
 
         //assert
         assert_eq!(sut.len(), 3);
@@ -210,6 +411,30 @@ mod tests {
             _ => panic!("Unexpected enum variant"),
         }
     }
+
+    #[test]
+    fn should_skip_chapter_nodes_hidden_by_stylesheets() {
+        //arrange
+        let chapter_content: &str = r#"
+            <body>
+                <h1>Chapter 1</h1>
+                <p class="pagebreak">12</p>
+                <p>'Hello there' - said Obi Wan Kenobi</p>
+            </body>
+        "#;
+        let chapter_styles =
+            ChapterStyles::from_stylesheets(&[Stylesheet::parse(".pagebreak { display: none }")]);
+        let toc_item = TableOfContentsItem::new(String::new(), String::new(), None);
+
+        //act
+        let chapter_node = Chapter::from_item_with_content(toc_item, chapter_content.to_string());
+        let body_node = chapter_node.get_body().expect("Body node not found");
+        let sut =
+            create_chapter_content_nodes(&body_node, &chapter_styles, &BookFonts::default(), None);
+
+        //assert
+        assert_eq!(sut.len(), 2);
+    }
 }
 
 // fn create_html_nodes_for_children(